@@ -1,46 +1,90 @@
 use std::collections::HashMap;
 use std::fmt::format;
 use std::ops::Deref;
-use colored::Colorize;
+use crate::core::analyzer::class::ClassSignature;
 use crate::core::analyzer::function::FunctionSignature;
+use crate::core::analyzer::pattern::{Coverage, Pattern};
 use crate::core::analyzer::scope::Scope;
+use crate::core::analyzer::symbol::{Reference, Symbol, SymbolIndex, SymbolKind};
 use crate::core::analyzer::variable::{VariableSignature, VariableType};
 use crate::core::converter::stree::STree;
+use crate::core::interp::value::Value;
 use crate::core::lexer::token_type::TokenType;
+use crate::core::module::context::ModuleContext;
+use crate::core::util::diagnostic::{codes, Diagnostic, Severity};
+use crate::core::util::location::Span;
 use crate::core::util::logger::Logger;
 
 #[derive(Debug, Clone)]
 pub struct Analyzer {
+    // Functions of the module being analyzed
     pub functions: HashMap<String, FunctionSignature>,
-    pub errors: Vec<String>,
-    pub warnings: Vec<String>,
+    // Function tables of the modules analyzed before it
+    pub modules: HashMap<String, HashMap<String, FunctionSignature>>,
+    pub classes: HashMap<String, ClassSignature>,
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
     pub log: Logger,
-    loop_depth: usize
+    loop_depth: usize,
+    // Span of the defer statement being visited
+    deferred: Option<Span>,
+    return_type: Option<VariableType>,
+    // Span of the node being visited, used to locate messages
+    span: Span,
+    // Span of the innermost block, where new declarations are visible
+    block: Span,
+    module: ModuleContext,
+    symbols: Option<SymbolIndex>
 }
 
 impl Analyzer {
     pub fn new(_debug: bool) -> Analyzer {
         Analyzer {
             functions: HashMap::new(),
+            modules: HashMap::new(),
+            classes: HashMap::new(),
             errors: Vec::new(),
             warnings: Vec::new(),
             log: Logger::new(_debug),
-            loop_depth: 0
+            loop_depth: 0,
+            deferred: None,
+            return_type: None,
+            span: Span::empty(),
+            block: Span::empty(),
+            module: ModuleContext::default(),
+            symbols: None
         }
     }
 
-    pub fn analyze(&mut self, tree: STree) -> Result<Vec<String>, (Vec<String>, Vec<String>)> {
+    // Collect declarations and references while analyzing, for the language server
+    pub fn index_symbols(&mut self) {
+        self.symbols = Some(SymbolIndex::new());
+    }
+
+    pub fn take_symbols(&mut self) -> SymbolIndex {
+        self.symbols.take().unwrap_or_default()
+    }
+
+    pub fn analyze(&mut self, tree: STree) -> Result<Vec<Diagnostic>, (Vec<Diagnostic>, Vec<Diagnostic>)> {
 
         self.collect_function_signatures(&tree);
         self.visit(&tree, &mut Scope::new());
 
+        if let Some(entry) = self.modules.remove("") {
+            self.functions = entry;
+        }
         self.print_function_table();
 
         // Detect unused Functions
-        let function_map = self.functions.clone();
-        for function in function_map.values() {
+        // Every function of the entry module has to be used, other modules only have to use their private ones
+        let mut unused: Vec<FunctionSignature> = self.functions.values().cloned().collect();
+        for table in self.modules.values() {
+            unused.extend(table.values().filter(|function| function.is_private()).cloned());
+        }
+        for function in unused {
             if !function.called {
-                self.create_warning_message(format!(
+                self.span = function.span;
+                self.create_warning_message(codes::UNUSED_FUNCTION, format!(
                         "Unused function '{}'", function.name
                     )
                 )
@@ -54,10 +98,58 @@ impl Analyzer {
         }
     }
 
+    // Analyze top level REPL input against a persistent scope
+    // Declarations from input with errors are rolled back
+    pub fn analyze_line(&mut self, nodes: &[STree], scope: &mut Scope) -> Result<Vec<Diagnostic>, (Vec<Diagnostic>, Vec<Diagnostic>)> {
+        let saved_scope = scope.clone();
+        let saved_functions = self.functions.clone();
+        let saved_classes = self.classes.clone();
+
+        for node in nodes {
+            self.collect_function_signatures(node);
+        }
+        for node in nodes {
+            self.visit(node, scope);
+        }
+
+        let warnings = std::mem::take(&mut self.warnings);
+        let errors = std::mem::take(&mut self.errors);
+
+        if !errors.is_empty() {
+            *scope = saved_scope;
+            self.functions = saved_functions;
+            self.classes = saved_classes;
+            Err((warnings, errors))
+        } else {
+            Ok(warnings)
+        }
+    }
+
+    // Type of an expression in the given scope, without keeping any messages
+    pub fn type_of(&mut self, node: &STree, scope: &mut Scope) -> Result<VariableType, Vec<Diagnostic>> {
+        let mut local = scope.clone();
+        let var_type = self.visit(node, &mut local);
+
+        self.warnings.clear();
+        let errors = std::mem::take(&mut self.errors);
+
+        match var_type {
+            Some(t) if errors.is_empty() => Ok(t),
+            _ => Err(errors),
+        }
+    }
+
     fn visit(&mut self, node: &STree, scope: &mut Scope) -> Option<VariableType> {
+        let outer = std::mem::replace(&mut self.span, node.span());
+        let var_type = self.visit_node(node, scope);
+        self.span = outer;
+        var_type
+    }
+
+    fn visit_node(&mut self, node: &STree, scope: &mut Scope) -> Option<VariableType> {
         match node {
 
-            STree::START { functions } => {
+            STree::START { functions, .. } => {
                 self.log.info("analyze()");
                 self.log.indent_inc();
 
@@ -69,23 +161,76 @@ impl Analyzer {
                 None
             }
 
-            STree::FUNCTION { function_type, return_type, name, params, body } => {
+            // Modules arrive dependencies first, so every imported table is complete
+            STree::MODULE { name, imports, declarations, .. } => {
+                self.log.info("analyze_module()");
+                self.log.indent_inc();
+
+                self.module = ModuleContext::new(name, imports);
+                self.functions = HashMap::new();
+
+                for declaration in declarations {
+                    self.collect_function_signatures(declaration);
+                }
+                let mut local = Scope::new();
+                for declaration in declarations {
+                    self.visit(declaration, &mut local);
+                }
+
+                let functions = std::mem::take(&mut self.functions);
+                self.modules.insert(name.clone(), functions);
+
+                self.log.indent_dec();
+                None
+            }
+
+            // Without the loader, imported modules are unknown and calls through them are not checked
+            STree::IMPORT { path, .. } => {
+                if let Some(alias) = path.last() {
+                    self.module.imports.insert(alias.clone(), path.join("."));
+                }
+                None
+            }
+
+            STree::FUNCTION { function_type, return_type, name, params, body, span } => {
                 self.log.info("analyze_function()");
                 self.log.indent_inc();
 
+                // Methods take 'this' first, a name no declared parameter can have
+                let is_method = params.first().is_some_and(|(param, _)| param == "this");
+
+                if let Some(function) = self.functions.get(name).filter(|_| !is_method) {
+                    let detail = function.describe();
+                    self.declare_symbol(name, SymbolKind::FUNCTION, detail, *span, Span::empty());
+                }
+
+                self.check_type(return_type, *span);
                 let mut local = Scope::new();
                 for (name, token_type) in params {
-                    let _ =local.declare_variable(name.clone(), token_type.clone(), false);
+                    self.check_type(token_type, *span);
+                    // Fields of the object a method runs on can be assigned
+                    let _ =local.declare_variable(name.clone(), token_type.clone(), is_method && name == "this", *span);
+                    if let Some(param) = local.lookup(name) {
+                        let detail = param.describe(name);
+                        self.declare_symbol(name, SymbolKind::PARAMETER, detail, *span, *span);
+                    }
                 }
 
+                let enclosing = self.return_type.replace(return_type.clone());
+                let outer = std::mem::replace(&mut self.block, *span);
                 self.visit(body, &mut local);
+                self.block = outer;
+                self.return_type = enclosing;
 
                 if *return_type != VariableType::NULL {
                     if !self.has_return(body) {
-                        self.create_error_message(format!(
+                        let diagnostic = self.error(codes::MISSING_RETURN, format!(
                             "Function '{}' declares return type {:?} but has no return statement",
                             name, return_type
-                        ));
+                        ))
+                            .with_label(format!("expected to return {:?}", return_type))
+                            .with_help("add a return statement, or declare the function as null");
+                        self.report(diagnostic);
                     }
                 }
 
@@ -93,18 +238,62 @@ impl Analyzer {
                 Some(return_type.clone())
             }
 
-            STree::BLOCK { statements } => {
+            STree::CLASS { name, fields, methods, span } => {
+                self.log.info("analyze_class()");
+                self.log.indent_inc();
+
+                if let Some(class) = self.classes.get(name).cloned() {
+                    if class.span != *span {
+                        let diagnostic = self.error(codes::INVALID_CLASS, format!("Class '{}' is declared twice", name))
+                            .with_label("duplicate class")
+                            .with_secondary(class.span, "other declaration here");
+                        self.report(diagnostic);
+                    }
+                    self.declare_symbol(name, SymbolKind::CLASS, class.describe(), *span, Span::empty());
+                }
+
+                let mut seen: Vec<&String> = Vec::new();
+                for (field, field_type) in fields {
+                    if seen.contains(&field) {
+                        let diagnostic = self.error(codes::INVALID_CLASS, format!("Field '{}' is declared twice in class '{}'", field, name))
+                            .with_label("duplicate field");
+                        self.report(diagnostic);
+                    }
+                    seen.push(field);
+
+                    self.check_type(field_type, *span);
+                    if self.contains_class(field_type, name, &mut Vec::new()) {
+                        let diagnostic = self.error(codes::INVALID_CLASS, format!("Class '{}' contains itself through field '{}'", name, field))
+                            .with_label("would have infinite size")
+                            .with_help("an object cannot hold a value of its own class");
+                        self.report(diagnostic);
+                    }
+                }
+
+                for method in methods {
+                    self.visit(method, scope);
+                }
+
+                self.log.indent_dec();
+                None
+            }
+
+            STree::BLOCK { statements, span } => {
                 self.log.info("analyze_block()");
                 self.log.indent_inc();
 
                 let mut local = Scope::new_child(scope);
 
+                let outer = std::mem::replace(&mut self.block, *span);
                 for statement in statements {
                     self.visit(statement, &mut local);
                 }
+                self.block = outer;
+                self.forget_assigned(node, scope);
 
                 if statements.is_empty() {
                     self.create_warning_message(
+                        codes::EMPTY_BLOCK,
                         "Empty Block".to_string()
                     )
                 }
@@ -113,113 +302,692 @@ impl Analyzer {
                 None
             }
 
-            STree::VAR_DECL { id, var_type, mutable, expression} => {
+            STree::VAR_DECL { id, var_type, mutable, expression, .. } => {
                 self.log.info("analyze_variable_declaration()");
                 self.log.indent_inc();
 
-                match scope.declare_variable(id.clone(), var_type.clone(), mutable.clone()) {
+                self.check_type(var_type, self.span);
+
+                match scope.declare_variable(id.clone(), var_type.clone(), mutable.clone(), self.span) {
                     Ok(_) => {}
                     Err(_) => {
                         _ =scope.mark_used(id.as_str(), false);
                         _ = scope.mark_mutability(id.as_str(), mutable.clone());
                     }
                 };
+                if let Some(variable) = scope.lookup(id) {
+                    let detail = variable.describe(id);
+                    self.declare_symbol(id, SymbolKind::VARIABLE, detail, self.span, self.block);
+                }
+
+                // Without a value the variable starts zeroed, or null when it is nullable
+                if !matches!(expression.deref(), STree::BLANK { .. }) {
+                    let value_type = self.visit(expression, scope);
+                    if let Some(value_type) = value_type {
+                        if !var_type.accepts(&value_type) {
+                            let diagnostic = Diagnostic::error(codes::TYPE_MISMATCH, format!(
+                                "Assignment type mismatch for '{}': {:?} vs {:?}",
+                                id, var_type, value_type
+                            ), expression.span())
+                                .with_label(format!("expected {:?}, found {:?}", var_type, value_type));
+                            self.report(Self::suggest_fix(diagnostic, var_type, &value_type));
+                        } else {
+                            Self::track_null(id, var_type, &value_type, scope);
+                        }
+                    }
+                }
+
+                self.log.indent_dec();
+                None
+            }
+
+            STree::VAR_ASSIGN { id, expression, .. } => {
+                self.log.info("analyze_assignment()");
+                self.log.indent_inc();
+
+                let value_type = self.visit(expression, scope);
+
+                match scope.lookup(id).cloned() {
+                    Some(variable) => {
+                        self.refer_assignment(id, &variable);
+                        self.check_assignable(id, &variable);
+
+                        if let Some(value_type) = value_type {
+                            if !variable.var_type.accepts(&value_type) {
+                                let diagnostic = Diagnostic::error(codes::TYPE_MISMATCH, format!(
+                                    "Assignment type mismatch for '{}': {:?} vs {:?}",
+                                    id, variable.var_type, value_type
+                                ), expression.span())
+                                    .with_label(format!("expected {:?}, found {:?}", variable.var_type, value_type))
+                                    .with_secondary(variable.span, format!("'{}' declared as {:?} here", id, variable.var_type));
+                                self.report(Self::suggest_fix(diagnostic, &variable.var_type, &value_type));
+                            } else {
+                                Self::track_null(id, &variable.var_type, &value_type, scope);
+                            }
+                        }
+                    }
+                    None => {
+                        let diagnostic = self.error(codes::UNDECLARED_VARIABLE, format!("Variable '{}' is not declared", id))
+                            .with_label("not found in this scope");
+                        self.report(diagnostic);
+                    }
+                }
+
+                self.log.indent_dec();
+                None
+            }
+
+            STree::INDEX_ASSIGN { array, index, expression, .. } => {
+                self.log.info("analyze_index_assignment()");
+                self.log.indent_inc();
+
+                let value_type = self.visit(expression, scope);
+                let element_type = self.index_type(array, index, true, scope);
+
+                self.check_root_assignable(array, scope);
+
+                if let (Some(element_type), Some(value_type)) = (element_type, value_type) {
+                    if !element_type.accepts(&value_type) {
+                        let diagnostic = Diagnostic::error(codes::TYPE_MISMATCH, format!(
+                            "Assignment type mismatch for array element: {:?} vs {:?}",
+                            element_type, value_type
+                        ), expression.span())
+                            .with_label(format!("expected {:?}, found {:?}", element_type, value_type));
+                        self.report(diagnostic);
+                    }
+                }
+
+                self.log.indent_dec();
+                None
+            }
+
+            STree::MEMBER_ASSIGN { object, member, expression, .. } => {
+                self.log.info("analyze_member_assignment()");
+                self.log.indent_inc();
+
+                let value_type = self.visit(expression, scope);
+                let field_type = self.member_type(object, member, scope);
+                self.check_root_assignable(object, scope);
+
+                if let (Some(field_type), Some(value_type)) = (field_type, value_type) {
+                    if !field_type.accepts(&value_type) {
+                        let diagnostic = Diagnostic::error(codes::TYPE_MISMATCH, format!(
+                            "Assignment type mismatch for field '{}': {:?} vs {:?}",
+                            member, field_type, value_type
+                        ), expression.span())
+                            .with_label(format!("expected {:?}, found {:?}", field_type, value_type));
+                        self.report(diagnostic);
+                    }
+                }
+
+                self.log.indent_dec();
+                None
+            }
+
+            STree::COMPOUND_ASSIGN { target, operator, expression, .. } => {
+                self.log.info("analyze_compound_assignment()");
+                self.log.indent_inc();
+
+                let value_type = self.visit(expression, scope);
+
+                // Elements and fields keep their type, like an assignment to them
+                let STree::ID { name: id, .. } = target.deref() else {
+                    let current = self.target_type(target, scope);
+                    self.check_root_assignable(target, scope);
+
+                    if let (Some(current), Some(value_type)) = (current, value_type) {
+                        let result = self.binary_type(current.clone(), operator, value_type);
+                        if let Some(result) = result.filter(|result| !current.accepts(result)) {
+                            let place = match target.deref() {
+                                STree::MEMBER_CALL { member, .. } => format!("field '{}'", member),
+                                _ => "array element".to_string(),
+                            };
+                            let diagnostic = Diagnostic::error(codes::TYPE_MISMATCH, format!(
+                                "Assignment type mismatch for {}: {:?} vs {:?}",
+                                place, current, result
+                            ), target.span())
+                                .with_label(format!("expected {:?}, found {:?}", current, result));
+                            self.report(diagnostic);
+                        }
+                    }
+
+                    self.log.indent_dec();
+                    return None;
+                };
+
+                match scope.lookup(id).cloned() {
+                    Some(variable) => {
+                        self.refer_assignment(id, &variable);
+                        self.check_assignable(id, &variable);
+                        _ = scope.mark_used(id, true);
+
+                        // The result is stored back, so it has to keep the variable's type
+                        let current = scope.narrowed_type(id).unwrap_or(variable.var_type.clone());
+                        let result = value_type.and_then(|value_type| self.binary_type(current, operator, value_type));
+                        if let Some(result) = result {
+                            if !variable.var_type.accepts(&result) {
+                                let diagnostic = self.error(codes::TYPE_MISMATCH, format!(
+                                    "Assignment type mismatch for '{}': {:?} vs {:?}",
+                                    id, variable.var_type, result
+                                ))
+                                    .with_label(format!("expected {:?}, found {:?}", variable.var_type, result))
+                                    .with_secondary(variable.span, format!("'{}' declared as {:?} here", id, variable.var_type));
+                                self.report(diagnostic);
+                            }
+                        }
+                    }
+                    None => {
+                        let diagnostic = self.error(codes::UNDECLARED_VARIABLE, format!("Variable '{}' is not declared", id))
+                            .with_label("not found in this scope");
+                        self.report(diagnostic);
+                    }
+                }
+
+                self.log.indent_dec();
+                None
+            }
 
-                self.visit(expression, scope);
+            // Jumps cannot leave a deferred statement, it runs while the block is already exiting
+            STree::DEFER { statement, span } => {
+                self.log.info("analyze_defer()");
+                self.log.indent_inc();
+
+                let outer = self.deferred.replace(*span);
+                let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+                self.visit(statement, scope);
+                self.loop_depth = loop_depth;
+                self.deferred = outer;
 
                 self.log.indent_dec();
                 None
             }
 
-            STree::WHILE_STMT { condition, body }
-            | STree::DO_WHILE_STMT { condition, body } => {
+            STree::RETURN_STMT { expression, .. } => {
+                self.log.info("analyze_return()");
+                self.log.indent_inc();
+
+                if let Some(defer) = self.deferred {
+                    let diagnostic = self.error(codes::INVALID_DEFER, "Return statement used inside a deferred statement".to_string())
+                        .with_label("cannot return from here")
+                        .with_secondary(defer, "deferred here")
+                        .with_help("deferred statements run while the block exits and cannot change where it goes");
+                    self.report(diagnostic);
+                }
+
+                let value_type = match expression {
+                    Some(expression) => self.visit(expression, scope),
+                    None => Some(VariableType::NULL),
+                };
+
+                match (self.return_type.clone(), value_type) {
+                    (None, _) => self.create_error_message(codes::RETURN_OUTSIDE_FUNCTION, "Return statement used outside of function".to_string()),
+                    (Some(expected), Some(found)) if !expected.accepts(&found) => {
+                        let diagnostic = self.error(codes::RETURN_MISMATCH, format!(
+                            "Return type mismatch: expected {:?}, got {:?}",
+                            expected, found
+                        ))
+                            .with_label(format!("expected {:?}, found {:?}", expected, found));
+                        self.report(diagnostic);
+                    }
+                    _ => {}
+                }
+
+                self.log.indent_dec();
+                None
+            }
+
+            STree::PRINT { expression, .. } => {
+                self.log.info("analyze_print()");
+                self.log.indent_inc();
+
+                match self.visit(expression, scope) {
+                    Some(VariableType::NULL) => {
+                        self.create_error_message(codes::PRINT_NULL, "Cannot print a null value".to_string());
+                    }
+                    Some(VariableType::ARRAY(..)) => {
+                        self.create_error_message(codes::INVALID_ARRAY, "Cannot print an array, print its elements instead".to_string());
+                    }
+                    Some(VariableType::OBJECT(class)) => {
+                        self.create_error_message(codes::INVALID_CLASS, format!("Cannot print an object of class '{}', print its fields instead", class));
+                    }
+                    Some(nullable @ VariableType::NULLABLE(_)) => self.report_nullable(&nullable),
+                    _ => {}
+                }
+
+                self.log.indent_dec();
+                None
+            }
+
+            STree::IF_STMT { condition, then_block, else_block, .. } => {
+                self.log.info("analyze_if()");
+                self.log.indent_inc();
+
+                // Null checks in the condition narrow nullable variables in the branches
+                self.check_condition("If", condition, scope);
+                let (when_true, when_false) = Self::null_checks(condition);
+
+                let mut then_scope = Scope::new_child(scope);
+                Self::narrow(&when_true, &mut then_scope);
+                self.visit(then_block, &mut then_scope);
+                if let Some(else_block) = else_block {
+                    let mut else_scope = Scope::new_child(scope);
+                    Self::narrow(&when_false, &mut else_scope);
+                    self.visit(else_block, &mut else_scope);
+                }
+                self.forget_assigned(node, scope);
+
+                // After a branch that always leaves the block, the other condition holds
+                if Self::exits(then_block) {
+                    Self::narrow(&when_false, scope);
+                }
+                if else_block.as_deref().is_some_and(Self::exits) {
+                    Self::narrow(&when_true, scope);
+                }
+
+                self.log.indent_dec();
+                None
+            }
+
+            STree::WHILE_STMT { condition, body, .. }
+            | STree::DO_WHILE_STMT { condition, body, .. } => {
                 self.log.info("analyze_while()");
                 self.log.indent_inc();
 
-                self.visit(condition, scope);
+                // Assignments in the body hold again on the next pass
+                self.forget_assigned(body, scope);
+                self.check_condition("While", condition, scope);
+
+                let mut local = Scope::new_child(scope);
+                if matches!(node, STree::WHILE_STMT { .. }) {
+                    Self::narrow(&Self::null_checks(condition).0, &mut local);
+                }
+                self.loop_depth += 1;
+                self.visit(body, &mut local);
+                self.loop_depth -= 1;
+
+                self.log.indent_dec();
+                None
+            },
+
+            STree::FOR_STMT { init, condition, step, body, .. } => {
+                self.log.info("analyze_for()");
+                self.log.indent_inc();
+
+                // Variables from the init are only visible inside the loop
+                let mut local = Scope::new_child(scope);
+                let outer = std::mem::replace(&mut self.block, self.span);
+                if let Some(init) = init {
+                    self.visit(init, &mut local);
+                }
+                self.forget_assigned(node, scope);
+                self.forget_assigned(node, &mut local);
+                self.check_condition("For", condition, &mut local);
 
                 self.loop_depth += 1;
-                let mut local = Scope::new();
                 self.visit(body, &mut local);
                 self.loop_depth -= 1;
 
+                if let Some(step) = step {
+                    self.visit(step, &mut local);
+                }
+                self.block = outer;
+
                 self.log.indent_dec();
                 None
             },
 
-            STree::BREAK | STree::CONTINUE | STree::REPEAT => {
+            STree::FOR_EACH_STMT { id, start, end, body, .. } => {
+                self.log.info("analyze_for_each()");
+                self.log.indent_inc();
+
+                for bound in [start, end] {
+                    if let Some(found) = self.visit(bound, scope) {
+                        if found != VariableType::INT {
+                            let diagnostic = Diagnostic::error(codes::INVALID_RANGE, format!(
+                                "Range bounds must be Int, found {:?}", found
+                            ), bound.span())
+                                .with_label(format!("expected INT, found {:?}", found));
+                            self.report(diagnostic);
+                        }
+                    }
+                }
+
+                // The loop variable is immutable, it is set by the loop on every pass
+                let mut local = Scope::new_child(scope);
+                let _ = local.declare_variable(id.clone(), VariableType::INT, false, self.span);
+                if let Some(variable) = local.lookup(id) {
+                    let detail = variable.describe(id);
+                    self.declare_symbol(id, SymbolKind::VARIABLE, detail, self.span, self.span);
+                }
+
+                self.loop_depth += 1;
+                self.visit(body, &mut local);
+                self.loop_depth -= 1;
+
+                self.log.indent_dec();
+                None
+            },
+
+            STree::MATCH_STMT { scrutinee, arms, .. } => {
+                self.log.info("analyze_match()");
+                self.log.indent_inc();
+
+                let scrutinee_type = self.visit(scrutinee, scope);
+                let scrutinee_type = match scrutinee_type {
+                    Some(VariableType::INT | VariableType::CHAR | VariableType::STRING | VariableType::BOOLEAN) => scrutinee_type,
+                    Some(found) => {
+                        let diagnostic = Diagnostic::error(codes::INVALID_PATTERN, format!(
+                            "Cannot match on {:?}, expected Int, Char, String or Bool", found
+                        ), scrutinee.span())
+                            .with_label(format!("found {:?}", found));
+                        self.report(diagnostic);
+                        None
+                    }
+                    None => None
+                };
+
+                let mut coverage = Coverage::default();
+                for arm in arms {
+                    if let STree::MATCH_ARM { pattern, body, span } = arm {
+                        self.span = *span;
+                        self.check_match_arm(pattern.as_deref(), scrutinee_type.as_ref(), &mut coverage, scope);
+                        // Declarations in an arm only exist while it runs
+                        let mut local = Scope::new_child(scope);
+                        self.visit(body, &mut local);
+                    }
+                }
+                self.forget_assigned(node, scope);
+
+                self.log.indent_dec();
+                None
+            },
+
+            STree::EXPR { left, operator, right, .. } => {
+                self.log.info("analyze_expression()");
+                self.log.indent_inc();
+
+                // The right side of && and || only runs when the left one decided nothing, x != null && x > 0
+                let left_type = self.visit(left, scope);
+                let right_type = match operator {
+                    TokenType::AND | TokenType::OR => {
+                        let (when_true, when_false) = Self::null_checks(left);
+                        let mut local = Scope::new_child(scope);
+                        Self::narrow(if *operator == TokenType::AND { &when_true } else { &when_false }, &mut local);
+                        self.visit(right, &mut local)
+                    }
+                    _ => self.visit(right, scope),
+                };
+                // Null checks and defaults stay allowed for a nullable variable that was narrowed
+                let declared = |operand: &STree, narrowed: Option<VariableType>| match operand {
+                    STree::ID { name, .. } => scope.check_variable(name).ok().or(narrowed),
+                    _ => narrowed,
+                };
+                let (left_type, right_type) = match (left.deref(), operator, right.deref()) {
+                    (_, TokenType::COALESCE, _) => (declared(left, left_type), right_type),
+                    (_, TokenType::EQUAL | TokenType::NOT_EQUAL, STree::NULL { .. }) => (declared(left, left_type), right_type),
+                    (STree::NULL { .. }, TokenType::EQUAL | TokenType::NOT_EQUAL, _) => (left_type, declared(right, right_type)),
+                    _ => (left_type, right_type),
+                };
+
+                self.log.indent_dec();
+                match (left_type, right_type) {
+                    (Some(l), Some(r)) => self.binary_type(l, operator, r),
+                    _ => None
+                }
+            },
+
+            STree::PRFX_EXPR { operator: operator @ (TokenType::INCREMENT | TokenType::DECREMENT), right: operand, .. }
+            | STree::PTFX_EXPR { left: operand, operator, .. } => {
+                self.log.info("analyze_increment()");
+                self.log.indent_inc();
+
+                let position = if matches!(node, STree::PRFX_EXPR { .. }) { "Prefix" } else { "Postfix" };
+                let operand_type = self.target_type(operand, scope);
+                self.check_root_assignable(operand, scope);
+
+                self.log.indent_dec();
+                match operand_type {
+                    Some(t @ (VariableType::INT | VariableType::FLOAT)) => Some(t),
+                    Some(other) => {
+                        let diagnostic = self.error(codes::INVALID_OPERANDS, format!(
+                            "{} {:?} requires a numeric variable, found {:?}", position, operator, other
+                        ))
+                            .with_label(format!("expected INT or FLOAT, found {:?}", other));
+                        self.report(diagnostic);
+                        None
+                    }
+                    None => None
+                }
+            },
+
+            STree::PRFX_EXPR { operator, right, .. } => {
+                self.log.info("analyze_prefix_expression()");
+                self.log.indent_inc();
+
+                let right_type = self.visit(right, scope);
+
+                self.log.indent_dec();
+                match right_type {
+                    Some(t) => self.prefix_type(operator, t),
+                    None => None
+                }
+            },
+
+            STree::BREAK { .. } | STree::CONTINUE { .. } | STree::REPEAT { .. } => {
                 self.log.info("analyze_jump()");
-                if self.loop_depth == 0 {
-                    self.create_error_message("Jump statement used outside of loop".to_string())
+                // Loops outside a deferred statement do not count, loops inside it do
+                match (self.loop_depth, self.deferred) {
+                    (0, Some(defer)) => {
+                        let diagnostic = self.error(codes::INVALID_DEFER, "Jump statement used inside a deferred statement".to_string())
+                            .with_label("cannot jump from here")
+                            .with_secondary(defer, "deferred here")
+                            .with_help("deferred statements run while the block exits and cannot change where it goes");
+                        self.report(diagnostic);
+                    }
+                    (0, None) => {
+                        let diagnostic = self.error(codes::JUMP_OUTSIDE_LOOP, "Jump statement used outside of loop".to_string())
+                            .with_label("not inside a loop");
+                        self.report(diagnostic);
+                    }
+                    _ => {}
                 }
                 None
             },
 
-            STree::FUNCTION_CALL { callee, args } => {
+            STree::FUNCTION_CALL { callee, args, .. } => {
                 self.log.info("analyze_function_call()");
                 self.log.indent_inc();
 
-                let name = match callee.deref() {
-                    STree::ID { name } => {
-                        name
+                // Methods are called on an object, which is passed as 'this' and not counted as an argument
+                // module.function calls a function of an imported module, unless a variable hides the import
+                let (name, receiver, module) = match callee.deref() {
+                    STree::ID { name, .. } if self.classes.contains_key(name) => {
+                        let object_type = self.construct(name, callee.span(), args, scope);
+                        self.log.indent_dec();
+                        return object_type;
+                    }
+                    STree::ID { name, .. } if self.builtin_len(name) => {
+                        let length_type = self.length_type(args, scope);
+                        self.log.indent_dec();
+                        return length_type;
+                    }
+                    STree::ID { name, .. } => {
+                        (name.clone(), 0, self.module.name.clone())
+                    }
+                    STree::MEMBER_CALL { object, member, .. } if self.imported(object, scope).is_some() => {
+                        let module = self.imported(object, scope).unwrap_or_default();
+                        if !self.modules.contains_key(&module) {
+                            for arg in args {
+                                self.visit(arg, scope);
+                            }
+                            self.log.indent_dec();
+                            return None
+                        }
+                        (member.clone(), 0, module)
+                    }
+                    STree::MEMBER_CALL { object, member, .. } => {
+                        match self.visit(object, scope) {
+                            Some(VariableType::OBJECT(class)) => {
+                                let module = self.classes.get(&class).map(|class| class.module.clone()).unwrap_or_default();
+                                (format!("{}.{}", class, member), 1, module)
+                            },
+                            Some(nullable @ VariableType::NULLABLE(_)) => {
+                                self.report_nullable(&nullable);
+                                self.log.indent_dec();
+                                return None
+                            }
+                            Some(found) => {
+                                let diagnostic = self.error(codes::UNDEFINED_MEMBER, format!("Cannot call method '{}' on {:?}", member, found))
+                                    .with_label("not an object");
+                                self.report(diagnostic);
+                                self.log.indent_dec();
+                                return None
+                            }
+                            None => {
+                                self.log.indent_dec();
+                                return None
+                            }
+                        }
                     }
                     _ => {
-                        self.create_error_message(format!("Callee '{:?}' is not a function call", callee));
+                        self.create_error_message(codes::NOT_CALLABLE, format!("Callee '{:?}' is not a function call", callee));
                         self.log.indent_dec();
                         return None
                     }
                 };
 
-                let called_function_option = self.functions.get(name).cloned();
+                let called_function_option = self.function_table(&module).and_then(|table| table.get(&name)).cloned();
                 let function = match called_function_option {
                     Some(func) => {
                         func
                     },
                     None => {
-                        self.create_error_message(format!("Called function '{:?}' does not exist", name));
+                        let diagnostic = match name.split_once('.') {
+                            Some((class, method)) => self.error(codes::UNDEFINED_MEMBER, format!("Class '{}' has no method '{}'", class, method))
+                                .with_label("unknown method"),
+                            None if module != self.module.name => self.error(codes::UNDEFINED_FUNCTION, format!("Module '{}' has no function '{}'", module, name))
+                                .with_label("not found in the imported module"),
+                            None => self.error(codes::UNDEFINED_FUNCTION, format!("Called function '{}' does not exist", name))
+                                .with_label("not found in this file"),
+                        };
+                        self.report(diagnostic);
                         self.log.indent_dec();
                         return None
                     }
                 };
 
-                if let Some(f) = self.functions.get_mut(name) {
-                    f.call();
+                if !self.accessible(&function, &module) {
+                    let kind = if receiver == 0 { "Function" } else { "Method" };
+                    let message = match function.function_type {
+                        TokenType::PRIVATE => format!("{} '{}' is private to module '{}'", kind, name, module),
+                        _ => format!("{} '{}' is protected, only modules in the directory of '{}' can call it", kind, name, module),
+                    };
+                    let diagnostic = self.error(codes::INACCESSIBLE_FUNCTION, message)
+                        .with_label("not accessible from this module")
+                        .with_secondary(function.span, format!("declared {} here", function.visibility()))
+                        .with_help("declare it public to call it from any module");
+                    self.report(diagnostic);
                 }
 
-                if function.parameters.len() != args.len() {
-                    self.create_error_message(format!(
-                        "Function '{}' expects {} arguments, got {}",
-                        name, function.parameters.len(), args.len()
-                    ));
+                if let Some(f) = self.function_table(&module).and_then(|table| table.get_mut(&name)) {
+                    f.call();
                 }
-
-                for (param, arg) in function.parameters.iter().zip(args.iter()) {
-                    let arg_type = self.visit(arg, scope).unwrap_or(VariableType::NULL);
-                    if *param != arg_type {
-                        self.create_error_message(format!(
-                            "Argument type mismatch in '{}': expected {:?}, got {:?}",
-                            name, param, arg_type
-                        ))
-                    }
+                if receiver == 0 && module == self.module.name {
+                    self.refer_symbol(&name, function.describe(), callee.span(), function.span);
                 }
 
+                let kind = if receiver == 0 { "Function" } else { "Method" };
+                self.check_arguments(kind, &name, &function.parameters[receiver..], args, function.span, scope);
+
                 self.log.indent_dec();
                 Some(function.return_type.clone())
             },
 
-            STree::ID { name } => {
+            STree::MEMBER_CALL { object, member, .. } => {
+                self.log.info("analyze_member()");
+                self.log.indent_inc();
+
+                let field_type = self.member_type(object, member, scope);
+
+                self.log.indent_dec();
+                field_type
+            },
+
+            STree::ID { name, .. } => {
                 if name.chars().nth(0).unwrap().is_ascii_uppercase() {
-                    self.create_warning_message(format!("Variable name '{}' should not start with uppercase letter", name));
+                    self.create_warning_message(codes::UPPERCASE_VARIABLE, format!("Variable name '{}' should not start with uppercase letter", name));
+                }
+
+                if let Some(variable) = scope.lookup(name) {
+                    let detail = variable.describe(name);
+                    let definition = variable.span;
+                    self.refer_symbol(name, detail, self.span, definition);
                 }
 
                 match scope.mark_used(name, true) {
                     Ok(_) => {}
                     Err(msg) => {
-                        self.create_error_message(msg);
+                        let diagnostic = self.error(codes::UNDECLARED_VARIABLE, msg)
+                            .with_label("not found in this scope");
+                        self.report(diagnostic);
                     }
                 }
 
-                Some(scope.check_variable(name).unwrap_or(VariableType::NULL))
+                let declared = scope.check_variable(name).ok();
+                declared.map(|declared| scope.narrowed_type(name).unwrap_or(declared))
+            },
+
+            STree::INDEX { array, index, .. } => {
+                self.log.info("analyze_index()");
+                self.log.indent_inc();
+
+                let element_type = self.index_type(array, index, false, scope);
+
+                self.log.indent_dec();
+                element_type
+            },
+
+            STree::CAST { expression, target, interpolated, .. } => {
+                self.log.info("analyze_cast()");
+                self.log.indent_inc();
+
+                let source = self.visit(expression, scope);
+                let cast_type = source.and_then(|source| self.cast_type(expression, source, target, *interpolated));
+
+                self.log.indent_dec();
+                cast_type
+            },
+
+            STree::LIT_ARRAY { elements, .. } => {
+                self.log.info("analyze_array()");
+                self.log.indent_inc();
+
+                let mut element_type = None;
+                for element in elements {
+                    let Some(found) = self.visit(element, scope) else {
+                        continue;
+                    };
+                    match &element_type {
+                        None => element_type = Some(found),
+                        Some(expected) if *expected != found => {
+                            let diagnostic = Diagnostic::error(codes::TYPE_MISMATCH, format!(
+                                "Array elements must share one type, expected {:?}, found {:?}", expected, found
+                            ), element.span())
+                                .with_label(format!("expected {:?}, found {:?}", expected, found));
+                            self.report(diagnostic);
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                if elements.is_empty() {
+                    let diagnostic = self.error(codes::INVALID_ARRAY, "Array literals need at least one element".to_string())
+                        .with_label("empty array");
+                    self.report(diagnostic);
+                }
+
+                self.log.indent_dec();
+                element_type.map(|element_type| VariableType::ARRAY(Box::new(element_type), elements.len()))
             },
 
             STree::LIT_INT { .. } => Some(VariableType::INT),
@@ -227,10 +995,10 @@ impl Analyzer {
             STree::LIT_CHAR { .. } => Some(VariableType::CHAR),
             STree::LIT_STRING { .. } => Some(VariableType::STRING),
             STree::LIT_BOOL { .. } => Some(VariableType::BOOLEAN),
-            STree::NULL => Some(VariableType::NULL),
+            STree::NULL { .. } => Some(VariableType::NULL),
 
-            STree::BLANK => {
-                self.create_warning_message("Unnecessary semicolons".to_string());
+            STree::BLANK { .. } => {
+                self.create_warning_message(codes::UNNECESSARY_SEMICOLON, "Unnecessary semicolons".to_string());
                 None
             }
 
@@ -240,26 +1008,654 @@ impl Analyzer {
 
 }
 
+// Expression Types
+impl Analyzer {
+
+    fn is_numeric(var_type: &VariableType) -> bool {
+        matches!(var_type, VariableType::INT | VariableType::FLOAT | VariableType::CHAR)
+    }
+
+    // char takes part in arithmetic as an int
+    fn promote(var_type: VariableType) -> VariableType {
+        match var_type {
+            VariableType::CHAR => VariableType::INT,
+            other => other
+        }
+    }
+
+    fn operator_name(operator: &TokenType) -> String {
+        match operator {
+            TokenType::PLUS => "ADD".to_string(),
+            TokenType::DASH => "SUBTRACT".to_string(),
+            TokenType::STAR => "MULTIPLY".to_string(),
+            TokenType::SLASH => "DIVIDE".to_string(),
+            TokenType::PERCENT => "MODULO".to_string(),
+            other => format!("{:?}", other)
+        }
+    }
+
+    fn binary_type(&mut self, left: VariableType, operator: &TokenType, right: VariableType) -> Option<VariableType> {
+        // Nullable values can only be compared with null or given a default, until a check narrows them
+        let compares_null = matches!(operator, TokenType::EQUAL | TokenType::NOT_EQUAL)
+            && (left == VariableType::NULL || right == VariableType::NULL);
+        if !compares_null && *operator != TokenType::COALESCE {
+            if let Some(nullable) = [&left, &right].into_iter().find(|operand| operand.is_nullable()) {
+                self.report_nullable(&nullable.clone());
+                return None;
+            }
+        }
+
+        if *operator == TokenType::COALESCE {
+            return match &left {
+                VariableType::NULLABLE(inner) if inner.accepts(&right) => Some(*inner.clone()),
+                VariableType::NULLABLE(_) if left.accepts(&right) => Some(left.clone()),
+                VariableType::NULL => Some(right),
+                _ if left.accepts(&right) => {
+                    self.create_warning_message(codes::NEVER_NULL, format!("Left side of ?? is {:?} and never null", left));
+                    Some(left)
+                }
+                _ => {
+                    self.create_error_message(codes::INVALID_OPERANDS, format!(
+                        "Default of ?? must be {:?}, found {:?}", left.non_null(), right
+                    ));
+                    None
+                }
+            };
+        }
+
+        if compares_null {
+            if left.is_nullable() || right.is_nullable() || left == right {
+                return Some(VariableType::BOOLEAN);
+            }
+            let other = if left == VariableType::NULL { right } else { left };
+            self.create_error_message(codes::INVALID_OPERANDS, format!("Cannot compare {:?} with null, it is never null", other));
+            return None;
+        }
+
+        if matches!(operator, TokenType::POWER | TokenType::ROOT) {
+            // Operands may mix, only int ^ int stays an int and a root is always a float
+            let (l, r) = (Self::promote(left.clone()), Self::promote(right.clone()));
+            if Self::is_numeric(&l) && Self::is_numeric(&r) {
+                if *operator == TokenType::POWER && l == VariableType::INT && r == VariableType::INT {
+                    return Some(VariableType::INT);
+                }
+                return Some(VariableType::FLOAT);
+            }
+            self.create_error_message(codes::INVALID_OPERANDS, format!(
+                "Invalid operands for {}: {:?} and {:?}",
+                Self::operator_name(operator), left, right
+            ));
+            None
+        } else if *operator == TokenType::PLUS && left == VariableType::STRING && right == VariableType::STRING {
+            Some(VariableType::STRING)
+        } else if operator.is_arithmetic_operator() {
+            // Mixed operands are widened, 1 + 2.0 is a float
+            let (l, r) = (Self::promote(left.clone()), Self::promote(right.clone()));
+            if Self::is_numeric(&l) && Self::is_numeric(&r) {
+                return l.common(&r);
+            }
+            self.create_error_message(codes::INVALID_OPERANDS, format!(
+                "Invalid operands for {}: {:?} and {:?}",
+                Self::operator_name(operator), left, right
+            ));
+            None
+        } else if matches!(operator, TokenType::EQUAL | TokenType::NOT_EQUAL) {
+            let common = Self::promote(left.clone()).common(&Self::promote(right.clone()));
+            if common.is_some() && !matches!(left, VariableType::ARRAY(..) | VariableType::OBJECT(..)) {
+                return Some(VariableType::BOOLEAN);
+            }
+            self.create_error_message(codes::INVALID_OPERANDS, format!("Cannot compare {:?} and {:?}", left, right));
+            None
+        } else if operator.is_relational_operator() {
+            // Strings are ordered byte by byte
+            let (l, r) = (Self::promote(left.clone()), Self::promote(right.clone()));
+            if Self::is_numeric(&l) && Self::is_numeric(&r) || l == VariableType::STRING && r == VariableType::STRING {
+                return Some(VariableType::BOOLEAN);
+            }
+            self.create_error_message(codes::INVALID_OPERANDS, format!(
+                "Comparison requires numeric types or strings, got {:?} and {:?}",
+                left, right
+            ));
+            None
+        } else if operator.is_logical_operator() {
+            if left == VariableType::BOOLEAN && right == VariableType::BOOLEAN {
+                return Some(VariableType::BOOLEAN);
+            }
+            self.create_error_message(codes::INVALID_OPERANDS, format!(
+                "Logical operator {:?} requires Bool operands",
+                operator
+            ));
+            None
+        } else {
+            self.create_error_message(codes::INVALID_OPERANDS, format!("Unsupported operator {:?}", operator));
+            None
+        }
+    }
+
+    fn prefix_type(&mut self, operator: &TokenType, right: VariableType) -> Option<VariableType> {
+        if right.is_nullable() {
+            self.report_nullable(&right);
+            return None;
+        }
+
+        match operator {
+            TokenType::NOT => {
+                if right == VariableType::BOOLEAN {
+                    return Some(VariableType::BOOLEAN);
+                }
+                self.create_error_message(codes::INVALID_OPERANDS, format!("Unary NOT requires Bool, found {:?}", right));
+                None
+            }
+            TokenType::DASH => {
+                if Self::is_numeric(&right) {
+                    return Some(Self::promote(right));
+                }
+                self.create_error_message(codes::INVALID_OPERANDS, format!("Unary negation requires a numeric type, found {:?}", right));
+                None
+            }
+            other => {
+                self.create_error_message(codes::INVALID_OPERANDS, format!("Unsupported prefix operator {:?}", other));
+                None
+            }
+        }
+    }
+
+    // Assignment statements start with their target
+    fn refer_assignment(&mut self, id: &str, variable: &VariableSignature) {
+        let target = Span {
+            end_line: self.span.start_line,
+            end_col: self.span.start_col + id.chars().count() - 1,
+            ..self.span
+        };
+        self.refer_symbol(id, variable.describe(id), target, variable.span);
+    }
+
+    // Element type of array[index], constant indexes are checked against the length
+    // Elements of arrays, or the chars of a string, which can only be read
+    fn index_type(&mut self, array: &STree, index: &STree, writing: bool, scope: &mut Scope) -> Option<VariableType> {
+        let array_type = self.visit(array, scope);
+        let index_type = self.visit(index, scope);
+
+        if let Some(found) = index_type {
+            if found != VariableType::INT {
+                let diagnostic = Diagnostic::error(codes::INVALID_INDEX, format!("Array index must be Int, found {:?}", found), index.span())
+                    .with_label(format!("expected INT, found {:?}", found));
+                self.report(diagnostic);
+            }
+        }
+
+        match array_type? {
+            VariableType::ARRAY(element, length) => {
+                if let STree::LIT_INT { value, .. } = index {
+                    if *value < 0 || *value as usize >= length {
+                        let diagnostic = Diagnostic::error(codes::INVALID_INDEX, format!(
+                            "Index {} is out of bounds for an array of length {}", value, length
+                        ), index.span())
+                            .with_label(format!("valid indexes are 0 to {}", length - 1));
+                        self.report(diagnostic);
+                    }
+                }
+                Some(*element)
+            }
+            VariableType::STRING if writing => {
+                let diagnostic = Diagnostic::error(codes::INVALID_INDEX, "Strings cannot be changed in place".to_string(), array.span())
+                    .with_label("string is read only")
+                    .with_help("build a new string with '+' instead");
+                self.report(diagnostic);
+                None
+            }
+            VariableType::STRING => Some(VariableType::CHAR),
+            found => {
+                let diagnostic = Diagnostic::error(codes::INVALID_INDEX, format!("Cannot index into {:?}", found), array.span())
+                    .with_label("not an array");
+                self.report(diagnostic);
+                None
+            }
+        }
+    }
+
+    // len is built in, unless the module declares a function of that name
+    fn builtin_len(&mut self, name: &str) -> bool {
+        let module = self.module.name.clone();
+        name == "len" && !self.function_table(&module).is_some_and(|table| table.contains_key(name))
+    }
+
+    // len(x) of a string counts its bytes, of an array its elements
+    fn length_type(&mut self, args: &[STree], scope: &mut Scope) -> Option<VariableType> {
+        let [arg] = args else {
+            let diagnostic = self.error(codes::ARGUMENT_COUNT, format!("Function 'len' expects 1 argument, got {}", args.len()))
+                .with_label("len takes one string or array");
+            self.report(diagnostic);
+            return None;
+        };
+
+        match self.visit(arg, scope)? {
+            VariableType::STRING | VariableType::ARRAY(..) => Some(VariableType::INT),
+            nullable @ VariableType::NULLABLE(_) => {
+                self.report_nullable(&nullable);
+                None
+            }
+            found => {
+                let diagnostic = Diagnostic::error(codes::ARGUMENT_TYPE, format!("Function 'len' expects a string or an array, got {:?}", found), arg.span())
+                    .with_label(format!("expected a string or an array, found {:?}", found));
+                self.report(diagnostic);
+                None
+            }
+        }
+    }
+
+    // Arguments are checked in order against the parameter types
+    fn check_arguments(&mut self, kind: &str, name: &str, parameters: &[VariableType], args: &[STree], definition: Span, scope: &mut Scope) {
+        if parameters.len() != args.len() {
+            let diagnostic = self.error(codes::ARGUMENT_COUNT, format!(
+                "{} '{}' expects {} arguments, got {}",
+                kind, name, parameters.len(), args.len()
+            ))
+                .with_label(format!("expected {} arguments", parameters.len()))
+                .with_secondary(definition, format!("{} defined here", kind.to_lowercase()));
+            self.report(diagnostic);
+        }
+
+        for (param, arg) in parameters.iter().zip(args.iter()) {
+            if let Some(arg_type) = self.visit(arg, scope) {
+                if !param.accepts(&arg_type) {
+                    let diagnostic = Diagnostic::error(codes::ARGUMENT_TYPE, format!(
+                        "Argument type mismatch in '{}': expected {:?}, got {:?}",
+                        name, param, arg_type
+                    ), arg.span())
+                        .with_label(format!("expected {:?}, found {:?}", param, arg_type))
+                        .with_note(format!("parameters of '{}' are {:?}", name, parameters));
+                    self.report(diagnostic);
+                }
+            }
+        }
+    }
+
+    // Module name of an import alias, when no variable of that name hides it
+    fn imported(&self, object: &STree, scope: &Scope) -> Option<String> {
+        match object {
+            STree::ID { name, .. } if scope.lookup(name).is_none() => self.module.imports.get(name).cloned(),
+            _ => None,
+        }
+    }
+
+    // Private functions are only visible in their module, protected ones also in the modules of the same directory
+    fn accessible(&self, function: &FunctionSignature, module: &str) -> bool {
+        let directory = |module: &str| module.rsplit_once('.').map_or(String::new(), |(directory, _)| directory.to_string());
+        match function.function_type {
+            TokenType::PRIVATE => module == self.module.name,
+            TokenType::PROTECTED => module == self.module.name || directory(module) == directory(&self.module.name),
+            _ => true,
+        }
+    }
+
+    fn function_table(&mut self, module: &str) -> Option<&mut HashMap<String, FunctionSignature>> {
+        if module == self.module.name {
+            Some(&mut self.functions)
+        } else {
+            self.modules.get_mut(module)
+        }
+    }
+
+    // Class(a, b) sets every field in declaration order, Class() leaves them zeroed
+    fn construct(&mut self, name: &str, span: Span, args: &[STree], scope: &mut Scope) -> Option<VariableType> {
+        let class = self.classes.get(name).cloned()?;
+        self.refer_symbol(name, class.describe(), span, class.span);
+
+        if !args.is_empty() {
+            self.check_arguments("Class", name, &class.field_types(), args, class.span, scope);
+        }
+        Some(VariableType::OBJECT(name.to_string()))
+    }
+
+    // Type of object.member, where the object has to be an instance of a class declaring the field
+    fn member_type(&mut self, object: &STree, member: &str, scope: &mut Scope) -> Option<VariableType> {
+        match self.visit(object, scope)? {
+            VariableType::OBJECT(name) => {
+                let class = self.classes.get(&name).cloned()?;
+                if let Some(field_type) = class.field(member) {
+                    return Some(field_type.clone());
+                }
+
+                let diagnostic = self.error(codes::UNDEFINED_MEMBER, format!("Class '{}' has no field '{}'", name, member))
+                    .with_label("unknown field")
+                    .with_secondary(class.span, format!("'{}' declared here", name));
+                self.report(diagnostic);
+                None
+            }
+            nullable @ VariableType::NULLABLE(_) => {
+                self.report_nullable(&nullable);
+                None
+            }
+            found => {
+                let diagnostic = self.error(codes::UNDEFINED_MEMBER, format!("Cannot access field '{}' of {:?}", member, found))
+                    .with_label("not an object");
+                self.report(diagnostic);
+                None
+            }
+        }
+    }
+
+    // Class names used as types have to be declared
+    fn check_type(&mut self, var_type: &VariableType, span: Span) {
+        match var_type {
+            VariableType::ARRAY(element, _) => self.check_type(element, span),
+            VariableType::OBJECT(name) if !self.classes.contains_key(name) => {
+                let diagnostic = Diagnostic::error(codes::UNDEFINED_CLASS, format!("Class '{}' does not exist", name), span)
+                    .with_label("not found in this file");
+                self.report(diagnostic);
+            }
+            _ => {}
+        }
+    }
+
+    // Whether a value of the type holds an object of the target class, directly or through fields
+    fn contains_class(&self, var_type: &VariableType, target: &str, seen: &mut Vec<String>) -> bool {
+        match var_type {
+            VariableType::ARRAY(element, _) => self.contains_class(element, target, seen),
+            VariableType::OBJECT(name) if name == target => true,
+            VariableType::OBJECT(name) if !seen.contains(name) => {
+                seen.push(name.clone());
+                match self.classes.get(name) {
+                    Some(class) => class.fields.iter().any(|(_, field_type)| self.contains_class(field_type, target, seen)),
+                    None => false,
+                }
+            }
+            _ => false
+        }
+    }
+
+    // Elements and fields belong to the variable holding them
+    // Type of a variable, element or field that is written to, a string cannot be written through its index
+    fn target_type(&mut self, target: &STree, scope: &mut Scope) -> Option<VariableType> {
+        match target {
+            STree::INDEX { array, index, .. } => self.index_type(array, index, true, scope),
+            STree::MEMBER_CALL { object, member, .. } => self.member_type(object, member, scope),
+            _ => self.visit(target, scope),
+        }
+    }
+
+    fn check_root_assignable(&mut self, target: &STree, scope: &Scope) {
+        let mut root = target;
+        loop {
+            match root {
+                STree::INDEX { array, .. } => root = array,
+                STree::MEMBER_CALL { object, .. } => root = object,
+                _ => break,
+            }
+        }
+
+        if let STree::ID { name, .. } = root {
+            if let Some(variable) = scope.lookup(name).cloned() {
+                self.check_assignable(name, &variable);
+            }
+        }
+    }
+
+    fn check_assignable(&mut self, id: &str, variable: &VariableSignature) {
+        if !variable.mutable {
+            let diagnostic = self.error(codes::IMMUTABLE_ASSIGNMENT, format!("Cannot assign to immutable variable '{}'", id))
+                .with_label("cannot assign twice to a const")
+                .with_secondary(variable.span, format!("'{}' declared as const here", id))
+                .with_help("declare it with 'var' to make it mutable");
+            self.report(diagnostic);
+        }
+    }
+
+    // Arms are tried in order, so an arm is unreachable when earlier ones already match all its values
+    fn check_match_arm(&mut self, pattern: Option<&STree>, scrutinee_type: Option<&VariableType>, coverage: &mut Coverage, scope: &mut Scope) {
+        if let Some(default) = coverage.default {
+            let diagnostic = Diagnostic::warning(codes::UNREACHABLE_PATTERN, "Unreachable match arm", pattern.map_or(self.span, |p| p.span()))
+                .with_label("never reached")
+                .with_secondary(default, "the default arm before it matches every value");
+            self.report(diagnostic);
+            return;
+        }
+
+        let Some(pattern) = pattern else {
+            coverage.default = Some(self.span);
+            if scrutinee_type.is_some_and(|t| coverage.is_exhaustive(t)) {
+                let diagnostic = Diagnostic::warning(codes::UNREACHABLE_PATTERN, "Unreachable default arm", self.span)
+                    .with_label("earlier arms already match every value");
+                self.report(diagnostic);
+            }
+            return;
+        };
+
+        // Patterns are typed like the value they are compared against
+        let bounds = match pattern {
+            STree::RANGE { start, end, .. } => vec![start.as_ref(), end.as_ref()],
+            _ => vec![pattern],
+        };
+        for bound in &bounds {
+            let found = self.visit(bound, scope);
+            if let (Some(found), Some(expected)) = (found, scrutinee_type) {
+                if found != *expected {
+                    let diagnostic = Diagnostic::error(codes::TYPE_MISMATCH, format!(
+                        "Match pattern type mismatch: expected {:?}, found {:?}", expected, found
+                    ), bound.span())
+                        .with_label(format!("expected {:?}, found {:?}", expected, found));
+                    self.report(diagnostic);
+                    return;
+                }
+            }
+        }
+
+        if matches!(pattern, STree::RANGE { .. }) && !matches!(scrutinee_type, None | Some(VariableType::INT | VariableType::CHAR)) {
+            let diagnostic = Diagnostic::error(codes::INVALID_PATTERN, "Range patterns need an Int or Char value", pattern.span())
+                .with_label("range pattern");
+            self.report(diagnostic);
+            return;
+        }
+
+        let Some(values) = Pattern::from_tree(pattern) else {
+            let diagnostic = Diagnostic::error(codes::INVALID_PATTERN, "Match patterns must be literals or ranges of literals", pattern.span())
+                .with_label("not a literal");
+            self.report(diagnostic);
+            return;
+        };
+
+        if let Some(first) = coverage.duplicate(&values) {
+            let diagnostic = Diagnostic::warning(codes::DUPLICATE_PATTERN, "Duplicate match arm", pattern.span())
+                .with_label("never reached")
+                .with_secondary(first, "same pattern matched here first");
+            self.report(diagnostic);
+        } else if values.is_empty() {
+            let diagnostic = Diagnostic::warning(codes::UNREACHABLE_PATTERN, "Empty range pattern", pattern.span())
+                .with_label("matches no values");
+            self.report(diagnostic);
+        } else if coverage.covers(&values) {
+            let diagnostic = Diagnostic::warning(codes::UNREACHABLE_PATTERN, "Unreachable match arm", pattern.span())
+                .with_label("earlier arms already match these values");
+            self.report(diagnostic);
+        }
+
+        coverage.add(values, pattern.span());
+    }
+
+    fn report_nullable(&mut self, var_type: &VariableType) {
+        let diagnostic = self.error(codes::NULLABLE_VALUE, format!("Value of type {:?} may be null", var_type))
+            .with_label("may be null here")
+            .with_help("check it with '!= null' first, or give it a default with '??'");
+        self.report(diagnostic);
+    }
+
+    // Hints for a mismatched assignment, a nullable declaration or an explicit conversion
+    fn suggest_fix(diagnostic: Diagnostic, target: &VariableType, value: &VariableType) -> Diagnostic {
+        match value {
+            VariableType::NULL if !target.is_nullable() => diagnostic.with_help(format!("declare it as {}? to allow null", target.keyword())),
+            VariableType::NULLABLE(inner) if **inner == *target => diagnostic.with_help("check the value with '!= null' first, or give it a default with '??'"),
+            _ if value.is_scalar() && target.non_null().is_scalar() => diagnostic.with_help(format!("convert it explicitly with 'as {}'", target.non_null().keyword())),
+            _ => diagnostic,
+        }
+    }
+
+    // A nullable variable is known not to be null after it was given a value of its inner type
+    fn track_null(id: &str, var_type: &VariableType, value_type: &VariableType, scope: &mut Scope) {
+        if let VariableType::NULLABLE(inner) = var_type {
+            match **inner == *value_type {
+                true => scope.narrow(id, value_type.clone()),
+                false => scope.widen(id),
+            }
+        }
+    }
+
+    fn narrow(names: &[String], scope: &mut Scope) {
+        for name in names {
+            if let Some(VariableType::NULLABLE(inner)) = scope.lookup(name).map(|variable| variable.var_type.clone()) {
+                scope.narrow(name, *inner);
+            }
+        }
+    }
+
+    // Variables a statement assigns may be null again once it ran
+    fn forget_assigned(&self, node: &STree, scope: &mut Scope) {
+        match node {
+            STree::VAR_ASSIGN { id, .. } => scope.widen(id),
+            STree::BLOCK { statements, .. } => statements.iter().for_each(|statement| self.forget_assigned(statement, scope)),
+            STree::IF_STMT { then_block, else_block, .. } => {
+                self.forget_assigned(then_block, scope);
+                if let Some(else_block) = else_block {
+                    self.forget_assigned(else_block, scope);
+                }
+            }
+            STree::WHILE_STMT { body, .. }
+            | STree::DO_WHILE_STMT { body, .. }
+            | STree::FOR_EACH_STMT { body, .. }
+            | STree::MATCH_ARM { body, .. } => self.forget_assigned(body, scope),
+            STree::FOR_STMT { init, step, body, .. } => {
+                for part in [init, step].into_iter().flatten() {
+                    self.forget_assigned(part, scope);
+                }
+                self.forget_assigned(body, scope);
+            }
+            STree::MATCH_STMT { arms, .. } => arms.iter().for_each(|arm| self.forget_assigned(arm, scope)),
+            _ => {}
+        }
+    }
+
+    // Nullable variables that are not null when the condition is true, and when it is false
+    fn null_checks(condition: &STree) -> (Vec<String>, Vec<String>) {
+        match condition {
+            STree::EXPR { left, operator, right, .. } => match (left.deref(), operator, right.deref()) {
+                (STree::ID { name, .. }, TokenType::NOT_EQUAL, STree::NULL { .. })
+                | (STree::NULL { .. }, TokenType::NOT_EQUAL, STree::ID { name, .. }) => (vec![name.clone()], Vec::new()),
+                (STree::ID { name, .. }, TokenType::EQUAL, STree::NULL { .. })
+                | (STree::NULL { .. }, TokenType::EQUAL, STree::ID { name, .. }) => (Vec::new(), vec![name.clone()]),
+                (_, TokenType::AND, _) => {
+                    let (mut when_true, _) = Self::null_checks(left);
+                    when_true.extend(Self::null_checks(right).0);
+                    (when_true, Vec::new())
+                }
+                (_, TokenType::OR, _) => {
+                    let (_, mut when_false) = Self::null_checks(left);
+                    when_false.extend(Self::null_checks(right).1);
+                    (Vec::new(), when_false)
+                }
+                _ => (Vec::new(), Vec::new()),
+            },
+            STree::PRFX_EXPR { operator: TokenType::NOT, right, .. } => {
+                let (when_true, when_false) = Self::null_checks(right);
+                (when_false, when_true)
+            }
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+
+    // Whether a statement always leaves the enclosing block
+    fn exits(node: &STree) -> bool {
+        match node {
+            STree::RETURN_STMT { .. } | STree::BREAK { .. } | STree::CONTINUE { .. } | STree::REPEAT { .. } => true,
+            STree::BLOCK { statements, .. } => statements.iter().any(Self::exits),
+            STree::IF_STMT { then_block, else_block: Some(else_block), .. } => Self::exits(then_block) && Self::exits(else_block),
+            _ => false,
+        }
+    }
+
+    // value as type, between scalar types. Constants are converted here, so a cast that cannot succeed
+    // is reported before the program runs
+    fn cast_type(&mut self, expression: &STree, source: VariableType, target: &VariableType, interpolated: bool) -> Option<VariableType> {
+        if source.is_nullable() {
+            self.report_nullable(&source);
+            return None;
+        }
+
+        if interpolated && !source.is_scalar() {
+            let diagnostic = self.error(codes::INVALID_CAST, format!("Cannot format {:?} inside a string", source))
+                .with_label("cannot be formatted")
+                .with_note("only int, float, char, boolean and string values can be interpolated");
+            self.report(diagnostic);
+            return None;
+        }
+
+        if !source.is_scalar() || !target.is_scalar() {
+            let diagnostic = self.error(codes::INVALID_CAST, format!("Cannot convert {:?} to {:?}", source, target))
+                .with_label("invalid conversion")
+                .with_note("only int, float, char, boolean and string convert with 'as'");
+            self.report(diagnostic);
+            return None;
+        }
+
+        if source == *target && !interpolated {
+            let diagnostic = Diagnostic::warning(codes::REDUNDANT_CAST, format!("Value is already {:?}", target), self.span)
+                .with_label("this conversion has no effect");
+            self.report(diagnostic);
+        } else if let Some(Err(message)) = Value::constant(expression).map(|constant| constant.cast(target)) {
+            let diagnostic = self.error(codes::INVALID_CAST, message)
+                .with_label("conversion always fails");
+            self.report(diagnostic);
+        }
+
+        Some(target.clone())
+    }
+
+    fn check_condition(&mut self, statement: &str, condition: &STree, scope: &mut Scope) {
+        if let Some(found) = self.visit(condition, scope) {
+            if found != VariableType::BOOLEAN {
+                let diagnostic = Diagnostic::error(codes::INVALID_CONDITION, format!(
+                    "{} condition must be Bool, found {:?}",
+                    statement, found
+                ), condition.span())
+                    .with_label(format!("expected BOOLEAN, found {:?}", found));
+                self.report(diagnostic);
+            }
+        }
+    }
+
+}
+
 // Helpers
 impl Analyzer {
 
-    pub fn create_warning_message(&mut self, msg: String) {
-        self.warnings.push(
-            format!(
-                "{}: {}",
-                "Warning".yellow(),
-                msg
-            )
-        );
-    }
-    pub fn create_error_message(&mut self, msg: String) {
-        self.errors.push(
-            format!(
-                "{}: {}",
-                "Error".red(),
-                msg
-            )
-        );
+    pub fn create_warning_message(&mut self, code: &'static str, msg: String) {
+        self.warnings.push(Diagnostic::warning(code, msg, self.span));
+    }
+    pub fn create_error_message(&mut self, code: &'static str, msg: String) {
+        self.errors.push(Diagnostic::error(code, msg, self.span));
+    }
+
+    // Error located at the node being visited, for adding labels before reporting
+    fn error(&self, code: &'static str, msg: String) -> Diagnostic {
+        Diagnostic::error(code, msg, self.span)
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        match diagnostic.severity {
+            Severity::ERROR => self.errors.push(diagnostic),
+            Severity::WARNING => self.warnings.push(diagnostic),
+        }
+    }
+
+    fn declare_symbol(&mut self, name: &str, kind: SymbolKind, detail: String, span: Span, scope: Span) {
+        if let Some(symbols) = &mut self.symbols {
+            symbols.symbols.push(Symbol { name: name.to_string(), kind, detail, span, scope });
+        }
+    }
+
+    fn refer_symbol(&mut self, name: &str, detail: String, span: Span, definition: Span) {
+        if let Some(symbols) = &mut self.symbols {
+            symbols.references.push(Reference { name: name.to_string(), detail, span, definition });
+        }
     }
 
     pub fn print_function_table(&mut self) {
@@ -274,13 +1670,23 @@ impl Analyzer {
 
     pub fn collect_function_signatures(&mut self, node: &STree) {
         match node {
-            STree::START { functions} => {
+            STree::START { functions, .. } => {
                 for function in functions {
                     self.collect_function_signatures(function);
                 }
             },
 
-            STree::FUNCTION { function_type, return_type, name, params, .. } => {
+            STree::CLASS { name, fields, methods, span } => {
+                // The first declaration of a class wins
+                if !self.classes.contains_key(name) {
+                    self.classes.insert(name.clone(), ClassSignature::new(name.clone(), fields.clone(), self.module.name.clone(), *span));
+                }
+                for method in methods {
+                    self.collect_function_signatures(method);
+                }
+            },
+
+            STree::FUNCTION { function_type, return_type, name, params, span, .. } => {
                 let mut param_types = Vec::new();
                 for (_, token_type) in params {
                     param_types.push(token_type.clone());
@@ -292,7 +1698,9 @@ impl Analyzer {
                         name.clone(),
                         param_types,
                         return_type.clone(),
-                        name == "main"
+                        function_type.clone(),
+                        name == "main",
+                        *span
                     )
                 );
             },
@@ -304,16 +1712,130 @@ impl Analyzer {
     fn has_return(&self, node: &STree) -> bool {
         match node {
             STree::RETURN_STMT { .. } => true,
-            STree::BLOCK { statements } => statements.iter().any(|s| self.has_return(s)),
+            STree::BLOCK { statements, .. } => statements.iter().any(|s| self.has_return(s)),
             STree::IF_STMT { then_block, else_block, .. } => {
                 let then_has = self.has_return(then_block);
                 let else_has = else_block.as_ref().map(|b| self.has_return(b)).unwrap_or(false);
                 then_has || else_has
             }
+            STree::MATCH_STMT { arms, .. } => arms.iter().any(|a| self.has_return(a)),
+            STree::MATCH_ARM { body, .. } => self.has_return(body),
             STree::FUNCTION { body, .. } => self.has_return(body),
-            STree::START { functions } => functions.iter().any(|f| self.has_return(f)),
+            STree::START { functions, .. } => functions.iter().any(|f| self.has_return(f)),
             _ => false,
         }
     }
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::converter::converter::Converter;
+    use crate::core::lexer::lexer::Lexer;
+    use crate::core::parser::parser::Parser;
+
+    fn analyze(input: &str) -> Vec<Diagnostic> {
+        let mut parser = Parser::new(Lexer::new(input.to_string()), false);
+        let tree = parser.analyze();
+        assert_eq!(parser.take_diagnostics(), Vec::new());
+
+        let stree = Converter::new(false).convert_tree(&tree).unwrap();
+        match Analyzer::new(false).analyze(stree) {
+            Ok(_) => Vec::new(),
+            Err((_, errors)) => errors,
+        }
+    }
+
+    // Modules by name, the entry module last and unnamed. Every import is relative to the program root
+    fn analyze_modules(modules: &[(&str, &str)]) -> Vec<Diagnostic> {
+        let mut program = Vec::new();
+        for (name, input) in modules {
+            let mut parser = Parser::new(Lexer::new(input.to_string()), false);
+            let tree = parser.analyze();
+            assert_eq!(parser.take_diagnostics(), Vec::new());
+
+            let Ok(STree::START { functions, span }) = Converter::new(false).convert_tree(&tree) else {
+                panic!("module '{}' did not convert", name);
+            };
+            let (imports, declarations): (Vec<STree>, Vec<STree>) = functions
+                .into_iter()
+                .partition(|declaration| matches!(declaration, STree::IMPORT { .. }));
+            let imports = imports
+                .iter()
+                .filter_map(|import| match import {
+                    STree::IMPORT { path, .. } => Some((path.last()?.clone(), path.join("."))),
+                    _ => None,
+                })
+                .collect();
+            program.push(STree::MODULE { name: name.to_string(), imports, declarations, span });
+        }
+
+        match Analyzer::new(false).analyze(STree::START { functions: program, span: Span::empty() }) {
+            Ok(_) => Vec::new(),
+            Err((_, errors)) => errors,
+        }
+    }
+
+    #[test]
+    fn private_functions_are_only_visible_in_their_module() {
+        let geo = "private int secret() { return 1; }\npublic int open() { return secret(); }";
+        let errors = analyze_modules(&[
+            ("util.geo", geo),
+            ("", "import util.geo;\npublic int main() { return geo.open(); }"),
+        ]);
+        assert_eq!(errors, Vec::new());
+
+        let errors = analyze_modules(&[
+            ("util.geo", geo),
+            ("", "import util.geo;\npublic int main() { return geo.secret(); }"),
+        ]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, codes::INACCESSIBLE_FUNCTION);
+        assert_eq!(errors[0].message, "Function 'secret' is private to module 'util.geo'");
+    }
+
+    #[test]
+    fn protected_functions_are_visible_in_the_same_directory() {
+        let geo = "protected int area(int r) { return r * r; }";
+        let errors = analyze_modules(&[
+            ("util.geo", geo),
+            ("util.shapes", "import util.geo;\npublic int square() { return geo.area(2); }"),
+            ("", "import util.shapes;\npublic int main() { return shapes.square(); }"),
+        ]);
+        assert_eq!(errors, Vec::new());
+
+        let errors = analyze_modules(&[
+            ("util.geo", geo),
+            ("", "import util.geo;\npublic int main() { return geo.area(2); }"),
+        ]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, codes::INACCESSIBLE_FUNCTION);
+    }
+
+    #[test]
+    fn jumps_cannot_leave_a_deferred_statement() {
+        let errors = analyze("public int main() { while (true) { defer break; } return 0; }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, codes::INVALID_DEFER);
+        assert!(errors[0].labels.iter().any(|label| label.message == "deferred here"));
+    }
+
+    #[test]
+    fn compound_assignment_keeps_the_element_type() {
+        let errors = analyze("public int main() { int[2] xs = [1, 2]; xs[0] += 1.5; return 0; }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, codes::TYPE_MISMATCH);
+    }
+
+    #[test]
+    fn strings_cannot_be_incremented_in_place() {
+        let errors = analyze("public int main() { string s = \"a\"; s[0]++; return 0; }");
+        assert!(errors.iter().any(|error| error.code == codes::INVALID_INDEX));
+    }
+
+    #[test]
+    fn jumps_inside_a_deferred_loop_are_allowed() {
+        let errors = analyze("public int main() { defer while (true) { break; } return 0; }");
+        assert_eq!(errors, Vec::new());
+    }
+}
@@ -0,0 +1,35 @@
+use crate::core::analyzer::variable::VariableType;
+use crate::core::util::location::Span;
+
+// Fields in declaration order, methods are kept with the functions of the declaring module as Class.method
+#[derive(Debug, Clone)]
+pub struct ClassSignature {
+    pub name: String,
+    pub fields: Vec<(String, VariableType)>,
+    pub module: String,
+    pub span: Span
+}
+
+impl ClassSignature {
+    pub fn new(name: String, fields: Vec<(String, VariableType)>, module: String, span: Span) -> ClassSignature {
+        ClassSignature {
+            name,
+            fields,
+            module,
+            span
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&VariableType> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, field_type)| field_type)
+    }
+
+    pub fn field_types(&self) -> Vec<VariableType> {
+        self.fields.iter().map(|(_, field_type)| field_type.clone()).collect()
+    }
+
+    pub fn describe(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|(name, field_type)| format!("{} {};", field_type.keyword(), name)).collect();
+        format!("class {} {{ {} }}", self.name, fields.join(" "))
+    }
+}
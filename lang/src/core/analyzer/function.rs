@@ -1,23 +1,47 @@
 use crate::core::analyzer::variable::VariableType;
+use crate::core::lexer::token_type::TokenType;
+use crate::core::util::location::Span;
 
 #[derive(Debug, Clone)]
 pub struct FunctionSignature {
     pub name: String,
     pub parameters: Vec<VariableType>,
     pub return_type: VariableType,
-    pub called: bool
+    // PUBLIC, PROTECTED or PRIVATE
+    pub function_type: TokenType,
+    pub called: bool,
+    pub span: Span
 }
 
 impl FunctionSignature {
-    pub fn new(name: String, parameters: Vec<VariableType>, return_type: VariableType, called: bool) -> FunctionSignature {
+    pub fn new(name: String, parameters: Vec<VariableType>, return_type: VariableType, function_type: TokenType, called: bool, span: Span) -> FunctionSignature {
         FunctionSignature {
             name,
             parameters,
             return_type,
-            called
+            function_type,
+            called,
+            span
         }
     }
 
+    pub fn is_private(&self) -> bool {
+        self.function_type == TokenType::PRIVATE
+    }
+
+    pub fn visibility(&self) -> &'static str {
+        match self.function_type {
+            TokenType::PRIVATE => "private",
+            TokenType::PROTECTED => "protected",
+            _ => "public",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|param| param.keyword()).collect();
+        format!("{} {}({})", self.return_type.keyword(), self.name, params.join(", "))
+    }
+
     pub fn call(&mut self) {
         self.called = true;
     }
@@ -1,4 +1,7 @@
 pub mod scope;
 pub mod variable;
 pub mod analyzer;
-pub mod function;
\ No newline at end of file
+pub mod function;
+pub mod symbol;
+pub mod pattern;
+pub mod class;
\ No newline at end of file
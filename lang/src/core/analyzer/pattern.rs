@@ -0,0 +1,104 @@
+use crate::core::analyzer::variable::VariableType;
+use crate::core::converter::stree::STree;
+use crate::core::lexer::token_type::TokenType;
+use crate::core::util::location::Span;
+
+// Values matched by an arm, ints, chars and booleans as an inclusive interval
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    INTERVAL(i64, i64),
+    STRING(String)
+}
+
+impl Pattern {
+    // None when the pattern is not a literal or a range of literals
+    pub fn from_tree(node: &STree) -> Option<Pattern> {
+        match node {
+            STree::RANGE { start, end, inclusive, .. } => {
+                let (Some(Pattern::INTERVAL(start, _)), Some(Pattern::INTERVAL(end, _))) = (Self::from_tree(start), Self::from_tree(end)) else {
+                    return None;
+                };
+                Some(Pattern::INTERVAL(start, if *inclusive { end } else { end - 1 }))
+            },
+            STree::LIT_STRING { value, .. } => Some(Pattern::STRING(value.clone())),
+            _ => Self::constant(node).map(|value| Pattern::INTERVAL(value, value)),
+        }
+    }
+
+    fn constant(node: &STree) -> Option<i64> {
+        match node {
+            STree::LIT_INT { value, .. } => Some(*value as i64),
+            STree::LIT_CHAR { value, .. } => Some(*value as i64),
+            STree::LIT_BOOL { value, .. } => Some(*value as i64),
+            STree::PRFX_EXPR { operator: TokenType::DASH, right, .. } => match right.as_ref() {
+                STree::LIT_INT { value, .. } => Some(-(*value as i64)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Pattern::INTERVAL(start, end) if end < start)
+    }
+
+    // Every value the type can hold, for types small enough to list
+    fn domain(var_type: &VariableType) -> Option<Pattern> {
+        match var_type {
+            VariableType::BOOLEAN => Some(Pattern::INTERVAL(0, 1)),
+            // Chars are 16 bit codes, like the i16 codegen stores them in
+            VariableType::CHAR => Some(Pattern::INTERVAL(0, 0xFFFF)),
+            VariableType::INT => Some(Pattern::INTERVAL(i32::MIN as i64, i32::MAX as i64)),
+            _ => None,
+        }
+    }
+}
+
+// Patterns of the arms seen so far in a match
+#[derive(Debug, Default)]
+pub struct Coverage {
+    patterns: Vec<(Pattern, Span)>,
+    pub default: Option<Span>
+}
+
+impl Coverage {
+    pub fn add(&mut self, pattern: Pattern, span: Span) {
+        self.patterns.push((pattern, span));
+    }
+
+    // Span of an earlier arm with the very same pattern
+    pub fn duplicate(&self, pattern: &Pattern) -> Option<Span> {
+        self.patterns.iter().find(|(seen, _)| seen == pattern).map(|(_, span)| *span)
+    }
+
+    // Whether earlier arms together already match every value of the pattern
+    pub fn covers(&self, pattern: &Pattern) -> bool {
+        match pattern {
+            Pattern::STRING(_) => self.duplicate(pattern).is_some(),
+            Pattern::INTERVAL(start, end) => {
+                let mut intervals: Vec<(i64, i64)> = self.patterns
+                    .iter()
+                    .filter_map(|(seen, _)| match seen {
+                        Pattern::INTERVAL(start, end) if start <= end => Some((*start, *end)),
+                        _ => None,
+                    })
+                    .collect();
+                intervals.sort();
+
+                // Walk up from the start until a gap is found
+                let mut next = *start;
+                for (low, high) in intervals {
+                    if low > next {
+                        break;
+                    }
+                    next = next.max(high + 1);
+                }
+                next > *end
+            }
+        }
+    }
+
+    pub fn is_exhaustive(&self, var_type: &VariableType) -> bool {
+        Pattern::domain(var_type).is_some_and(|domain| self.covers(&domain))
+    }
+}
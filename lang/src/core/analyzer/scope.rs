@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 use crate::core::analyzer::variable::{VariableSignature, VariableType};
 use crate::core::lexer::token_type::TokenType;
+use crate::core::util::location::Span;
 
 #[derive(Debug, Clone)]
 pub struct Scope {
     pub variables: HashMap<String, VariableSignature>,
+    // Nullable variables known not to be null here, None where an assignment undid that
+    pub narrowed: HashMap<String, Option<VariableType>>,
     pub parent: Option<Box<Scope>>
 }
 
@@ -12,6 +15,7 @@ impl Scope {
     pub fn new() -> Self {
         Scope {
             variables: HashMap::new(),
+            narrowed: HashMap::new(),
             parent: None,
         }
     }
@@ -19,18 +23,36 @@ impl Scope {
     pub fn new_child(parent: &Scope) -> Self {
         Scope {
             variables: HashMap::new(),
+            narrowed: HashMap::new(),
             parent: Some(Box::new(parent.clone())),
         }
     }
 
-    pub fn declare_variable(&mut self, name: String, var_type: VariableType, mutable: bool) -> Result<(), String> {
+    pub fn narrow(&mut self, name: &str, var_type: VariableType) {
+        self.narrowed.insert(name.to_string(), Some(var_type));
+    }
+
+    pub fn widen(&mut self, name: &str) {
+        self.narrowed.insert(name.to_string(), None);
+    }
+
+    // Narrowing from outer scopes does not apply to a variable declared in this one
+    pub fn narrowed_type(&self, name: &str) -> Option<VariableType> {
+        match self.narrowed.get(name) {
+            Some(narrowed) => narrowed.clone(),
+            None if self.variables.contains_key(name) => None,
+            None => self.parent.as_ref().and_then(|parent| parent.narrowed_type(name)),
+        }
+    }
+
+    pub fn declare_variable(&mut self, name: String, var_type: VariableType, mutable: bool, span: Span) -> Result<(), String> {
         if self.variables.contains_key(&name) {
             Err(format!(
                 "Variable '{}' is already declared in this scope",
                 name
             ))
         } else {
-            self.variables.insert(name, VariableSignature { var_type, used: false, mutable });
+            self.variables.insert(name, VariableSignature::new(var_type, false, mutable, span));
             Ok(())
         }
     }
@@ -45,6 +67,13 @@ impl Scope {
         }
     }
 
+    pub fn lookup(&self, name: &str) -> Option<&VariableSignature> {
+        match self.variables.get(name) {
+            Some(v) => Some(v),
+            None => self.parent.as_ref().and_then(|parent| parent.lookup(name)),
+        }
+    }
+
     pub fn check_mutability(&self, name: &String) -> Result<bool, String> {
         if let Some(v) = self.variables.get(name) {
             Ok(v.mutable.clone())
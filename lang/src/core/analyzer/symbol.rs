@@ -0,0 +1,80 @@
+use crate::core::util::location::{Location, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    VARIABLE,
+    PARAMETER,
+    FUNCTION,
+    CLASS
+}
+
+// Declared name and the part of the source it is visible in
+// Functions and classes are visible in the whole file, so their scope is empty
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub detail: String,
+    pub span: Span,
+    pub scope: Span
+}
+
+// Use of a name, resolved to its declaration
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: String,
+    pub detail: String,
+    pub span: Span,
+    pub definition: Span
+}
+
+// Declarations and references collected by the analyzer for editor tooling
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>
+}
+
+impl SymbolIndex {
+    pub fn new() -> SymbolIndex {
+        SymbolIndex::default()
+    }
+
+    // Top level functions and classes
+    pub fn declarations(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter().filter(|symbol| matches!(symbol.kind, SymbolKind::FUNCTION | SymbolKind::CLASS))
+    }
+
+    pub fn reference_at(&self, location: Location) -> Option<&Reference> {
+        self.references.iter().find(|reference| reference.span.contains(location))
+    }
+
+    pub fn symbol_at(&self, span: Span) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.span == span)
+    }
+
+    // Symbols that can be named at a location, innermost declaration first
+    pub fn visible(&self, location: Location) -> Vec<&Symbol> {
+        let position = (location.get_line(), location.get_col());
+
+        let mut visible: Vec<&Symbol> = self.symbols
+            .iter()
+            .filter(|symbol| match symbol.kind {
+                SymbolKind::FUNCTION | SymbolKind::CLASS => true,
+                _ => symbol.scope.contains(location)
+                    && (symbol.span.start_line, symbol.span.start_col) <= position,
+            })
+            .collect();
+        visible.sort_by_key(|symbol| std::cmp::Reverse((symbol.span.start_line, symbol.span.start_col)));
+
+        let mut names = Vec::new();
+        visible.retain(|symbol| {
+            if names.contains(&symbol.name) {
+                return false;
+            }
+            names.push(symbol.name.clone());
+            true
+        });
+        visible
+    }
+}
@@ -1,32 +1,148 @@
 
+use std::fmt;
+use crate::core::util::location::Span;
+
 #[derive(Debug, Clone)]
 pub struct VariableSignature {
     pub var_type: VariableType,
     pub used: bool,
-    pub mutable: bool
+    pub mutable: bool,
+    pub span: Span
 }
 
 impl VariableSignature {
-    pub fn new(var_type: VariableType, used: bool, mutable: bool) -> VariableSignature {
+    pub fn new(var_type: VariableType, used: bool, mutable: bool, span: Span) -> VariableSignature {
         VariableSignature {
             var_type,
             used,
-            mutable
+            mutable,
+            span
         }
     }
+
+    pub fn describe(&self, name: &str) -> String {
+        let binding = if self.mutable { "" } else { "const " };
+        format!("{}{} {}", binding, self.var_type.keyword(), name)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum VariableType {
     INT,
     FLOAT,
     CHAR,
     STRING,
     BOOLEAN,
+    // Element type and length
+    ARRAY(Box<VariableType>, usize),
 
     FUNCTION,
     CLASS,
 
-    OBJECT,
+    // Instance of the named class
+    OBJECT(String),
+    // Value of the inner type or null, int?
+    NULLABLE(Box<VariableType>),
     NULL
 }
+
+impl VariableType {
+    // Name of the type as written in source
+    pub fn keyword(&self) -> String {
+        match self {
+            VariableType::INT => "int".to_string(),
+            VariableType::FLOAT => "float".to_string(),
+            VariableType::CHAR => "char".to_string(),
+            VariableType::STRING => "string".to_string(),
+            VariableType::BOOLEAN => "boolean".to_string(),
+            VariableType::ARRAY(..) => {
+                let (element, sizes) = self.element_and_sizes();
+                format!("{}{}", element.keyword(), sizes)
+            },
+            VariableType::FUNCTION => "function".to_string(),
+            VariableType::CLASS => "class".to_string(),
+            VariableType::OBJECT(name) => name.clone(),
+            VariableType::NULLABLE(inner) => format!("{}?", inner.keyword()),
+            VariableType::NULL => "null".to_string(),
+        }
+    }
+
+    // Type of the value once it is known not to be null
+    pub fn non_null(&self) -> &VariableType {
+        match self {
+            VariableType::NULLABLE(inner) => inner,
+            other => other,
+        }
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        matches!(self, VariableType::NULLABLE(_))
+    }
+
+    // Values of the other type can be stored in a variable of this one, int? holds int and null,
+    // float holds int
+    pub fn accepts(&self, other: &VariableType) -> bool {
+        match (self, other) {
+            (VariableType::NULLABLE(_), VariableType::NULL) => true,
+            (VariableType::NULLABLE(inner), other) if inner.accepts(other) => true,
+            _ => other.widens_to(self),
+        }
+    }
+
+    // The only implicit conversions, char to int and int to float. Both the analyzer and codegen follow these
+    pub fn widens_to(&self, target: &VariableType) -> bool {
+        self == target || matches!(
+            (self, target),
+            (VariableType::CHAR, VariableType::INT) | (VariableType::CHAR | VariableType::INT, VariableType::FLOAT)
+        )
+    }
+
+    // Type the operands of a mixed expression are widened to, 1 + 2.0 is a float
+    pub fn common(&self, other: &VariableType) -> Option<VariableType> {
+        if self.widens_to(other) {
+            Some(other.clone())
+        } else if other.widens_to(self) {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+
+    // Types that convert into each other with as
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, VariableType::INT | VariableType::FLOAT | VariableType::CHAR | VariableType::BOOLEAN | VariableType::STRING)
+    }
+
+    // Innermost element type and the sizes as written outermost first, int[3][2]
+    fn element_and_sizes(&self) -> (&VariableType, String) {
+        let mut sizes = String::new();
+        let mut element = self;
+        while let VariableType::ARRAY(inner, length) = element {
+            sizes.push_str(&format!("[{}]", length));
+            element = inner;
+        }
+        (element, sizes)
+    }
+}
+
+// Messages name types in capitals, arrays with their sizes like INT[3][2], classes by their name
+impl fmt::Debug for VariableType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableType::ARRAY(..) => {
+                let (element, sizes) = self.element_and_sizes();
+                write!(f, "{:?}{}", element, sizes)
+            },
+            VariableType::INT => write!(f, "INT"),
+            VariableType::FLOAT => write!(f, "FLOAT"),
+            VariableType::CHAR => write!(f, "CHAR"),
+            VariableType::STRING => write!(f, "STRING"),
+            VariableType::BOOLEAN => write!(f, "BOOLEAN"),
+            VariableType::FUNCTION => write!(f, "FUNCTION"),
+            VariableType::CLASS => write!(f, "CLASS"),
+            VariableType::OBJECT(name) => write!(f, "{}", name),
+            VariableType::NULLABLE(inner) => write!(f, "{:?}?", inner),
+            VariableType::NULL => write!(f, "NULL"),
+        }
+    }
+}
\ No newline at end of file
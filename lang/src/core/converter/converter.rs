@@ -1,5 +1,6 @@
 use crate::core::{converter::stree::STree, parser::mtree::MTree, util::logger::Logger, lexer::token_type::TokenType};
 use crate::core::analyzer::variable::VariableType;
+use crate::core::util::diagnostic::{codes, Diagnostic};
 
 pub struct Converter {
     log: Logger,
@@ -11,11 +12,14 @@ impl Converter {
         Converter { log }
     }
 
-    pub fn convert_tree(&mut self, node: &MTree) -> Result<STree, String> {
-        
+    pub fn convert_tree(&mut self, node: &MTree) -> Result<STree, Diagnostic> {
+        let span = node.span;
+        let malformed = |message: &str| Diagnostic::error(codes::MALFORMED_TREE, message, span);
+        let invalid_type = |message: String| Diagnostic::error(codes::INVALID_TYPE, message, span);
+
         match &node.token.token_type {
 
-            // Program Root: All Children are Functions
+            // Program Root: All Children are Functions, Classes and Imports
             &TokenType::START => {
                 self.log.info("convert_program()");
                 self.log.indent_inc();
@@ -31,7 +35,7 @@ impl Converter {
                     self.log.indent_dec();
                 }
                 self.log.indent_dec();
-                Ok(STree::START { functions })
+                Ok(STree::START { functions, span })
             }
 
             // Expected Function Declaration Children
@@ -41,12 +45,11 @@ impl Converter {
                 self.log.info("convert_function_decl()");
                 
                 let function_type = node.children[0].token.token_type.clone();
-                let return_type_token = node.children[1].token.token_type.clone();
-                let return_type = self.token_to_variable_type(&return_type_token, true)?;
+                let return_type = self.type_node_to_variable_type(&node.children[1], true).map_err(invalid_type)?;
                 let name_node = node.children[2].token.token_type.clone();
                 let function_name: String = match &name_node {
                     TokenType::ID { name } => name.clone(),
-                    _ => return Err("Expected ID in Function Declaration".into()),
+                    _ => return Err(malformed("Expected ID in Function Declaration")),
                 };
 
                 self.log.info("convert_param_list()");
@@ -57,16 +60,15 @@ impl Converter {
                 for param_node in &params_node.children {
                     self.log.info("convert_param()");
 
-                    let type_node = param_node.children.get(0).ok_or("Param Missing Type")?;
+                    let type_node = param_node.children.get(0).ok_or_else(|| malformed("Param Missing Type"))?;
 
-                    let id_node = param_node.children.get(1).ok_or("Param Missing ID")?;
+                    let id_node = param_node.children.get(1).ok_or_else(|| malformed("Param Missing ID"))?;
 
                     let param_name = match &id_node.token.token_type {
                         TokenType::ID { name } => name,
-                        _ => return Err("Expected ID in param".into()),
+                        _ => return Err(malformed("Expected ID in param")),
                     };
-                    let param_type_token = type_node.token.token_type.clone();
-                    let param_type = self.token_to_variable_type(&param_type_token, false)?;
+                    let param_type = self.type_node_to_variable_type(type_node, false).map_err(invalid_type)?;
                     params.push((param_name.to_string(), param_type));
                 }
                 self.log.indent_dec();
@@ -86,17 +88,86 @@ impl Converter {
                         name: function_name,
                         params,
                         body: Box::new(body),
+                        span,
                     }
                 )
             }
 
+            // Expected Import Children
+            // [ ID(segment)... ]
+            TokenType::IMPORT => {
+                self.log.info("convert_import()");
+
+                let mut path = Vec::new();
+                for segment in &node.children {
+                    match &segment.token.token_type {
+                        TokenType::ID { name } => path.push(name.clone()),
+                        _ => return Err(malformed("Expected ID in import path")),
+                    }
+                }
+
+                Ok(STree::IMPORT { path, span })
+            }
+
+            // Expected Class Declaration Children
+            // [ ID(name), FIELD(Type, ID)..., FUNC_DECL... ]
+            TokenType::CLASS_DECL => {
+                self.log.info("convert_class_decl()");
+                self.log.indent_inc();
+
+                let name = match node.children.get(0).map(|id| &id.token.token_type) {
+                    Some(TokenType::ID { name }) => name.clone(),
+                    _ => return Err(malformed("Expected ID in Class Declaration")),
+                };
+
+                let mut fields = Vec::new();
+                let mut methods = Vec::new();
+                for member in &node.children[1..] {
+                    match member.token.token_type {
+                        TokenType::FIELD => {
+                            self.log.info("convert_field()");
+
+                            let type_node = member.children.get(0).ok_or_else(|| malformed("Field Missing Type"))?;
+                            let field_name = match member.children.get(1).map(|id| &id.token.token_type) {
+                                Some(TokenType::ID { name }) => name.clone(),
+                                _ => return Err(malformed("Expected ID in field")),
+                            };
+                            let field_type = self.type_node_to_variable_type(type_node, false).map_err(invalid_type)?;
+                            fields.push((field_name, field_type));
+                        },
+                        TokenType::FUNC_DECL => {
+                            let STree::FUNCTION { function_type, return_type, name: method, mut params, body, span } = self.convert_tree(member)? else {
+                                return Err(malformed("Expected Function in Class Declaration"));
+                            };
+
+                            // The object a method is called on is passed first
+                            params.insert(0, ("this".to_string(), VariableType::OBJECT(name.clone())));
+                            methods.push(STree::FUNCTION {
+                                function_type,
+                                return_type,
+                                name: format!("{}.{}", name, method),
+                                params,
+                                body,
+                                span
+                            });
+                        },
+                        // Members the parser could not read
+                        _ => {}
+                    }
+                }
+
+                self.log.indent_dec();
+
+                Ok(STree::CLASS { name, fields, methods, span })
+            }
+
             TokenType::BLOCK => {
                 let mut statements = Vec::new();
                 for child in &node.children {
                     let stmt = self.convert_tree(child)?;
                     statements.push(stmt);
                 }
-                Ok(STree::BLOCK { statements })
+                Ok(STree::BLOCK { statements, span })
             }
 
             // Expected Variable Declaration Children
@@ -108,21 +179,20 @@ impl Converter {
                 let mut mutable = true;
 
                 let type_token = node.children[0].token.token_type.clone();
-                let variable_type_token = match type_token {
-                    TokenType::VAR => self.infer_type(&node.children[2].token.token_type)?,
+                let var_type = match type_token {
+                    TokenType::VAR => self.infer_variable_type(&node.children[2]).map_err(invalid_type)?,
                     TokenType::CONST => {
                         mutable = false;
-                        self.infer_type(&node.children[2].token.token_type)?
+                        self.infer_variable_type(&node.children[2]).map_err(invalid_type)?
                     }
-                    _ => type_token
+                    _ => self.type_node_to_variable_type(&node.children[0], false).map_err(invalid_type)?
                 };
-                let var_type = self.token_to_variable_type(&variable_type_token, false)?;
 
 
-                let id_node = node.children.get(1).ok_or("Variable Missing ID")?;
+                let id_node = node.children.get(1).ok_or_else(|| malformed("Variable Missing ID"))?;
                 let id = match &id_node.token.token_type {
                     TokenType::ID { name } => name.clone(),
-                    _ => return Err("Unexpected ID in Variable".into()),
+                    _ => return Err(malformed("Unexpected ID in Variable")),
                 };
 
 
@@ -131,12 +201,13 @@ impl Converter {
                     let expression_node = &node.children[2];
                     expression = Box::new(self.convert_tree(expression_node)?);
                 } else {
-                    expression = Box::new(STree::NULL)
+                    // Declarations without a value start zeroed, nullable ones as null
+                    expression = Box::new(STree::BLANK { span: id_node.span })
                 }
 
                 self.log.indent_dec();
 
-                Ok(STree::VAR_DECL { id, var_type, mutable, expression })
+                Ok(STree::VAR_DECL { id, var_type, mutable, expression, span })
             }
 
             // Expected Assignment Children
@@ -146,46 +217,72 @@ impl Converter {
                 self.log.indent_inc();
 
                 if node.children.len() != 2 {
-                    return Err("Assignment must have left and right side".into());
+                    return Err(malformed("Assignment must have left and right side"));
                 }
 
                 let left = &node.children[0];
+                let right = self.convert_tree(&node.children[1])?;
+
+                // Array elements are assigned through their index
+                if left.token.token_type == TokenType::INDEX {
+                    let STree::INDEX { array, index, .. } = self.convert_tree(left)? else {
+                        return Err(malformed("Index must have array and index"));
+                    };
+
+                    self.log.indent_dec();
+                    return Ok(STree::INDEX_ASSIGN { array, index, expression: Box::new(right), span });
+                }
+
+                // Fields are assigned through their object
+                if left.token.token_type == TokenType::PERIOD {
+                    let STree::MEMBER_CALL { object, member, .. } = self.convert_tree(left)? else {
+                        return Err(malformed("Member access must have object and member"));
+                    };
+
+                    self.log.indent_dec();
+                    return Ok(STree::MEMBER_ASSIGN { object, member, expression: Box::new(right), span });
+                }
+
                 let id = match &left.token.token_type {
                     TokenType::ID { name } => name.clone(),
-                    _ => return Err("Left side of assignment must be an ID".into()),
+                    _ => return Err(
+                        Diagnostic::error(codes::INVALID_ASSIGNMENT_TARGET, "Left side of assignment must be an ID, an array element or a field", left.span)
+                            .with_label("cannot assign to this expression")
+                    ),
                 };
-                let right = self.convert_tree(&node.children[1])?;
 
                 self.log.indent_dec();
 
-                Ok(STree::VAR_ASSIGN { id, expression: Box::new(right) })
+                Ok(STree::VAR_ASSIGN { id, expression: Box::new(right), span })
             }
 
 
-            /*
-            TokenType::ADD_ASSIGN | TokenType::SUB_ASSIGN | TokenType::MULT_ASSIGN |TokenType::DIV_ASSIGN
+            // Expected Compound Assignment Children
+            // [ ID(name) | INDEX | PERIOD, Expression ]
+            TokenType::ADD_ASSIGN | TokenType::SUB_ASSIGN | TokenType::MULT_ASSIGN | TokenType::DIV_ASSIGN
             | TokenType::REM_ASSIGN | TokenType::POWER_ASSIGN | TokenType::ROOT_ASSIGN => {
-                self.log.info("convert_expression_assignment()");
+                self.log.info("convert_compound_assignment()");
                 self.log.indent_inc();
 
-                let variable_node = node.children.get(0).ok_or("Assignment missing left side")?;
-                let variable = self.convert_tree(variable_node)?;
-                let name = match &variable_node.token.token_type {
-                    TokenType::ID { name } => name.clone(),
-                    _ => return Err("Left side of assignment must be an ID".into()),
-                };
-
-                let right_node = node.children.get(1).ok_or("Assignment missing right side")?;
-                let expression = self.convert_tree(right_node)?;
-
-                let operator = node.token.token_type;
+                if node.children.len() != 2 {
+                    return Err(malformed("Assignment must have left and right side"));
+                }
 
-                let combined = STree::EXPR { left: Box::new(variable.clone()), operator, right: Box::new(expression) };
+                let left = &node.children[0];
+                if !left.token.token_type.is_identifier() && !matches!(left.token.token_type, TokenType::INDEX | TokenType::PERIOD) {
+                    return Err(
+                        Diagnostic::error(codes::INVALID_ASSIGNMENT_TARGET, "Left side of assignment must be an ID, an array element or a field", left.span)
+                            .with_label("cannot assign to this expression")
+                    );
+                }
+                let target = self.convert_tree(left)?;
+                let right = self.convert_tree(&node.children[1])?;
+                let operator = node.token.token_type.compound_operator().unwrap();
 
                 self.log.indent_dec();
 
-                Ok(STree::VAR_ASSIGN { id: name, expression: Box::new(combined) })
-            }*/
+                Ok(STree::COMPOUND_ASSIGN { target: Box::new(target), operator, expression: Box::new(right), span })
+            }
 
             // Expected Print Children
             // [ Expression ]
@@ -197,7 +294,7 @@ impl Converter {
                 let expression = self.convert_tree(expression_node)?;
 
                 self.log.indent_dec();
-                Ok(STree::PRINT { expression: Box::new(expression) })
+                Ok(STree::PRINT { expression: Box::new(expression), span })
             }
 
             // Expected Return Children
@@ -211,29 +308,74 @@ impl Converter {
                     Some(_) => {
                         let expression = self.convert_tree(expression_node.unwrap())?;
                         self.log.indent_dec();
-                        Ok(STree::RETURN_STMT { expression: Some(Box::new(expression)) })
+                        Ok(STree::RETURN_STMT { expression: Some(Box::new(expression)), span })
                     },
                     None => {
                         self.log.indent_dec();
-                        Ok(STree::RETURN_STMT { expression: None })
+                        Ok(STree::RETURN_STMT { expression: None, span })
                     }
                 }
             }
 
+            // Expected Defer Children
+            // [ Statement ]
+            TokenType::DEFER => {
+                self.log.info("convert_defer()");
+                self.log.indent_inc();
+
+                let statement_node = node.children.get(0).ok_or_else(|| malformed("Defer Missing Statement"))?;
+                let statement = self.convert_tree(statement_node)?;
+
+                self.log.indent_dec();
+                Ok(STree::DEFER { statement: Box::new(statement), span })
+            }
+
             // Unary Prefix Only Operators 
             TokenType::NOT => {
                 self.log.info("convert_unary_op()");
                 self.log.indent_inc();
 
                 if node.children.len() != 1 {
-                    return Err("Unary Prefix NOT must have one child".into());
+                    return Err(malformed("Unary Prefix NOT must have one child"));
                 }
 
                 let child = self.convert_tree(&node.children[0])?;
 
                 self.log.indent_dec();
 
-                Ok(STree::PRFX_EXPR { operator: TokenType::NOT, right: Box::new(child) })
+                Ok(STree::PRFX_EXPR { operator: TokenType::NOT, right: Box::new(child), span })
+            }
+
+            // Expected Increment/Decrement Children
+            // [ ID(name) | INDEX | PERIOD ]
+            TokenType::INCREMENT | TokenType::DECREMENT => {
+                self.log.info("convert_increment()");
+                self.log.indent_inc();
+
+                if node.children.len() != 1 {
+                    return Err(malformed("Increment must have one child"));
+                }
+
+                let operand = &node.children[0];
+                if !operand.token.token_type.is_identifier() && !matches!(operand.token.token_type, TokenType::INDEX | TokenType::PERIOD) {
+                    return Err(
+                        Diagnostic::error(codes::INVALID_ASSIGNMENT_TARGET, format!("{:?} target must be a variable, an array element or a field", node.token.token_type), operand.span)
+                            .with_label("cannot assign to this expression")
+                    );
+                }
+                let child = self.convert_tree(operand)?;
+                let operator = node.token.token_type.clone();
+
+                self.log.indent_dec();
+
+                // A prefix operator comes before its operand in the source
+                let token_start = (node.token.span.start_line, node.token.span.start_col);
+                let operand_start = (operand.span.start_line, operand.span.start_col);
+                if token_start < operand_start {
+                    Ok(STree::PRFX_EXPR { operator, right: Box::new(child), span })
+                } else {
+                    Ok(STree::PTFX_EXPR { left: Box::new(child), operator, span })
+                }
             }
 
             // Binary Operators
@@ -243,7 +385,8 @@ impl Converter {
             | TokenType::EQUAL | TokenType::NOT_EQUAL 
             | TokenType::LESS | TokenType::GREATER 
             | TokenType::LESS_EQUAL | TokenType::GREATER_EQUAL 
-            | TokenType::AND | TokenType::OR | TokenType::XOR => {
+            | TokenType::AND | TokenType::OR | TokenType::XOR
+            | TokenType::COALESCE => {
 
                 // Check for Unary
                 if node.children.len() == 1 {
@@ -255,7 +398,7 @@ impl Converter {
 
                     self.log.indent_dec();
 
-                    Ok(STree::PRFX_EXPR { operator, right: Box::new(child) })
+                    Ok(STree::PRFX_EXPR { operator, right: Box::new(child), span })
                 } else if node.children.len() == 2 {
                     self.log.info("convert_binary_op()");
                     self.log.indent_inc();
@@ -265,12 +408,90 @@ impl Converter {
                     let operator = node.token.token_type.clone();
 
                     self.log.indent_dec();
-                    Ok(STree::EXPR { left: Box::new(left), operator, right: Box::new(right) })
+                    Ok(STree::EXPR { left: Box::new(left), operator, right: Box::new(right), span })
                 } else {
-                    return Err("Operator must have either one or two children".into());
+                    return Err(malformed("Operator must have either one or two children"));
                 }
             },
 
+            // Expected Cast Children
+            // [ Value, Type ]
+            TokenType::AS => {
+                self.log.info("convert_cast()");
+                self.log.indent_inc();
+
+                let [value, type_node] = node.children.as_slice() else {
+                    return Err(malformed("Cast must have a value and a type"));
+                };
+                let expression = Box::new(self.convert_tree(value)?);
+                let target = self.type_node_to_variable_type(type_node, false).map_err(invalid_type)?;
+
+                self.log.indent_dec();
+                Ok(STree::CAST { expression, target, interpolated: false, span })
+            },
+
+            // Expected Template Children
+            // [ Text, Value, Text, ..., Text ]
+            // Desugars to text + (value as string) + text ..., empty text is left out
+            TokenType::TEMPLATE => {
+                self.log.info("convert_template()");
+                self.log.indent_inc();
+
+                let mut parts = Vec::new();
+                for (position, child) in node.children.iter().enumerate() {
+                    let part = self.convert_tree(child)?;
+                    match part {
+                        STree::LIT_STRING { ref value, .. } if position % 2 == 0 && value.is_empty() => continue,
+                        STree::LIT_STRING { .. } => parts.push(part),
+                        _ => {
+                            let span = part.span();
+                            parts.push(STree::CAST { expression: Box::new(part), target: VariableType::STRING, interpolated: true, span });
+                        },
+                    }
+                }
+
+                let mut parts = parts.into_iter();
+                let first = parts.next().unwrap_or(STree::LIT_STRING { value: String::new(), span });
+                let template = parts.fold(first, |left, right| {
+                    let span = left.span().to(right.span());
+                    STree::EXPR { left: Box::new(left), operator: TokenType::PLUS, right: Box::new(right), span }
+                });
+
+                self.log.indent_dec();
+                Ok(template)
+            },
+
+            // Expected Array Children
+            // [ Element... ]
+            TokenType::ARRAY => {
+                self.log.info("convert_array()");
+                self.log.indent_inc();
+
+                let mut elements = Vec::new();
+                for child in &node.children {
+                    elements.push(self.convert_tree(child)?);
+                }
+
+                self.log.indent_dec();
+                Ok(STree::LIT_ARRAY { elements, span })
+            },
+
+            // Expected Index Children
+            // [ Array, Index ]
+            TokenType::INDEX => {
+                self.log.info("convert_index()");
+                self.log.indent_inc();
+
+                if node.children.len() != 2 {
+                    return Err(malformed("Index must have array and index"));
+                }
+                let array = self.convert_tree(&node.children[0])?;
+                let index = self.convert_tree(&node.children[1])?;
+
+                self.log.indent_dec();
+                Ok(STree::INDEX { array: Box::new(array), index: Box::new(index), span })
+            },
+
             // Expected If Children
             // [ Expression, Body, Else(Else if) ]
             TokenType::IF => {
@@ -278,11 +499,11 @@ impl Converter {
                 self.log.indent_inc();
 
                 // condition
-                let condition_node = node.children.get(0).ok_or("If statement missing condition")?;
+                let condition_node = node.children.get(0).ok_or_else(|| malformed("If statement missing condition"))?;
                 let condition = self.convert_tree(condition_node)?;
 
                 // then block
-                let then_node = node.children.get(1).ok_or("If statement missing then block")?;
+                let then_node = node.children.get(1).ok_or_else(|| malformed("If statement missing then block"))?;
                 let then_block = self.convert_tree(then_node)?;
 
                 // else or else-if
@@ -298,6 +519,7 @@ impl Converter {
                     condition: Box::new(condition),
                     then_block: Box::new(then_block),
                     else_block,
+                    span,
                 })
             },
 
@@ -307,15 +529,134 @@ impl Converter {
                 self.log.info("convert_while()");
                 self.log.indent_inc();
 
-                let condition_node = node.children.get(0).ok_or("While missing condition")?;
+                let condition_node = node.children.get(0).ok_or_else(|| malformed("While missing condition"))?;
                 let condition = self.convert_tree(condition_node)?;
 
-                let body_node = node.children.get(1).ok_or("While missing body")?;
+                let body_node = node.children.get(1).ok_or_else(|| malformed("While missing body"))?;
                 let body = self.convert_tree(body_node)?;
 
                 self.log.indent_dec();
 
-                Ok(STree::WHILE_STMT { condition: Box::new(condition), body: Box::new(body) })
+                Ok(STree::WHILE_STMT { condition: Box::new(condition), body: Box::new(body), span })
+            },
+
+            // Expected For Children
+            // [ Init, Condition, Step, Body ], init and step may be SEMICOLON
+            TokenType::FOR => {
+                self.log.info("convert_for()");
+                self.log.indent_inc();
+
+                if node.children.len() != 4 {
+                    return Err(malformed("For loop must have init, condition, step and body"));
+                }
+
+                let init = match node.children[0].token.token_type {
+                    TokenType::SEMICOLON => None,
+                    _ => Some(Box::new(self.convert_tree(&node.children[0])?)),
+                };
+                let condition = self.convert_tree(&node.children[1])?;
+                let step = match node.children[2].token.token_type {
+                    TokenType::SEMICOLON => None,
+                    _ => Some(Box::new(self.convert_tree(&node.children[2])?)),
+                };
+                let body = self.convert_tree(&node.children[3])?;
+
+                self.log.indent_dec();
+
+                Ok(STree::FOR_STMT { init, condition: Box::new(condition), step, body: Box::new(body), span })
+            },
+
+            // Expected For-Each Children
+            // [ ID(name), RANGE(start, end), Body ]
+            TokenType::FOR_EACH => {
+                self.log.info("convert_for_each()");
+                self.log.indent_inc();
+
+                if node.children.len() != 3 {
+                    return Err(malformed("For-each loop must have variable, range and body"));
+                }
+
+                let id = match &node.children[0].token.token_type {
+                    TokenType::ID { name } => name.clone(),
+                    _ => return Err(malformed("Unexpected ID in For-each")),
+                };
+
+                let range = &node.children[1];
+                let inclusive = match range.token.token_type {
+                    TokenType::RANGE => false,
+                    TokenType::RANGE_INCLUSIVE => true,
+                    _ => return Err(malformed("For-each must loop over a range")),
+                };
+                if range.children.len() != 2 {
+                    return Err(malformed("Range must have start and end"));
+                }
+                let start = self.convert_tree(&range.children[0])?;
+                let end = self.convert_tree(&range.children[1])?;
+
+                let body = self.convert_tree(&node.children[2])?;
+
+                self.log.indent_dec();
+
+                Ok(STree::FOR_EACH_STMT {
+                    id,
+                    start: Box::new(start),
+                    end: Box::new(end),
+                    inclusive,
+                    body: Box::new(body),
+                    span
+                })
+            },
+
+            // Expected Match Children
+            // [ Expression, MATCH_ARM(Pattern, Body)... ]
+            TokenType::MATCH => {
+                self.log.info("convert_match()");
+                self.log.indent_inc();
+
+                let scrutinee_node = node.children.get(0).ok_or_else(|| malformed("Match missing expression"))?;
+                let scrutinee = self.convert_tree(scrutinee_node)?;
+
+                let mut arms = Vec::new();
+                for arm in &node.children[1..] {
+                    arms.push(self.convert_tree(arm)?);
+                }
+
+                self.log.indent_dec();
+
+                Ok(STree::MATCH_STMT { scrutinee: Box::new(scrutinee), arms, span })
+            },
+
+            // Expected Match Arm Children
+            // [ Pattern, Body ], the pattern is DEFAULT, RANGE(start, end) or an expression
+            TokenType::MATCH_ARM => {
+                self.log.info("convert_match_arm()");
+                self.log.indent_inc();
+
+                if node.children.len() != 2 {
+                    return Err(malformed("Match arm must have pattern and body"));
+                }
+
+                let pattern_node = &node.children[0];
+                let pattern = match pattern_node.token.token_type {
+                    TokenType::DEFAULT => None,
+                    TokenType::RANGE | TokenType::RANGE_INCLUSIVE => {
+                        if pattern_node.children.len() != 2 {
+                            return Err(malformed("Range must have start and end"));
+                        }
+                        Some(Box::new(STree::RANGE {
+                            start: Box::new(self.convert_tree(&pattern_node.children[0])?),
+                            end: Box::new(self.convert_tree(&pattern_node.children[1])?),
+                            inclusive: pattern_node.token.token_type == TokenType::RANGE_INCLUSIVE,
+                            span: pattern_node.span
+                        }))
+                    },
+                    _ => Some(Box::new(self.convert_tree(pattern_node)?)),
+                };
+                let body = self.convert_tree(&node.children[1])?;
+
+                self.log.indent_dec();
+
+                Ok(STree::MATCH_ARM { pattern, body: Box::new(body), span })
             },
 
             // Expected Do-While Children
@@ -324,20 +665,20 @@ impl Converter {
                 self.log.info("convert_while()");
                 self.log.indent_inc();
 
-                let condition_node = node.children.get(1).ok_or("While missing condition")?;
+                let condition_node = node.children.get(1).ok_or_else(|| malformed("While missing condition"))?;
                 let condition = self.convert_tree(condition_node)?;
 
-                let body_node = node.children.get(0).ok_or("While missing body")?;
+                let body_node = node.children.get(0).ok_or_else(|| malformed("While missing body"))?;
                 let body = self.convert_tree(body_node)?;
 
                 self.log.indent_dec();
 
-                Ok(STree::DO_WHILE_STMT { condition: Box::new(condition), body: Box::new(body) })
+                Ok(STree::DO_WHILE_STMT { condition: Box::new(condition), body: Box::new(body), span })
             },
 
-            TokenType::BREAK => Ok(STree::BREAK),
-            TokenType::CONTINUE => Ok(STree::CONTINUE),
-            TokenType::REPEAT => Ok(STree::REPEAT),
+            TokenType::BREAK => Ok(STree::BREAK { span }),
+            TokenType::CONTINUE => Ok(STree::CONTINUE { span }),
+            TokenType::REPEAT => Ok(STree::REPEAT { span }),
 
             // Expected Call Children
             // [ Id/Dot, Arg_List ]
@@ -346,7 +687,7 @@ impl Converter {
                 self.log.indent_inc();
 
                 
-                let callee_node = node.children.get(0).ok_or("Call missing callee")?;
+                let callee_node = node.children.get(0).ok_or_else(|| malformed("Call missing callee"))?;
                 let callee = self.convert_tree(callee_node)?;
 
                 // Remaining children are args (depends on your parser shape)
@@ -365,6 +706,7 @@ impl Converter {
                 Ok(STree::FUNCTION_CALL {
                     callee: Box::new(callee),
                     args,
+                    span,
                 })
             },
 
@@ -377,7 +719,7 @@ impl Converter {
                 let right_node = &node.children[1];
                 let member = match &right_node.token.token_type {
                     TokenType::ID { name } => name.clone(),
-                    _ => return Err("Right side of '.' must be an identifier".into()),
+                    _ => return Err(malformed("Right side of '.' must be an identifier")),
                 };
 
                 self.log.indent_dec();
@@ -385,25 +727,30 @@ impl Converter {
                 Ok(STree::MEMBER_CALL {
                     object: Box::new(left),
                     member,
+                    span,
                 })
             }
 
             // Identifier
             TokenType::ID { name } => {
                 self.log.info("convert_identifier()");
-                Ok(STree::ID { name: name.clone() })
+                Ok(STree::ID { name: name.clone(), span })
             }
 
-            TokenType::LIT_INT { value } => Ok(STree::LIT_INT { value: *value }),
-            TokenType::LIT_FLOAT { value } => Ok(STree::LIT_FLOAT { value: *value }),
-            TokenType::TRUE => Ok(STree::LIT_BOOL { value: true }),
-            TokenType::FALSE => Ok(STree::LIT_BOOL { value: false }),
+            // The object a method was called on
+            TokenType::THIS => Ok(STree::ID { name: "this".to_string(), span }),
+
+            TokenType::LIT_INT { value } => Ok(STree::LIT_INT { value: *value, span }),
+            TokenType::LIT_FLOAT { value } => Ok(STree::LIT_FLOAT { value: *value, span }),
+            TokenType::TRUE => Ok(STree::LIT_BOOL { value: true, span }),
+            TokenType::FALSE => Ok(STree::LIT_BOOL { value: false, span }),
 
-            TokenType::LIT_CHAR { value } => Ok(STree::LIT_CHAR { value: *value }),
-            TokenType::LIT_STRING { value } => Ok(STree::LIT_STRING { value: value.clone() }),
-            TokenType::NULL => Ok(STree::NULL),
+            TokenType::LIT_CHAR { value } => Ok(STree::LIT_CHAR { value: *value, span }),
+            TokenType::LIT_STRING { value } => Ok(STree::LIT_STRING { value: value.clone(), span }),
+            TokenType::NULL => Ok(STree::NULL { span }),
 
-            TokenType::SEMICOLON => Ok(STree::BLANK),
+            TokenType::SEMICOLON => Ok(STree::BLANK { span }),
+            TokenType::ERROR => Ok(STree::ERROR { span }),
 
             TokenType::INT | TokenType::FLOAT
             | TokenType::BOOLEAN
@@ -411,13 +758,13 @@ impl Converter {
             | TokenType::STRING => {
             
 
-                Ok(STree::VAR_TYPE { var_type: node.token.token_type.clone() })
+                Ok(STree::VAR_TYPE { var_type: node.token.token_type.clone(), span })
             }
 
 
             other => {
                 self.log.indent_dec();
-                Err(format!("Unrecognized token in semantic conversion: {:?}", other ))
+                Err(malformed(&format!("Unrecognized token in semantic conversion: {:?}", other)))
             }
         }
     }
@@ -429,7 +776,7 @@ impl Converter {
     pub fn infer_type(&self, literal: &TokenType) -> Result<TokenType, String> {
         match literal {
             TokenType::LIT_STRING { .. } => Ok(TokenType::STRING),
-            TokenType::CHAR { .. } => Ok(TokenType::CHAR),
+            TokenType::LIT_CHAR { .. } => Ok(TokenType::CHAR),
             TokenType::LIT_INT { .. } => Ok(TokenType::INT),
             TokenType::LIT_FLOAT { .. } => Ok(TokenType::FLOAT),
             TokenType::TRUE | TokenType::FALSE => Ok(TokenType::BOOLEAN),
@@ -442,6 +789,45 @@ impl Converter {
         }
     }
     
+    // Type of an implicit declaration, from a literal or an array of literals
+    pub fn infer_variable_type(&self, node: &MTree) -> Result<VariableType, String> {
+        if node.token.token_type == TokenType::ARRAY {
+            let first = node.children.first().ok_or("Cannot infer the type of an empty array")?;
+            let element = self.infer_variable_type(first)?;
+            return Ok(VariableType::ARRAY(Box::new(element), node.children.len()));
+        }
+
+        let token_type = self.infer_type(&node.token.token_type)?;
+        self.token_to_variable_type(&token_type, false)
+    }
+
+    // Children of the type token are an optional '?' and the array sizes, outermost first
+    pub fn type_node_to_variable_type(&self, node: &MTree, allow_null: bool) -> Result<VariableType, String> {
+        let mut var_type = self.token_to_variable_type(&node.token.token_type, allow_null)?;
+
+        let mut sizes = node.children.as_slice();
+        if let Some((first, rest)) = sizes.split_first() {
+            if first.token.token_type == TokenType::QUESTION {
+                if var_type == VariableType::NULL {
+                    return Err("Type null cannot be nullable".to_string());
+                }
+                var_type = VariableType::NULLABLE(Box::new(var_type));
+                sizes = rest;
+            }
+        }
+
+        for size in sizes.iter().rev() {
+            match size.token.token_type {
+                TokenType::LIT_INT { value } if value > 0 => {
+                    var_type = VariableType::ARRAY(Box::new(var_type), value as usize);
+                },
+                _ => return Err("Array size must be a positive integer".to_string()),
+            }
+        }
+
+        Ok(var_type)
+    }
+
     pub fn token_to_variable_type(&self, token_type: &TokenType, allow_null: bool) -> Result<VariableType, String> {
         match token_type {
             TokenType::STRING => Ok(VariableType::STRING),
@@ -449,6 +835,7 @@ impl Converter {
             TokenType::INT => Ok(VariableType::INT),
             TokenType::FLOAT => Ok(VariableType::FLOAT),
             TokenType::BOOLEAN => Ok(VariableType::BOOLEAN),
+            TokenType::ID { name } => Ok(VariableType::OBJECT(name.clone())),
             
             TokenType::NULL => {
                 if allow_null {
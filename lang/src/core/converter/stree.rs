@@ -2,46 +2,73 @@
 
 use crate::core::analyzer::variable::VariableType;
 use crate::core::lexer::token_type::TokenType;
+use crate::core::util::location::Span;
 
 // Semantic AST
 #[derive(Debug, Clone, PartialEq)]
 pub enum STree {
-    START { functions: Vec<STree> },
-    FUNCTION { function_type: TokenType, return_type: VariableType, name: String, params: Vec<(String, VariableType)>, body: Box<STree> },
-    BLOCK { statements: Vec<STree> },
-    VAR_TYPE { var_type: TokenType },
+    START { functions: Vec<STree>, span: Span },
+    FUNCTION { function_type: TokenType, return_type: VariableType, name: String, params: Vec<(String, VariableType)>, body: Box<STree>, span: Span },
+    // Declarations of one source file, imports map the name used in the file to the module name
+    MODULE { name: String, imports: Vec<(String, String)>, declarations: Vec<STree>, span: Span },
+    IMPORT { path: Vec<String>, span: Span },
+    // Methods are functions named Class.method, taking the object as their first parameter 'this'
+    CLASS { name: String, fields: Vec<(String, VariableType)>, methods: Vec<STree>, span: Span },
+    BLOCK { statements: Vec<STree>, span: Span },
+    VAR_TYPE { var_type: TokenType, span: Span },
 
     // Expressions
-    EXPR { left: Box<STree>, operator: TokenType, right: Box<STree> },
-    PRFX_EXPR { operator: TokenType, right: Box<STree> },
-    PTFX_EXPR { left: Box<STree>, operator: TokenType },
+    EXPR { left: Box<STree>, operator: TokenType, right: Box<STree>, span: Span },
+    PRFX_EXPR { operator: TokenType, right: Box<STree>, span: Span },
+    PTFX_EXPR { left: Box<STree>, operator: TokenType, span: Span },
+    INDEX { array: Box<STree>, index: Box<STree>, span: Span },
+    // Interpolated casts format a value inside a string template
+    CAST { expression: Box<STree>, target: VariableType, interpolated: bool, span: Span },
 
     // Literals
-    ID { name: String },
-    LIT_INT { value: i32 },
-    LIT_FLOAT { value: f32 },
-    LIT_BOOL { value: bool },
-    LIT_STRING { value: String },
-    LIT_CHAR { value: char },
+    ID { name: String, span: Span },
+    LIT_INT { value: i32, span: Span },
+    LIT_FLOAT { value: f32, span: Span },
+    LIT_BOOL { value: bool, span: Span },
+    LIT_STRING { value: String, span: Span },
+    LIT_CHAR { value: char, span: Span },
+    LIT_ARRAY { elements: Vec<STree>, span: Span },
+
+    // Patterns
+    RANGE { start: Box<STree>, end: Box<STree>, inclusive: bool, span: Span },
 
     // Statements
-    VAR_DECL { id: String, var_type: VariableType, mutable: bool, expression: Box<STree> },
-    VAR_ASSIGN { id: String, expression: Box<STree> },
-    RETURN_STMT { expression: Option<Box<STree>>},
-    IF_STMT { condition: Box<STree>, then_block: Box<STree>, else_block: Option<Box<STree>> },
-    WHILE_STMT { condition: Box<STree>, body: Box<STree> },
-    DO_WHILE_STMT { condition: Box<STree>, body: Box<STree> },
-    BREAK,
-    CONTINUE,
-    REPEAT,
+    VAR_DECL { id: String, var_type: VariableType, mutable: bool, expression: Box<STree>, span: Span },
+    VAR_ASSIGN { id: String, expression: Box<STree>, span: Span },
+    INDEX_ASSIGN { array: Box<STree>, index: Box<STree>, expression: Box<STree>, span: Span },
+    MEMBER_ASSIGN { object: Box<STree>, member: String, expression: Box<STree>, span: Span },
+    // The target is a variable, an array element or a field
+    COMPOUND_ASSIGN { target: Box<STree>, operator: TokenType, expression: Box<STree>, span: Span },
+    RETURN_STMT { expression: Option<Box<STree>>, span: Span },
+    // Runs its statement when the enclosing block exits, latest first
+    DEFER { statement: Box<STree>, span: Span },
+    IF_STMT { condition: Box<STree>, then_block: Box<STree>, else_block: Option<Box<STree>>, span: Span },
+    WHILE_STMT { condition: Box<STree>, body: Box<STree>, span: Span },
+    DO_WHILE_STMT { condition: Box<STree>, body: Box<STree>, span: Span },
+    FOR_STMT { init: Option<Box<STree>>, condition: Box<STree>, step: Option<Box<STree>>, body: Box<STree>, span: Span },
+    FOR_EACH_STMT { id: String, start: Box<STree>, end: Box<STree>, inclusive: bool, body: Box<STree>, span: Span },
+    // The default arm has no pattern
+    MATCH_STMT { scrutinee: Box<STree>, arms: Vec<STree>, span: Span },
+    MATCH_ARM { pattern: Option<Box<STree>>, body: Box<STree>, span: Span },
+    BREAK { span: Span },
+    CONTINUE { span: Span },
+    REPEAT { span: Span },
 
     // Calls
-    FUNCTION_CALL { callee: Box<STree>, args: Vec<STree> },
-    MEMBER_CALL { object: Box<STree>, member: String },
+    FUNCTION_CALL { callee: Box<STree>, args: Vec<STree>, span: Span },
+    MEMBER_CALL { object: Box<STree>, member: String, span: Span },
+
+    NULL { span: Span },
+    BLANK { span: Span },
+    PRINT { expression: Box<STree>, span: Span },
 
-    NULL,
-    BLANK,
-    PRINT { expression: Box<STree> }
+    // Source the parser skipped over after a syntax error
+    ERROR { span: Span }
 }
 
 impl STree {
@@ -55,4 +82,52 @@ impl STree {
             | STree::LIT_STRING { .. }
         )
     }
-}
\ No newline at end of file
+
+    pub fn span(&self) -> Span {
+        match self {
+            STree::START { span, .. }
+            | STree::FUNCTION { span, .. }
+            | STree::CLASS { span, .. }
+            | STree::MODULE { span, .. }
+            | STree::IMPORT { span, .. }
+            | STree::BLOCK { span, .. }
+            | STree::VAR_TYPE { span, .. }
+            | STree::EXPR { span, .. }
+            | STree::PRFX_EXPR { span, .. }
+            | STree::PTFX_EXPR { span, .. }
+            | STree::INDEX { span, .. }
+            | STree::CAST { span, .. }
+            | STree::ID { span, .. }
+            | STree::LIT_INT { span, .. }
+            | STree::LIT_FLOAT { span, .. }
+            | STree::LIT_BOOL { span, .. }
+            | STree::LIT_STRING { span, .. }
+            | STree::LIT_CHAR { span, .. }
+            | STree::LIT_ARRAY { span, .. }
+            | STree::RANGE { span, .. }
+            | STree::VAR_DECL { span, .. }
+            | STree::VAR_ASSIGN { span, .. }
+            | STree::INDEX_ASSIGN { span, .. }
+            | STree::MEMBER_ASSIGN { span, .. }
+            | STree::COMPOUND_ASSIGN { span, .. }
+            | STree::RETURN_STMT { span, .. }
+            | STree::DEFER { span, .. }
+            | STree::IF_STMT { span, .. }
+            | STree::WHILE_STMT { span, .. }
+            | STree::DO_WHILE_STMT { span, .. }
+            | STree::FOR_STMT { span, .. }
+            | STree::FOR_EACH_STMT { span, .. }
+            | STree::MATCH_STMT { span, .. }
+            | STree::MATCH_ARM { span, .. }
+            | STree::BREAK { span }
+            | STree::CONTINUE { span }
+            | STree::REPEAT { span }
+            | STree::FUNCTION_CALL { span, .. }
+            | STree::MEMBER_CALL { span, .. }
+            | STree::NULL { span }
+            | STree::BLANK { span }
+            | STree::PRINT { span, .. }
+            | STree::ERROR { span } => *span,
+        }
+    }
+}
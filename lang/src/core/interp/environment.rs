@@ -0,0 +1,84 @@
+use std::collections::{HashMap, HashSet};
+use crate::core::interp::value::Value;
+
+// Variables of a single function call, one map per nested block
+#[derive(Debug, Clone)]
+struct Frame {
+    scopes: Vec<HashMap<String, Value>>
+}
+
+#[derive(Debug, Clone)]
+pub struct Environment {
+    frames: Vec<Frame>
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment {
+            frames: vec![Frame { scopes: vec![HashMap::new()] }]
+        }
+    }
+
+    pub fn push_frame(&mut self) {
+        self.frames.push(Frame { scopes: vec![HashMap::new()] });
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.frames.pop();
+    }
+
+    pub fn push_scope(&mut self) {
+        self.frame_mut().scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.frame_mut().scopes.pop();
+    }
+
+    pub fn declare(&mut self, name: String, value: Value) {
+        self.frame_mut().scopes.last_mut().unwrap().insert(name, value);
+    }
+
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
+        for scope in self.frame_mut().scopes.iter_mut().rev() {
+            if let Some(slot) = scope.get_mut(name) {
+                *slot = value;
+                return Ok(());
+            }
+        }
+        Err(format!("Undefined var {}", name))
+    }
+
+    pub fn get(&self, name: &str) -> Result<Value, String> {
+        let frame = self.frames.last().unwrap();
+        for scope in frame.scopes.iter().rev() {
+            if let Some(value) = scope.get(name) {
+                return Ok(value.clone());
+            }
+        }
+        Err(format!("Undefined var {}", name))
+    }
+
+    // Names declared so far in the innermost block
+    pub fn declared(&self) -> HashSet<String> {
+        self.frames.last().unwrap().scopes.last().unwrap().keys().cloned().collect()
+    }
+
+    // Takes the innermost block's other variables out of sight until they are restored
+    pub fn hide_except(&mut self, visible: &HashSet<String>) -> HashMap<String, Value> {
+        let scope = self.frame_mut().scopes.last_mut().unwrap();
+        let hidden: Vec<String> = scope.keys().filter(|name| !visible.contains(*name)).cloned().collect();
+        hidden.into_iter().map(|name| {
+            let value = scope.remove(&name).unwrap();
+            (name, value)
+        }).collect()
+    }
+
+    pub fn restore(&mut self, hidden: HashMap<String, Value>) {
+        self.frame_mut().scopes.last_mut().unwrap().extend(hidden);
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+}
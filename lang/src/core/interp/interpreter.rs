@@ -0,0 +1,810 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::core::analyzer::variable::VariableType;
+use crate::core::converter::stree::STree;
+use crate::core::interp::environment::Environment;
+use crate::core::interp::value::Value;
+use crate::core::lexer::token_type::TokenType;
+use crate::core::module::context::ModuleContext;
+use crate::core::util::location::Span;
+use crate::core::util::logger::Logger;
+
+// Result of executing a statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flow {
+    NORMAL,
+    BREAK,
+    CONTINUE,
+    REPEAT,
+    RETURN(Value)
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub params: Vec<(String, VariableType)>,
+    pub return_type: VariableType,
+    pub body: STree,
+    // Module the function was declared in, its calls resolve there
+    pub module: ModuleContext
+}
+
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub fields: Vec<(String, VariableType)>
+}
+
+pub struct Interpreter {
+    pub log: Logger,
+    pub functions: HashMap<String, Function>,
+    pub classes: HashMap<String, Class>,
+    pub env: Environment,
+    module: ModuleContext
+}
+
+impl Interpreter {
+    pub fn new(_debug: bool) -> Interpreter {
+        Interpreter {
+            log: Logger::new(_debug),
+            functions: HashMap::new(),
+            classes: HashMap::new(),
+            env: Environment::new(),
+            module: ModuleContext::default()
+        }
+    }
+
+    // Run a whole program and return the exit code from main
+    pub fn run(&mut self, tree: &STree) -> Result<i32, String> {
+        self.log.info("run()");
+        self.log.indent_inc();
+
+        self.declare_functions(tree)?;
+
+        let main = self.functions.get("main").ok_or("No 'main' function found")?;
+        if !main.params.is_empty() {
+            return Err("Function 'main' must not take parameters".into());
+        }
+
+        let result = self.call_function("main", Vec::new())?;
+        let _ = std::io::stdout().flush();
+
+        self.log.indent_dec();
+
+        match result {
+            Value::INT(code) => Ok(code),
+            Value::NULL => Ok(0),
+            other => Err(format!("Function 'main' must return int or null, found {:?}", other.var_type())),
+        }
+    }
+
+    pub fn declare_functions(&mut self, tree: &STree) -> Result<(), String> {
+        match tree {
+            STree::START { functions, .. } => {
+                for function in functions {
+                    self.declare_functions(function)?;
+                }
+                Ok(())
+            },
+
+            STree::MODULE { name, imports, declarations, .. } => {
+                self.log.info(&format!("declare_module({})", name));
+                self.module = ModuleContext::new(name, imports);
+                for declaration in declarations {
+                    self.declare_functions(declaration)?;
+                }
+                self.module = ModuleContext::default();
+                Ok(())
+            },
+
+            // Methods are named Class.method in every module
+            STree::FUNCTION { return_type, name, params, body, .. } => {
+                self.log.info(&format!("declare_function({})", name));
+                let is_method = params.first().is_some_and(|(param, _)| param == "this");
+                let linked = if is_method { name.clone() } else { self.module.qualify(name) };
+
+                self.functions.insert(linked, Function {
+                    params: params.clone(),
+                    return_type: return_type.clone(),
+                    body: *body.clone(),
+                    module: self.module.clone()
+                });
+                Ok(())
+            },
+
+            STree::CLASS { name, fields, methods, .. } => {
+                self.log.info(&format!("declare_class({})", name));
+                self.classes.insert(name.clone(), Class { fields: fields.clone() });
+                for method in methods {
+                    self.declare_functions(method)?;
+                }
+                Ok(())
+            },
+
+            _ => Err(format!("Expected function declaration, found {:?}", tree)),
+        }
+    }
+
+    pub fn call_function(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        self.call(name, args).map(|(value, _)| value)
+    }
+
+    // Runs a function and also returns 'this' as a method left it, null for other functions
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<(Value, Value), String> {
+        self.log.info(&format!("call_function({})", name));
+        self.log.indent_inc();
+
+        let function = self.functions
+            .get(name)
+            .cloned()
+            .ok_or(format!("Undefined function '{}'", name))?;
+
+        if function.params.len() != args.len() {
+            return Err(format!(
+                "Function '{}' expects {} arguments, got {}",
+                name, function.params.len(), args.len()
+            ));
+        }
+
+        self.env.push_frame();
+        for ((param_name, param_type), arg) in function.params.iter().zip(args) {
+            self.env.declare(param_name.clone(), arg.widen(param_type));
+        }
+
+        let caller = std::mem::replace(&mut self.module, function.module.clone());
+        let flow = self.execute(&function.body);
+        self.module = caller;
+
+        let this = self.env.get("this").unwrap_or(Value::NULL);
+        self.env.pop_frame();
+        self.log.indent_dec();
+
+        match flow? {
+            Flow::RETURN(value) => Ok((value.widen(&function.return_type), this)),
+            // Falling off the end returns the zeroed value of the return type, like codegen
+            Flow::NORMAL => Ok((self.zero(&function.return_type), this)),
+            other => Err(format!("Jump statement {:?} escaped function '{}'", other, name)),
+        }
+    }
+}
+
+// Statements
+impl Interpreter {
+
+    pub fn execute(&mut self, node: &STree) -> Result<Flow, String> {
+        match node {
+            // Deferred statements run in reverse order however the block exits,
+            // seeing only the variables declared before them
+            STree::BLOCK { statements, .. } => {
+                self.env.push_scope();
+                let mut flow = Ok(Flow::NORMAL);
+                let mut deferred = Vec::new();
+                for statement in statements {
+                    if let STree::DEFER { statement, .. } = statement {
+                        deferred.push((statement, self.env.declared()));
+                        continue;
+                    }
+
+                    flow = self.execute(statement);
+                    if !matches!(flow, Ok(Flow::NORMAL)) {
+                        break;
+                    }
+                }
+
+                if flow.is_ok() {
+                    for (statement, visible) in deferred.into_iter().rev() {
+                        let hidden = self.env.hide_except(&visible);
+                        let result = self.execute(statement);
+                        self.env.restore(hidden);
+                        if let Err(error) = result {
+                            flow = Err(error);
+                            break;
+                        }
+                    }
+                }
+                self.env.pop_scope();
+                flow
+            },
+
+            // Outside a block the deferred statement is its own scope, so it runs right away
+            STree::DEFER { statement, .. } => {
+                self.execute(statement)?;
+                Ok(Flow::NORMAL)
+            },
+
+            STree::VAR_DECL { id, expression, var_type, .. } => {
+                let value = match expression.as_ref() {
+                    STree::BLANK { .. } => self.zero(var_type),
+                    _ => self.evaluate(expression)?.widen(var_type),
+                };
+                self.env.declare(id.clone(), value);
+                Ok(Flow::NORMAL)
+            },
+
+            STree::VAR_ASSIGN { id, expression, .. } => {
+                let value = self.evaluate(expression)?.widen(&self.env.get(id)?.var_type());
+                self.env.assign(id, value)?;
+                Ok(Flow::NORMAL)
+            },
+
+            STree::INDEX_ASSIGN { array, index, expression, .. } => {
+                let value = self.evaluate(expression)?;
+                self.assign_element(array, index, value)?;
+                Ok(Flow::NORMAL)
+            },
+
+            STree::MEMBER_ASSIGN { object, member, expression, .. } => {
+                let value = self.evaluate(expression)?;
+                self.assign_member(object, member, value)?;
+                Ok(Flow::NORMAL)
+            },
+
+            STree::COMPOUND_ASSIGN { target, operator, expression, .. } => {
+                let current = self.evaluate(target)?;
+                let value = self.evaluate(expression)?;
+                let result = self.binary(current, operator, value)?;
+                self.store(target, result)?;
+                Ok(Flow::NORMAL)
+            },
+
+            STree::RETURN_STMT { expression, .. } => {
+                let value = match expression {
+                    Some(expression) => self.evaluate(expression)?,
+                    None => Value::NULL,
+                };
+                Ok(Flow::RETURN(value))
+            },
+
+            STree::IF_STMT { condition, then_block, else_block, .. } => {
+                if self.condition(condition, "Condition must be boolean")? {
+                    self.execute(then_block)
+                } else if let Some(else_block) = else_block {
+                    self.execute(else_block)
+                } else {
+                    Ok(Flow::NORMAL)
+                }
+            },
+
+            STree::WHILE_STMT { condition, body, .. } => {
+                while self.condition(condition, "While condition must be boolean")? {
+                    match self.execute(body)? {
+                        Flow::BREAK => break,
+                        Flow::RETURN(value) => return Ok(Flow::RETURN(value)),
+                        _ => {}
+                    }
+                }
+                Ok(Flow::NORMAL)
+            },
+
+            STree::DO_WHILE_STMT { condition, body, .. } => {
+                loop {
+                    match self.execute(body)? {
+                        Flow::BREAK => break,
+                        Flow::RETURN(value) => return Ok(Flow::RETURN(value)),
+                        _ => {}
+                    }
+                    if !self.condition(condition, "Do-while condition must be boolean")? {
+                        break;
+                    }
+                }
+                Ok(Flow::NORMAL)
+            },
+
+            STree::FOR_STMT { init, condition, step, body, .. } => {
+                self.env.push_scope();
+                let flow = self.execute_for(init, condition, step, body);
+                self.env.pop_scope();
+                flow
+            },
+
+            STree::FOR_EACH_STMT { id, start, end, inclusive, body, .. } => {
+                let start = match self.evaluate(start)? {
+                    Value::INT(i) => i,
+                    _ => return Err("Range start must be an int".into()),
+                };
+                let end = match self.evaluate(end)? {
+                    Value::INT(i) => i,
+                    _ => return Err("Range end must be an int".into()),
+                };
+
+                self.env.push_scope();
+                self.env.declare(id.clone(), Value::INT(start));
+                let flow = self.execute_for_each(id, start, end, *inclusive, body);
+                self.env.pop_scope();
+                flow
+            },
+
+            STree::MATCH_STMT { scrutinee, arms, .. } => {
+                let value = self.evaluate(scrutinee)?;
+                for arm in arms {
+                    if let STree::MATCH_ARM { pattern, body, .. } = arm {
+                        if self.matches(&value, pattern.as_deref())? {
+                            // Each arm is its own scope, like a block
+                            self.env.push_scope();
+                            let flow = self.execute(body);
+                            self.env.pop_scope();
+                            return flow;
+                        }
+                    }
+                }
+                Ok(Flow::NORMAL)
+            },
+
+            STree::BREAK { .. } => Ok(Flow::BREAK),
+            STree::CONTINUE { .. } => Ok(Flow::CONTINUE),
+            STree::REPEAT { .. } => Ok(Flow::REPEAT),
+
+            STree::PRINT { expression, .. } => {
+                let value = self.evaluate(expression)?;
+                println!("{}", value);
+                Ok(Flow::NORMAL)
+            },
+
+            STree::FUNCTION_CALL { .. } => {
+                self.evaluate(node)?;
+                Ok(Flow::NORMAL)
+            },
+
+            STree::VAR_TYPE { .. } | STree::BLANK { .. } | STree::NULL { .. } => Ok(Flow::NORMAL),
+
+            STree::LIT_INT { .. }
+            | STree::LIT_FLOAT { .. }
+            | STree::LIT_BOOL { .. }
+            | STree::LIT_CHAR { .. }
+            | STree::LIT_STRING { .. }
+            | STree::ID { .. }
+            | STree::MEMBER_CALL { .. }
+            | STree::EXPR { .. }
+            | STree::PRFX_EXPR { .. }
+            | STree::PTFX_EXPR { .. } => {
+                self.evaluate(node)?;
+                Ok(Flow::NORMAL)
+            },
+
+            _ => Err(format!("Invalid statement node: {:?}", node)),
+        }
+    }
+
+    // Continue runs the step before the next pass, repeat skips it
+    fn execute_for(&mut self, init: &Option<Box<STree>>, condition: &STree, step: &Option<Box<STree>>, body: &STree) -> Result<Flow, String> {
+        if let Some(init) = init {
+            self.execute(init)?;
+        }
+
+        while self.condition(condition, "For condition must be boolean")? {
+            match self.execute(body)? {
+                Flow::BREAK => break,
+                Flow::RETURN(value) => return Ok(Flow::RETURN(value)),
+                Flow::REPEAT => continue,
+                _ => {}
+            }
+            if let Some(step) = step {
+                self.execute(step)?;
+            }
+        }
+        Ok(Flow::NORMAL)
+    }
+
+    fn execute_for_each(&mut self, id: &str, start: i32, end: i32, inclusive: bool, body: &STree) -> Result<Flow, String> {
+        let mut current = start;
+
+        while if inclusive { current <= end } else { current < end } {
+            self.env.assign(id, Value::INT(current))?;
+            match self.execute(body)? {
+                Flow::BREAK => break,
+                Flow::RETURN(value) => return Ok(Flow::RETURN(value)),
+                Flow::REPEAT => continue,
+                _ => {}
+            }
+            // Stopping at the end keeps '..= 2147483647' from overflowing
+            if current == end {
+                break;
+            }
+            current += 1;
+        }
+        Ok(Flow::NORMAL)
+    }
+
+    // No pattern is the default arm, which matches everything
+    fn matches(&mut self, value: &Value, pattern: Option<&STree>) -> Result<bool, String> {
+        match pattern {
+            None => Ok(true),
+            Some(STree::RANGE { start, end, inclusive, .. }) => {
+                let start = self.evaluate(start)?;
+                let end = self.evaluate(end)?;
+                match (Self::promote_int(value), Self::promote_int(&start), Self::promote_int(&end)) {
+                    (Some(v), Some(start), Some(end)) => Ok(start <= v && if *inclusive { v <= end } else { v < end }),
+                    _ => Err("Range patterns need an int or char value".into()),
+                }
+            },
+            Some(pattern) => Ok(self.evaluate(pattern)? == *value),
+        }
+    }
+
+    fn condition(&mut self, node: &STree, message: &str) -> Result<bool, String> {
+        match self.evaluate(node)? {
+            Value::BOOLEAN(b) => Ok(b),
+            _ => Err(message.to_string()),
+        }
+    }
+}
+
+// Expressions
+impl Interpreter {
+
+    pub fn evaluate(&mut self, node: &STree) -> Result<Value, String> {
+        match node {
+            STree::LIT_INT { value, .. } => Ok(Value::INT(*value)),
+            STree::LIT_FLOAT { value, .. } => Ok(Value::FLOAT(*value)),
+            STree::LIT_CHAR { value, .. } => Ok(Value::CHAR(*value)),
+            STree::LIT_STRING { value, .. } => Ok(Value::STRING(value.clone())),
+            STree::LIT_BOOL { value, .. } => Ok(Value::BOOLEAN(*value)),
+            STree::NULL { .. } => Ok(Value::NULL),
+
+            STree::ID { name, .. } => self.env.get(name),
+
+            STree::CAST { expression, target, span, .. } => {
+                let value = self.evaluate(expression)?;
+                value.cast(target).map_err(|message| format!("{} on line {}", message, span.start_line))
+            },
+
+            STree::LIT_ARRAY { elements, .. } => {
+                let mut values = Vec::new();
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                Ok(Value::ARRAY(values))
+            },
+
+            STree::INDEX { array, index, span } => {
+                let array = self.evaluate(array)?;
+                let index = self.evaluate(index)?;
+                match array {
+                    Value::ARRAY(mut elements) => {
+                        let slot = Self::element_slot(&elements, index, *span)?;
+                        Ok(elements.swap_remove(slot))
+                    },
+                    // The bytes of a string are its chars
+                    Value::STRING(s) => {
+                        let bytes: Vec<Value> = s.bytes().map(|byte| Value::CHAR(byte as char)).collect();
+                        let slot = Self::element_slot(&bytes, index, *span)?;
+                        Ok(bytes[slot].clone())
+                    },
+                    _ => Err("Only arrays can be indexed".into()),
+                }
+            },
+
+            STree::MEMBER_CALL { object, member, .. } => {
+                match self.evaluate(object)? {
+                    Value::OBJECT(class, mut fields) => {
+                        let slot = self.field_slot(&class, member)?;
+                        Ok(fields.swap_remove(slot))
+                    },
+                    _ => Err(format!("Cannot access field '{}' of a non-object", member)),
+                }
+            },
+
+            STree::PRFX_EXPR { operator: operator @ (TokenType::INCREMENT | TokenType::DECREMENT), right, .. } => {
+                self.increment(right, operator, true)
+            },
+
+            STree::PTFX_EXPR { left, operator, .. } => {
+                self.increment(left, operator, false)
+            },
+
+            STree::PRFX_EXPR { operator, right, .. } => {
+                let value = self.evaluate(right)?;
+                match (operator, value) {
+                    (TokenType::DASH, Value::INT(i)) => Ok(Value::INT(i.wrapping_neg())),
+                    (TokenType::DASH, Value::FLOAT(f)) => Ok(Value::FLOAT(-f)),
+                    (TokenType::DASH, _) => Err("Unsuported Type for Negation".to_string()),
+
+                    (TokenType::NOT, Value::BOOLEAN(b)) => Ok(Value::BOOLEAN(!b)),
+                    (TokenType::NOT, Value::INT(i)) => Ok(Value::INT(!i)),
+                    (TokenType::NOT, _) => Err("Unsupported Type for Not, must be Int/Bool".to_string()),
+
+                    _ => Err("Unsupported prefix op".into()),
+                }
+            },
+
+            // The right side is only evaluated when the left one does not decide the result
+            STree::EXPR { left, operator: operator @ (TokenType::AND | TokenType::OR), right, .. } => {
+                let message = format!("Logical operator {:?} requires boolean operands", operator);
+                let lhs = self.condition(left, &message)?;
+                if lhs == (*operator == TokenType::OR) {
+                    return Ok(Value::BOOLEAN(lhs));
+                }
+                Ok(Value::BOOLEAN(self.condition(right, &message)?))
+            },
+
+            // The default is only evaluated when the value is null
+            STree::EXPR { left, operator: TokenType::COALESCE, right, .. } => {
+                match self.evaluate(left)? {
+                    Value::NULL => self.evaluate(right),
+                    value => Ok(value),
+                }
+            },
+
+            STree::EXPR { left, operator, right, .. } => {
+                let lhs = self.evaluate(left)?;
+                let rhs = self.evaluate(right)?;
+                self.binary(lhs, operator, rhs)
+            },
+
+            STree::FUNCTION_CALL { callee, args, .. } => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.evaluate(arg)?);
+                }
+
+                match callee.as_ref() {
+                    // Objects are built from every field in order, or zeroed without arguments
+                    STree::ID { name, .. } if self.classes.contains_key(name) => match values.is_empty() {
+                        true => Ok(self.zero(&VariableType::OBJECT(name.clone()))),
+                        false => {
+                            let fields = self.classes[name].fields.iter().zip(values).map(|((_, field_type), value)| value.widen(field_type)).collect();
+                            Ok(Value::OBJECT(name.clone(), fields))
+                        },
+                    },
+                    STree::ID { name, .. } if name == "len" && !self.functions.contains_key(&self.module.qualify(name)) => {
+                        match values.as_slice() {
+                            [Value::STRING(s)] => Ok(Value::INT(s.len() as i32)),
+                            [Value::ARRAY(elements)] => Ok(Value::INT(elements.len() as i32)),
+                            _ => Err("Function 'len' expects a string or an array".into()),
+                        }
+                    },
+                    STree::ID { name, .. } => self.call_function(&self.module.qualify(name), values),
+                    // module.function, unless a variable hides the import
+                    STree::MEMBER_CALL { object, member, .. } => match object.as_ref() {
+                        STree::ID { name, .. } if self.env.get(name).is_err() && self.module.imports.contains_key(name) => {
+                            let linked = self.module.imported(name, member).unwrap_or_default();
+                            self.call_function(&linked, values)
+                        }
+                        _ => self.call_method(object, member, values),
+                    },
+                    _ => Err("Only simple function calls supported".into()),
+                }
+            },
+
+            _ => Err(format!("Invalid Expression Node: {:?}", node)),
+        }
+    }
+
+    // The object is passed as 'this', and stored back afterwards so the method can change its fields
+    fn call_method(&mut self, object: &STree, member: &str, mut args: Vec<Value>) -> Result<Value, String> {
+        let target = self.evaluate(object)?;
+        let Value::OBJECT(class, _) = &target else {
+            return Err(format!("Cannot call method '{}' on a non-object", member));
+        };
+
+        let name = format!("{}.{}", class, member);
+        args.insert(0, target);
+        let (result, this) = self.call(&name, args)?;
+
+        if matches!(object, STree::ID { .. } | STree::INDEX { .. } | STree::MEMBER_CALL { .. }) {
+            self.store(object, this)?;
+        }
+        Ok(result)
+    }
+
+    // Writes a value back to the variable, element or field it was read from
+    fn store(&mut self, target: &STree, value: Value) -> Result<(), String> {
+        match target {
+            STree::ID { name, .. } => self.env.assign(name, value),
+            STree::INDEX { array, index, .. } => self.assign_element(array, index, value),
+            STree::MEMBER_CALL { object, member, .. } => self.assign_member(object, member, value),
+            _ => Err("Assignment needs a variable, an array element or a field".into()),
+        }
+    }
+
+    fn assign_member(&mut self, object: &STree, member: &str, value: Value) -> Result<(), String> {
+        let mut target = self.evaluate(object)?;
+
+        let Value::OBJECT(class, fields) = &mut target else {
+            return Err(format!("Cannot assign field '{}' of a non-object", member));
+        };
+        let slot = self.field_slot(class, member)?;
+        let field_type = self.classes[class.as_str()].fields[slot].1.clone();
+        fields[slot] = value.widen(&field_type);
+
+        self.store(object, target)
+    }
+
+    fn field_slot(&self, class: &str, member: &str) -> Result<usize, String> {
+        self.classes
+            .get(class)
+            .and_then(|declared| declared.fields.iter().position(|(field, _)| field == member))
+            .ok_or(format!("Class '{}' has no field '{}'", class, member))
+    }
+
+    // Zeroed value of a type, objects get every field zeroed
+    fn zero(&self, var_type: &VariableType) -> Value {
+        match var_type {
+            VariableType::ARRAY(element, length) => Value::ARRAY(vec![self.zero(element); *length]),
+            VariableType::OBJECT(class) => {
+                let fields = self.classes
+                    .get(class)
+                    .map_or(Vec::new(), |declared| declared.fields.iter().map(|(_, field_type)| self.zero(field_type)).collect());
+                Value::OBJECT(class.clone(), fields)
+            },
+            other => Value::zero(other),
+        }
+    }
+
+    // Stores into the array held by a variable, nested elements are rebuilt from the inside out
+    fn assign_element(&mut self, array: &STree, index: &STree, value: Value) -> Result<(), String> {
+        let mut target = self.evaluate(array)?;
+        let index = self.evaluate(index)?;
+
+        let Value::ARRAY(elements) = &mut target else {
+            return Err("Only arrays can be indexed".into());
+        };
+        let slot = Self::element_slot(elements, index, array.span())?;
+        elements[slot] = value.widen(&elements[slot].var_type());
+
+        self.store(array, target)
+    }
+
+    // Position of an in bounds index, the error names the line like compiled code does
+    fn element_slot(elements: &[Value], index: Value, span: Span) -> Result<usize, String> {
+        let Value::INT(index) = index else {
+            return Err("Array index must be an int".into());
+        };
+        if index < 0 || index as usize >= elements.len() {
+            return Err(format!(
+                "Index {} out of bounds for length {} on line {}",
+                index, elements.len(), span.start_line
+            ));
+        }
+        Ok(index as usize)
+    }
+
+    // The prefix form evaluates to the new value and the postfix form to the old one
+    fn increment(&mut self, operand: &STree, operator: &TokenType, prefix: bool) -> Result<Value, String> {
+        let old = self.evaluate(operand)?;
+        let new = match (&old, operator) {
+            (Value::INT(i), TokenType::INCREMENT) => Value::INT(i.wrapping_add(1)),
+            (Value::INT(i), TokenType::DECREMENT) => Value::INT(i.wrapping_sub(1)),
+            (Value::FLOAT(f), TokenType::INCREMENT) => Value::FLOAT(f + 1.0),
+            (Value::FLOAT(f), TokenType::DECREMENT) => Value::FLOAT(f - 1.0),
+            _ => return Err(format!("Unsupported type for {:?}", operator)),
+        };
+
+        self.store(operand, new.clone())?;
+        Ok(if prefix { new } else { old })
+    }
+
+    fn binary(&self, lhs: Value, op: &TokenType, rhs: Value) -> Result<Value, String> {
+        if matches!(op, TokenType::POWER | TokenType::ROOT) {
+            return self.power(lhs, op, rhs);
+        }
+
+        // Mixed operands meet at the wider type, as the analyzer and codegen do
+        let (lhs, rhs) = match lhs.var_type().common(&rhs.var_type()) {
+            Some(common) => (lhs.widen(&common), rhs.widen(&common)),
+            None => (lhs, rhs),
+        };
+
+        match (lhs, rhs) {
+            (l @ Value::NULL, r) | (l, r @ Value::NULL) => match op {
+                TokenType::EQUAL => Ok(Value::BOOLEAN(l == r)),
+                TokenType::NOT_EQUAL => Ok(Value::BOOLEAN(l != r)),
+                _ => Err(format!("Null value used in expression '{:?}'", op)),
+            },
+
+            // Strings compare byte by byte, like the compiled runtime does
+            (Value::STRING(l), Value::STRING(r)) => match op {
+                TokenType::PLUS => Ok(Value::STRING(l + &r)),
+                TokenType::EQUAL => Ok(Value::BOOLEAN(l == r)),
+                TokenType::NOT_EQUAL => Ok(Value::BOOLEAN(l != r)),
+                TokenType::LESS => Ok(Value::BOOLEAN(l < r)),
+                TokenType::GREATER => Ok(Value::BOOLEAN(l > r)),
+                TokenType::LESS_EQUAL => Ok(Value::BOOLEAN(l <= r)),
+                TokenType::GREATER_EQUAL => Ok(Value::BOOLEAN(l >= r)),
+                _ => Err(format!("Unsupported string operator: {:?}", op)),
+            },
+
+            (Value::BOOLEAN(l), Value::BOOLEAN(r)) => match op {
+                TokenType::EQUAL => Ok(Value::BOOLEAN(l == r)),
+                TokenType::NOT_EQUAL => Ok(Value::BOOLEAN(l != r)),
+                TokenType::XOR => Ok(Value::BOOLEAN(l ^ r)),
+                _ => Err(format!(
+                    "Type error: boolean value cannot be used in arithmetic expression '{:?}'", op
+                )),
+            },
+
+            (Value::FLOAT(l), Value::FLOAT(r)) => self.float_binary(l, op, r),
+
+            (l, r) => match (Self::promote_int(&l), Self::promote_int(&r)) {
+                (Some(l), Some(r)) => self.int_binary(l, op, r),
+                _ => Err(format!("Type mismatch in expression: {:?}", op)),
+            },
+        }
+    }
+
+    // char to int, as codegen does before integer operations
+    fn promote_int(value: &Value) -> Option<i32> {
+        match value {
+            Value::INT(i) => Some(*i),
+            Value::CHAR(c) => Some(*c as i32),
+            _ => None,
+        }
+    }
+
+    // int ^ int stays an int, everything else is computed as float like codegen does
+    fn power(&self, lhs: Value, op: &TokenType, rhs: Value) -> Result<Value, String> {
+        let as_float = |value: &Value| match value {
+            Value::FLOAT(f) => Some(*f),
+            other => Self::promote_int(other).map(|i| i as f32),
+        };
+        let (Some(x), Some(y)) = (as_float(&lhs), as_float(&rhs)) else {
+            return Err(format!("Type mismatch in expression: {:?}", op));
+        };
+
+        match (op, Self::promote_int(&lhs), Self::promote_int(&rhs)) {
+            (TokenType::POWER, Some(base), Some(exponent)) => Self::int_power(base, exponent).map(Value::INT),
+            (TokenType::POWER, None, Some(exponent)) => Ok(Value::FLOAT(x.powi(exponent))),
+            (TokenType::POWER, _, _) => Ok(Value::FLOAT(x.powf(y))),
+            // An odd integer degree also takes the root of a negative value
+            (_, _, Some(degree)) if degree % 2 != 0 && x < 0.0 => Ok(Value::FLOAT(-(-x).powf(1.0 / y))),
+            (_, _, Some(2)) => Ok(Value::FLOAT(x.sqrt())),
+            _ => Ok(Value::FLOAT(x.powf(1.0 / y))),
+        }
+    }
+
+    // Exponentiation by squaring, a negative exponent divides 1 by the result
+    fn int_power(base: i32, exponent: i32) -> Result<i32, String> {
+        let mut result: i32 = 1;
+        let mut square = base;
+        let mut remaining = exponent.unsigned_abs();
+
+        while remaining != 0 {
+            if remaining & 1 == 1 {
+                result = result.wrapping_mul(square);
+            }
+            square = square.wrapping_mul(square);
+            remaining >>= 1;
+        }
+
+        if exponent < 0 {
+            return 1i32.checked_div(result).ok_or("Division by zero".into());
+        }
+        Ok(result)
+    }
+
+    fn int_binary(&self, l: i32, op: &TokenType, r: i32) -> Result<Value, String> {
+        match op {
+            TokenType::PLUS    => Ok(Value::INT(l.wrapping_add(r))),
+            TokenType::DASH    => Ok(Value::INT(l.wrapping_sub(r))),
+            TokenType::STAR    => Ok(Value::INT(l.wrapping_mul(r))),
+            TokenType::SLASH   => l.checked_div(r).map(Value::INT).ok_or("Division by zero".into()),
+            TokenType::PERCENT => l.checked_rem(r).map(Value::INT).ok_or("Division by zero".into()),
+
+            TokenType::LESS          => Ok(Value::BOOLEAN(l < r)),
+            TokenType::GREATER       => Ok(Value::BOOLEAN(l > r)),
+            TokenType::LESS_EQUAL    => Ok(Value::BOOLEAN(l <= r)),
+            TokenType::GREATER_EQUAL => Ok(Value::BOOLEAN(l >= r)),
+            TokenType::EQUAL         => Ok(Value::BOOLEAN(l == r)),
+            TokenType::NOT_EQUAL     => Ok(Value::BOOLEAN(l != r)),
+
+            _ => Err(format!("Unsupported int operator: {:?}", op)),
+        }
+    }
+
+    fn float_binary(&self, l: f32, op: &TokenType, r: f32) -> Result<Value, String> {
+        match op {
+            TokenType::PLUS  => Ok(Value::FLOAT(l + r)),
+            TokenType::DASH  => Ok(Value::FLOAT(l - r)),
+            TokenType::STAR  => Ok(Value::FLOAT(l * r)),
+            TokenType::SLASH => Ok(Value::FLOAT(l / r)),
+
+            TokenType::LESS          => Ok(Value::BOOLEAN(l < r)),
+            TokenType::GREATER       => Ok(Value::BOOLEAN(l > r)),
+            TokenType::LESS_EQUAL    => Ok(Value::BOOLEAN(l <= r)),
+            TokenType::GREATER_EQUAL => Ok(Value::BOOLEAN(l >= r)),
+            TokenType::EQUAL         => Ok(Value::BOOLEAN(l == r)),
+            TokenType::NOT_EQUAL     => Ok(Value::BOOLEAN(l != r)),
+
+            _ => Err(format!("Unsupported float operator: {:?}", op)),
+        }
+    }
+}
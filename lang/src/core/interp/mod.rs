@@ -0,0 +1,3 @@
+pub mod value;
+pub mod environment;
+pub mod interpreter;
\ No newline at end of file
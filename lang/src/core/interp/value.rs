@@ -0,0 +1,152 @@
+use std::fmt;
+use crate::core::analyzer::variable::VariableType;
+use crate::core::converter::stree::STree;
+use crate::core::lexer::token_type::TokenType;
+
+// Runtime value of the tree-walking interpreter
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    INT(i32),
+    FLOAT(f32),
+    CHAR(char),
+    STRING(String),
+    BOOLEAN(bool),
+    ARRAY(Vec<Value>),
+    // Class name and the fields in declaration order
+    OBJECT(String, Vec<Value>),
+    NULL
+}
+
+impl Value {
+    pub fn var_type(&self) -> VariableType {
+        match self {
+            Value::INT(_) => VariableType::INT,
+            Value::FLOAT(_) => VariableType::FLOAT,
+            Value::CHAR(_) => VariableType::CHAR,
+            Value::STRING(_) => VariableType::STRING,
+            Value::BOOLEAN(_) => VariableType::BOOLEAN,
+            Value::ARRAY(elements) => {
+                let element = elements.first().map_or(VariableType::NULL, Value::var_type);
+                VariableType::ARRAY(Box::new(element), elements.len())
+            },
+            Value::OBJECT(class, _) => VariableType::OBJECT(class.clone()),
+            Value::NULL => VariableType::NULL,
+        }
+    }
+
+    // Value of a declared but uninitialized array element, matching the zeroed memory of codegen
+    pub fn zero(var_type: &VariableType) -> Value {
+        match var_type {
+            VariableType::FLOAT => Value::FLOAT(0.0),
+            VariableType::CHAR => Value::CHAR('\0'),
+            VariableType::STRING => Value::STRING(String::new()),
+            VariableType::BOOLEAN => Value::BOOLEAN(false),
+            VariableType::ARRAY(element, length) => Value::ARRAY(vec![Value::zero(element); *length]),
+            VariableType::NULL | VariableType::NULLABLE(_) => Value::NULL,
+            _ => Value::INT(0),
+        }
+    }
+
+    // Value of a literal, or a negated number, known before the program runs
+    pub fn constant(node: &STree) -> Option<Value> {
+        match node {
+            STree::LIT_INT { value, .. } => Some(Value::INT(*value)),
+            STree::LIT_FLOAT { value, .. } => Some(Value::FLOAT(*value)),
+            STree::LIT_CHAR { value, .. } => Some(Value::CHAR(*value)),
+            STree::LIT_STRING { value, .. } => Some(Value::STRING(value.clone())),
+            STree::LIT_BOOL { value, .. } => Some(Value::BOOLEAN(*value)),
+            STree::PRFX_EXPR { operator: TokenType::DASH, right, .. } => match Value::constant(right)? {
+                Value::INT(i) => Some(Value::INT(i.wrapping_neg())),
+                Value::FLOAT(f) => Some(Value::FLOAT(-f)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Conversion of 'as'. Floats truncate and saturate, numbers become chars by their code and
+    // strings are parsed, a value that does not fit is an error
+    pub fn cast(self, target: &VariableType) -> Result<Value, String> {
+        match (self, target) {
+            (value, VariableType::STRING) => Ok(Value::STRING(value.to_string())),
+            (Value::STRING(s), target) => Value::parse(&s, target),
+            (Value::FLOAT(f), VariableType::FLOAT) => Ok(Value::FLOAT(f)),
+            (Value::FLOAT(f), VariableType::BOOLEAN) => Ok(Value::BOOLEAN(f != 0.0)),
+            (Value::FLOAT(f), target) => Value::INT(f as i32).cast(target),
+            (value, target) => {
+                let code = match value {
+                    Value::INT(i) => i,
+                    Value::CHAR(c) => c as i32,
+                    Value::BOOLEAN(b) => b as i32,
+                    other => return Err(format!("Cannot convert {:?} to {}", other.var_type(), target.keyword())),
+                };
+                match target {
+                    VariableType::INT => Ok(Value::INT(code)),
+                    VariableType::FLOAT => Ok(Value::FLOAT(code as f32)),
+                    VariableType::BOOLEAN => Ok(Value::BOOLEAN(code != 0)),
+                    VariableType::CHAR => Value::char_code(code).map(Value::CHAR).ok_or(format!("Cannot convert {} to char", code)),
+                    other => Err(format!("Cannot convert to {}", other.keyword())),
+                }
+            },
+        }
+    }
+
+    // Implicit widening when a value is stored or meets a wider operand
+    pub fn widen(self, target: &VariableType) -> Value {
+        let target = target.non_null();
+        let var_type = self.var_type();
+        if var_type == *target || !var_type.widens_to(target) {
+            return self;
+        }
+        self.clone().cast(target).unwrap_or(self)
+    }
+
+    // chars are 16 bits in compiled code
+    fn char_code(code: i32) -> Option<char> {
+        u32::try_from(code).ok().filter(|code| *code <= 0xFFFF).and_then(char::from_u32)
+    }
+
+    fn parse(s: &str, target: &VariableType) -> Result<Value, String> {
+        let parsed = match target {
+            VariableType::INT => s.trim_start().parse().ok().map(Value::INT),
+            VariableType::FLOAT => s.trim_start().parse().ok().map(Value::FLOAT),
+            VariableType::BOOLEAN => match s {
+                "true" => Some(Value::BOOLEAN(true)),
+                "false" => Some(Value::BOOLEAN(false)),
+                _ => None,
+            },
+            VariableType::CHAR => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if (c as u32) <= 0xFFFF => Some(Value::CHAR(c)),
+                    _ => None,
+                }
+            },
+            _ => None,
+        };
+        parsed.ok_or(format!("Cannot convert \"{}\" to {}", s, target.keyword()))
+    }
+
+}
+
+// Formats values the same way the compiled printf calls do
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::INT(i) => write!(f, "{}", i),
+            Value::FLOAT(x) => write!(f, "{:.6}", *x as f64),
+            Value::CHAR(c) => write!(f, "{}", c),
+            Value::STRING(s) => write!(f, "{}", s),
+            Value::BOOLEAN(b) => write!(f, "{}", b),
+            Value::ARRAY(elements) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            },
+            Value::OBJECT(class, fields) => {
+                let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+                write!(f, "{}({})", class, fields.join(", "))
+            },
+            Value::NULL => write!(f, "null"),
+        }
+    }
+}
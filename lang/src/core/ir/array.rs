@@ -0,0 +1,131 @@
+use inkwell::IntPredicate;
+use inkwell::types::{BasicType, BasicTypeEnum};
+use inkwell::values::{BasicValueEnum, FunctionValue, PointerValue};
+use crate::core::converter::stree::STree;
+use crate::core::ir::codegen::CodeGen;
+use crate::core::util::location::Span;
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // Arrays are LLVM array values, built one element at a time
+    pub fn compile_array_literal(&mut self, elements: &[STree]) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_array_literal()");
+
+        let mut values = Vec::new();
+        for element in elements {
+            let value = self.compile_expression(element)?;
+            values.push(self.build_owned(value)?);
+        }
+
+        let first = values.first().ok_or("Array literals need at least one element")?;
+        let array_type = first.get_type().array_type(values.len() as u32);
+
+        let mut array = array_type.get_undef();
+        for (position, value) in values.iter().enumerate() {
+            array = self.builder
+                .build_insert_value(array, *value, position as u32, "element")
+                .unwrap()
+                .into_array_value();
+        }
+
+        Ok(array.into())
+    }
+
+    // Pointer to the storage of a value and its type. Variables, elements and fields are used in place,
+    // any other value is copied to the stack first
+    pub fn compile_place(&mut self, node: &STree) -> Result<(PointerValue<'ctx>, BasicTypeEnum<'ctx>), String> {
+        match node {
+            STree::ID { name, .. } => {
+                let (pointer, var_type) = *self.variables.get(name).ok_or(format!("Undefined var {}", name))?;
+                // A nullable variable used in place was checked, so its value is used directly
+                match self.is_nullable_type(var_type) {
+                    true => {
+                        let struct_type = var_type.into_struct_type();
+                        let value = self.builder.build_struct_gep(struct_type, pointer, 1, name).unwrap();
+                        Ok((value, struct_type.get_field_type_at_index(1).unwrap()))
+                    },
+                    false => Ok((pointer, var_type)),
+                }
+            },
+            STree::INDEX { array, index, span } => self.compile_element_pointer(array, index, *span),
+            STree::MEMBER_CALL { object, member, .. } => self.compile_field_pointer(object, member),
+            _ => {
+                let value = self.compile_expression(node)?;
+                let function = self.current_fn.unwrap();
+                let temporary = self.create_entry_block_alloca(function, "value_tmp", value.get_type());
+                self.builder.build_store(temporary, value).unwrap();
+                Ok((temporary, value.get_type()))
+            },
+        }
+    }
+
+    // Pointer to array[index] and the element type, a string gives a pointer to one of its bytes
+    pub fn compile_element_pointer(&mut self, array: &STree, index: &STree, span: Span) -> Result<(PointerValue<'ctx>, BasicTypeEnum<'ctx>), String> {
+        let (base, base_type) = self.compile_place(array)?;
+
+        let index = match self.compile_expression(index)? {
+            BasicValueEnum::IntValue(i) => self.promote_int(i),
+            _ => return Err("Array index must be an int".into()),
+        };
+
+        let array_type = match base_type {
+            BasicTypeEnum::ArrayType(array_type) => array_type,
+            BasicTypeEnum::PointerType(string_type) => {
+                let string = self.builder.build_load(string_type, base, "string").unwrap().into_pointer_value();
+                let pointer = self.compile_string_element_pointer(string, index, span)?;
+                return Ok((pointer, self.context.i8_type().into()));
+            },
+            _ => return Err("Only arrays can be indexed".into()),
+        };
+
+        let length = self.context.i32_type().const_int(array_type.len() as u64, false);
+        self.build_bounds_check(index, length, span)?;
+
+        let zero = self.context.i32_type().const_zero();
+        let pointer = unsafe {
+            self.builder.build_in_bounds_gep(array_type, base, &[zero, index], "element_ptr").unwrap()
+        };
+        Ok((pointer, array_type.get_element_type()))
+    }
+
+    // An unsigned compare also catches negative indexes. Out of bounds prints the line and exits
+    pub fn build_bounds_check(&mut self, index: inkwell::values::IntValue<'ctx>, length: inkwell::values::IntValue<'ctx>, span: Span) -> Result<(), String> {
+        let int_type = self.context.i32_type();
+
+        let function = self.current_fn.unwrap();
+        let fail_bb = self.context.append_basic_block(function, "bounds_fail");
+        let ok_bb = self.context.append_basic_block(function, "bounds_ok");
+
+        let in_bounds = self.builder.build_int_compare(IntPredicate::ULT, index, length, "in_bounds").unwrap();
+        self.builder.build_conditional_branch(in_bounds, ok_bb, fail_bb).unwrap();
+
+        self.builder.position_at_end(fail_bb);
+        let printf = self.module.get_function("printf").ok_or("printf not declared")?;
+        let message = self.builder
+            .build_global_string_ptr("Index %d out of bounds for length %d on line %d\n", "bounds_msg")
+            .unwrap();
+        let line = int_type.const_int(span.start_line as u64, false);
+        self.builder.build_call(
+            printf,
+            &[message.as_pointer_value().into(), index.into(), length.into(), line.into()],
+            "bounds_print"
+        ).unwrap();
+
+        let exit = self.declare_exit();
+        self.builder.build_call(exit, &[int_type.const_int(1, false).into()], "").unwrap();
+        self.builder.build_unreachable().unwrap();
+
+        self.builder.position_at_end(ok_bb);
+        Ok(())
+    }
+
+    pub fn declare_exit(&self) -> FunctionValue<'ctx> {
+        if let Some(exit) = self.module.get_function("exit") {
+            return exit;
+        }
+
+        let exit_type = self.context.void_type().fn_type(&[self.context.i32_type().into()], false);
+        self.module.add_function("exit", exit_type, None)
+    }
+
+}
@@ -0,0 +1,311 @@
+use inkwell::{FloatPredicate, IntPredicate};
+use inkwell::types::{BasicTypeEnum, FunctionType};
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue};
+use crate::core::analyzer::variable::VariableType;
+use crate::core::ir::codegen::CodeGen;
+use crate::core::util::location::Span;
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // Language type of a compiled scalar, the conversion rules are written in terms of these
+    pub fn scalar_type(&self, ty: BasicTypeEnum<'ctx>) -> Option<VariableType> {
+        match ty {
+            BasicTypeEnum::IntType(int) if int == self.context.bool_type() => Some(VariableType::BOOLEAN),
+            BasicTypeEnum::IntType(int) if int == self.context.i16_type() => Some(VariableType::CHAR),
+            BasicTypeEnum::IntType(int) if int == self.context.i32_type() => Some(VariableType::INT),
+            BasicTypeEnum::FloatType(_) => Some(VariableType::FLOAT),
+            BasicTypeEnum::PointerType(_) => Some(VariableType::STRING),
+            _ => None,
+        }
+    }
+
+    // Implicit widening as VariableType::widens_to allows it, anything else is left as it is
+    pub fn widen(&mut self, value: BasicValueEnum<'ctx>, target: &VariableType) -> BasicValueEnum<'ctx> {
+        let int = self.context.i32_type();
+        let float = self.context.f32_type();
+
+        match (value, self.scalar_type(value.get_type()), target) {
+            (BasicValueEnum::IntValue(c), Some(VariableType::CHAR), VariableType::INT) => {
+                self.builder.build_int_z_extend(c, int, "widen").unwrap().into()
+            },
+            (BasicValueEnum::IntValue(c), Some(VariableType::CHAR), VariableType::FLOAT) => {
+                self.builder.build_unsigned_int_to_float(c, float, "widen").unwrap().into()
+            },
+            (BasicValueEnum::IntValue(i), Some(VariableType::INT), VariableType::FLOAT) => {
+                self.builder.build_signed_int_to_float(i, float, "widen").unwrap().into()
+            },
+            _ => value,
+        }
+    }
+
+    // Mixed operands meet at the wider type, 1 + 2.0 is a float
+    pub fn widen_operands(&mut self, lhs: BasicValueEnum<'ctx>, rhs: BasicValueEnum<'ctx>) -> (BasicValueEnum<'ctx>, BasicValueEnum<'ctx>) {
+        let common = match (self.scalar_type(lhs.get_type()), self.scalar_type(rhs.get_type())) {
+            (Some(l), Some(r)) => l.common(&r),
+            _ => None,
+        };
+        match common {
+            Some(common) => (self.widen(lhs, &common), self.widen(rhs, &common)),
+            None => (lhs, rhs),
+        }
+    }
+
+    // value as type, following Value::cast. Conversions that can fail check at runtime and exit
+    pub fn compile_cast(&mut self, value: BasicValueEnum<'ctx>, target: &VariableType, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_cast()");
+
+        let source = self.scalar_type(value.get_type()).ok_or("Only int, float, char, boolean and string convert with 'as'")?;
+        if source == *target || source.widens_to(target) {
+            return Ok(self.widen(value, target));
+        }
+
+        let bool_type = self.context.bool_type();
+        let char_type = self.context.i16_type();
+        let int_type = self.context.i32_type();
+
+        match (value, target) {
+            (_, VariableType::STRING) => self.compile_to_string(value),
+            (BasicValueEnum::PointerValue(_), _) => self.compile_parse(value, target, span),
+
+            (BasicValueEnum::FloatValue(f), VariableType::BOOLEAN) => {
+                let zero = self.context.f32_type().const_zero();
+                Ok(self.builder.build_float_compare(FloatPredicate::UNE, f, zero, "to_bool").unwrap().into())
+            },
+            // Truncates and saturates like a Rust 'as'
+            (BasicValueEnum::FloatValue(f), _) => {
+                let int = self.call_intrinsic("llvm.fptosi.sat", &[int_type.into(), f.get_type().into()], &[f.into()])?;
+                self.compile_cast(int, target, span)
+            },
+
+            (BasicValueEnum::IntValue(i), VariableType::BOOLEAN) => {
+                Ok(self.builder.build_int_compare(IntPredicate::NE, i, i.get_type().const_zero(), "to_bool").unwrap().into())
+            },
+            (BasicValueEnum::IntValue(b), VariableType::INT) => Ok(self.builder.build_int_z_extend(b, int_type, "to_int").unwrap().into()),
+            (BasicValueEnum::IntValue(b), VariableType::FLOAT) => {
+                Ok(self.builder.build_unsigned_int_to_float(b, self.context.f32_type(), "to_float").unwrap().into())
+            },
+            (BasicValueEnum::IntValue(b), VariableType::CHAR) if b.get_type() == bool_type => {
+                Ok(self.builder.build_int_z_extend(b, char_type, "to_char").unwrap().into())
+            },
+            // A char is a 16 bit code that is not a surrogate
+            (BasicValueEnum::IntValue(i), VariableType::CHAR) => {
+                let in_range = self.builder.build_int_compare(IntPredicate::ULE, i, int_type.const_int(0xFFFF, false), "in_range").unwrap();
+                let surrogate = self.builder.build_int_sub(i, int_type.const_int(0xD800, false), "surrogate").unwrap();
+                let not_surrogate = self.builder
+                    .build_int_compare(IntPredicate::UGT, surrogate, int_type.const_int(0x7FF, false), "not_surrogate")
+                    .unwrap();
+                let valid = self.builder.build_and(in_range, not_surrogate, "valid_char").unwrap();
+
+                self.build_conversion_check(valid, "Cannot convert %d to char on line %d\n", i.into(), span)?;
+                Ok(self.builder.build_int_truncate(i, char_type, "to_char").unwrap().into())
+            },
+
+            _ => Err(format!("Cannot convert {:?} to {:?}", source, target)),
+        }
+    }
+
+    // Formats like print does, into a new buffer
+    fn compile_to_string(&mut self, value: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+        let (format, value): (&str, BasicValueEnum<'ctx>) = match value {
+            BasicValueEnum::IntValue(b) if b.get_type() == self.context.bool_type() => {
+                let true_str = self.compile_string_literal("true");
+                let false_str = self.compile_string_literal("false");
+                return Ok(self.builder.build_select(b, true_str, false_str, "bool_str").unwrap());
+            },
+            BasicValueEnum::IntValue(c) if c.get_type() == self.context.i16_type() => {
+                ("%c", self.builder.build_int_z_extend(c, self.context.i32_type(), "char_ext").unwrap().into())
+            },
+            BasicValueEnum::IntValue(i) => ("%d", i.into()),
+            BasicValueEnum::FloatValue(f) => ("%f", self.builder.build_float_ext(f, self.context.f64_type(), "fext").unwrap().into()),
+            _ => return Ok(value),
+        };
+
+        let ptr_type = self.context.ptr_type(inkwell::AddressSpace::default());
+        let size_type = self.context.i64_type();
+        let snprintf = self.external("snprintf", self.context.i32_type().fn_type(&[ptr_type.into(), size_type.into(), ptr_type.into()], true));
+        let format = self.builder.build_global_string_ptr(format, "fmt").unwrap().as_pointer_value();
+
+        // Measure first, then print into a string of that length
+        let measured = self.call_value(snprintf, &[ptr_type.const_null().into(), size_type.const_zero().into(), format.into(), value])?;
+        let length = self.builder.build_int_s_extend(measured.into_int_value(), size_type, "str_len").unwrap();
+        let string = self.build_string_alloc(length)?;
+        let size = self.builder.build_int_add(length, size_type.const_int(1, false), "str_size").unwrap();
+        self.call_value(snprintf, &[string.into(), size.into(), format.into(), value])?;
+
+        Ok(string.into())
+    }
+
+    // The whole string has to be a value of the target type
+    fn compile_parse(&mut self, value: BasicValueEnum<'ctx>, target: &VariableType, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+        let ptr_type = self.context.ptr_type(inkwell::AddressSpace::default());
+        let int_type = self.context.i32_type();
+        let text = value.into_pointer_value();
+        let message = match target {
+            VariableType::INT => "Cannot convert \"%s\" to int on line %d\n",
+            VariableType::FLOAT => "Cannot convert \"%s\" to float on line %d\n",
+            VariableType::CHAR => "Cannot convert \"%s\" to char on line %d\n",
+            _ => "Cannot convert \"%s\" to boolean on line %d\n",
+        };
+
+        let (valid, result): (IntValue<'ctx>, BasicValueEnum<'ctx>) = match target {
+            VariableType::INT | VariableType::FLOAT => {
+                let function = self.current_fn.unwrap();
+                let end = self.create_entry_block_alloca(function, "parse_end", ptr_type.into());
+                let parsed = match target {
+                    VariableType::INT => {
+                        let long_type = self.context.i64_type();
+                        let strtol = self.external("strtol", long_type.fn_type(&[ptr_type.into(), ptr_type.into(), int_type.into()], false));
+                        let base = int_type.const_int(10, false);
+                        self.call_value(strtol, &[text.into(), end.into(), base.into()])?
+                    },
+                    _ => {
+                        let strtof = self.external("strtof", self.context.f32_type().fn_type(&[ptr_type.into(), ptr_type.into()], false));
+                        self.call_value(strtof, &[text.into(), end.into()])?
+                    },
+                };
+
+                // Something was read and nothing is left over
+                let end = self.builder.build_load(ptr_type, end, "end").unwrap().into_pointer_value();
+                let read = self.builder.build_int_compare(IntPredicate::NE, end, text, "read").unwrap();
+                let rest = self.builder.build_load(self.context.i8_type(), end, "rest").unwrap().into_int_value();
+                let consumed = self.builder.build_int_compare(IntPredicate::EQ, rest, rest.get_type().const_zero(), "consumed").unwrap();
+                let valid = self.builder.build_and(read, consumed, "valid").unwrap();
+
+                match parsed {
+                    BasicValueEnum::IntValue(long) => {
+                        let int = self.builder.build_int_truncate(long, int_type, "to_int").unwrap();
+                        let back = self.builder.build_int_s_extend(int, long.get_type(), "back").unwrap();
+                        let fits = self.builder.build_int_compare(IntPredicate::EQ, back, long, "fits").unwrap();
+                        (self.builder.build_and(valid, fits, "valid_int").unwrap(), int.into())
+                    },
+                    other => (valid, other),
+                }
+            },
+            VariableType::CHAR => {
+                let (valid, code) = self.build_utf8_char(text);
+                (valid, self.builder.build_int_truncate(code, self.context.i16_type(), "to_char").unwrap().into())
+            },
+            _ => {
+                let strcmp = self.external("strcmp", int_type.fn_type(&[ptr_type.into(), ptr_type.into()], false));
+                let mut matches = Vec::new();
+                for word in ["true", "false"] {
+                    let word = self.builder.build_global_string_ptr(word, "bool_str").unwrap();
+                    let order = self.call_value(strcmp, &[text.into(), word.as_pointer_value().into()])?.into_int_value();
+                    matches.push(self.builder.build_int_compare(IntPredicate::EQ, order, int_type.const_zero(), "is_word").unwrap());
+                }
+                (self.builder.build_or(matches[0], matches[1], "valid").unwrap(), matches[0].into())
+            },
+        };
+
+        self.build_conversion_check(valid, message, text.into(), span)?;
+        Ok(result)
+    }
+
+    // A string is a char when it holds exactly one UTF-8 encoded character up to U+FFFF, like Value::cast.
+    // Gives whether it is one and its code. Bytes past the end read the closing 0, so nothing is read out of bounds
+    fn build_utf8_char(&mut self, text: PointerValue<'ctx>) -> (IntValue<'ctx>, IntValue<'ctx>) {
+        let int_type = self.context.i32_type();
+        let size_type = self.context.i64_type();
+        let int = |value: u64| int_type.const_int(value, false);
+
+        let length = self.build_string_length(text);
+        let mut bytes = Vec::new();
+        for position in 0..3 {
+            let position = size_type.const_int(position, false);
+            let in_bounds = self.builder.build_int_compare(IntPredicate::ULT, position, length, "in_bounds").unwrap();
+            let offset = self.builder.build_select(in_bounds, position, length, "offset").unwrap().into_int_value();
+            let pointer = unsafe { self.builder.build_in_bounds_gep(self.context.i8_type(), text, &[offset], "byte_ptr").unwrap() };
+            let byte = self.builder.build_load(self.context.i8_type(), pointer, "byte").unwrap().into_int_value();
+            bytes.push(self.builder.build_int_z_extend(byte, int_type, "byte").unwrap());
+        }
+
+        // byte & mask == bits
+        let has = |codegen: &mut Self, byte: IntValue<'ctx>, mask: u64, bits: u64| {
+            let masked = codegen.builder.build_and(byte, int(mask), "masked").unwrap();
+            codegen.builder.build_int_compare(IntPredicate::EQ, masked, int(bits), "has").unwrap()
+        };
+        let payload = |codegen: &mut Self, byte: IntValue<'ctx>, mask: u64, shift: u64| {
+            let bits = codegen.builder.build_and(byte, int(mask), "payload").unwrap();
+            codegen.builder.build_left_shift(bits, int(shift), "payload").unwrap()
+        };
+        let sized = |codegen: &mut Self, size: u64| {
+            codegen.builder.build_int_compare(IntPredicate::EQ, length, size_type.const_int(size, false), "sized").unwrap()
+        };
+
+        // One byte below 0x80
+        let one = sized(self, 1);
+        let ascii = has(self, bytes[0], 0x80, 0);
+        let one = self.builder.build_and(one, ascii, "one").unwrap();
+
+        // 110xxxxx 10xxxxxx, at least 0x80
+        let lead = payload(self, bytes[0], 0x1F, 6);
+        let tail = payload(self, bytes[1], 0x3F, 0);
+        let two_code = self.builder.build_or(lead, tail, "two_code").unwrap();
+        let mut two = sized(self, 2);
+        for check in [has(self, bytes[0], 0xE0, 0xC0), has(self, bytes[1], 0xC0, 0x80)] {
+            two = self.builder.build_and(two, check, "two").unwrap();
+        }
+        let shortest = self.builder.build_int_compare(IntPredicate::UGE, two_code, int(0x80), "shortest").unwrap();
+        let two = self.builder.build_and(two, shortest, "two").unwrap();
+
+        // 1110xxxx 10xxxxxx 10xxxxxx, at least 0x800 and not a surrogate
+        let lead = payload(self, bytes[0], 0x0F, 12);
+        let middle = payload(self, bytes[1], 0x3F, 6);
+        let tail = payload(self, bytes[2], 0x3F, 0);
+        let three_code = self.builder.build_or(lead, middle, "three_code").unwrap();
+        let three_code = self.builder.build_or(three_code, tail, "three_code").unwrap();
+        let mut three = sized(self, 3);
+        for check in [has(self, bytes[0], 0xF0, 0xE0), has(self, bytes[1], 0xC0, 0x80), has(self, bytes[2], 0xC0, 0x80)] {
+            three = self.builder.build_and(three, check, "three").unwrap();
+        }
+        let shortest = self.builder.build_int_compare(IntPredicate::UGE, three_code, int(0x800), "shortest").unwrap();
+        let surrogate = self.builder.build_int_sub(three_code, int(0xD800), "surrogate").unwrap();
+        let not_surrogate = self.builder.build_int_compare(IntPredicate::UGT, surrogate, int(0x7FF), "not_surrogate").unwrap();
+        let three = self.builder.build_and(three, shortest, "three").unwrap();
+        let three = self.builder.build_and(three, not_surrogate, "three").unwrap();
+
+        let valid = self.builder.build_or(one, two, "valid").unwrap();
+        let valid = self.builder.build_or(valid, three, "valid").unwrap();
+        let code = self.builder.build_select(two, two_code, three_code, "code").unwrap().into_int_value();
+        let code = self.builder.build_select(one, bytes[0], code, "code").unwrap().into_int_value();
+        (valid, code)
+    }
+
+    // A failed conversion prints the value and the line, then exits like a failed bounds check
+    fn build_conversion_check(&mut self, valid: IntValue<'ctx>, message: &str, value: BasicValueEnum<'ctx>, span: Span) -> Result<(), String> {
+        let int_type = self.context.i32_type();
+        let function = self.current_fn.unwrap();
+        let fail_bb = self.context.append_basic_block(function, "cast_fail");
+        let ok_bb = self.context.append_basic_block(function, "cast_ok");
+        self.builder.build_conditional_branch(valid, ok_bb, fail_bb).unwrap();
+
+        self.builder.position_at_end(fail_bb);
+        let printf = self.module.get_function("printf").ok_or("printf not declared")?;
+        let message = self.builder.build_global_string_ptr(message, "cast_msg").unwrap();
+        let line = int_type.const_int(span.start_line as u64, false);
+        self.builder.build_call(printf, &[message.as_pointer_value().into(), value.into(), line.into()], "cast_print").unwrap();
+
+        let exit = self.declare_exit();
+        self.builder.build_call(exit, &[int_type.const_int(1, false).into()], "").unwrap();
+        self.builder.build_unreachable().unwrap();
+
+        self.builder.position_at_end(ok_bb);
+        Ok(())
+    }
+
+    // C library function, declared on first use
+    pub fn external(&self, name: &str, fn_type: FunctionType<'ctx>) -> FunctionValue<'ctx> {
+        self.module.get_function(name).unwrap_or_else(|| self.module.add_function(name, fn_type, None))
+    }
+
+    pub fn call_value(&mut self, function: FunctionValue<'ctx>, args: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, String> {
+        let args: Vec<_> = args.iter().map(|arg| (*arg).into()).collect();
+        let call = self.builder.build_call(function, &args, "call").unwrap();
+
+        match call.try_as_basic_value() {
+            inkwell::values::ValueKind::Basic(v) => Ok(v),
+            _ => Err(format!("{} returned no value", function.get_name().to_str().unwrap_or("call"))),
+        }
+    }
+
+}
@@ -0,0 +1,194 @@
+use inkwell::types::{BasicTypeEnum, StructType};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, PointerValue};
+use crate::core::analyzer::variable::VariableType;
+use crate::core::converter::stree::STree;
+use crate::core::ir::codegen::CodeGen;
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // Classes become named struct types. All are created before any body is set,
+    // so fields may name classes declared further down
+    pub fn declare_classes(&mut self, declarations: &[&STree]) -> Result<(), String> {
+        self.logger.info("declare_classes()");
+
+        for declaration in declarations {
+            if let STree::CLASS { name, fields, .. } = declaration {
+                let struct_type = self.context.opaque_struct_type(name);
+                self.classes.insert(name.clone(), (struct_type, fields.clone()));
+            }
+        }
+
+        for declaration in declarations {
+            if let STree::CLASS { name, fields, .. } = declaration {
+                let mut field_types = Vec::new();
+                for (_, field_type) in fields {
+                    field_types.push(self.llvm_type(field_type)?);
+                }
+                self.classes[name].0.set_body(&field_types, false);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Class(a, b) sets every field in declaration order, Class() is zeroed
+    pub fn compile_construction(&mut self, class: &str, args: &[STree]) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_construction()");
+
+        let (struct_type, _) = *self.classes.get(class).ok_or(format!("Undefined class {}", class))?;
+        if args.is_empty() {
+            return Ok(struct_type.const_zero().into());
+        }
+
+        let mut object = struct_type.get_undef();
+        for (position, arg) in args.iter().enumerate() {
+            let value = self.compile_expression(arg)?;
+            let value = self.coerce(value, struct_type.get_field_type_at_index(position as u32).ok_or("Missing field type")?);
+            let value = self.build_owned(value)?;
+            object = self.builder
+                .build_insert_value(object, value, position as u32, "field")
+                .unwrap()
+                .into_struct_value();
+        }
+
+        Ok(object.into())
+    }
+
+    // Pointer to object.member and the field type
+    pub fn compile_field_pointer(&mut self, object: &STree, member: &str) -> Result<(PointerValue<'ctx>, BasicTypeEnum<'ctx>), String> {
+        let (base, base_type) = self.compile_place(object)?;
+        let (struct_type, class) = self.class_of(base_type)?;
+
+        let position = self.classes[&class].1
+            .iter()
+            .position(|(field, _)| field == member)
+            .ok_or(format!("Class '{}' has no field '{}'", class, member))?;
+
+        let pointer = self.builder.build_struct_gep(struct_type, base, position as u32, member).unwrap();
+        let field_type = struct_type.get_field_type_at_index(position as u32).ok_or("Missing field type")?;
+        Ok((pointer, field_type))
+    }
+
+    // Methods take a pointer to their object, so changes to 'this' are seen by the caller
+    pub fn compile_method_call(&mut self, object: &STree, member: &str, args: &[STree]) -> Result<Option<BasicValueEnum<'ctx>>, String> {
+        self.logger.info("compile_method_call()");
+
+        let (this, this_type) = self.compile_place(object)?;
+        let (_, class) = self.class_of(this_type)?;
+
+        let name = format!("{}.{}", class, member);
+        let function = *self.functions
+            .get(&name)
+            .ok_or(format!("Undefined method '{}'", name))?;
+
+        let mut compiled_args: Vec<BasicMetadataValueEnum> = vec![this.into()];
+        let param_types = function.get_type().get_param_types();
+        for (arg, param_type) in args.iter().zip(param_types.into_iter().skip(1)) {
+            let value = self.compile_expression(arg)?;
+            compiled_args.push(self.coerce(value, param_type.try_into().map_err(|_| "Unsupported parameter type")?).into());
+        }
+
+        self.build_function_call(function, &compiled_args)
+    }
+
+    // The parameter 'this' of a method is a pointer, every other object is passed by value
+    pub fn parameter_type(&self, name: &str, var_type: &VariableType) -> Result<BasicTypeEnum<'ctx>, String> {
+        match var_type {
+            VariableType::OBJECT(_) if name == "this" => Ok(self.context.ptr_type(inkwell::AddressSpace::default()).into()),
+            _ => self.llvm_type(var_type),
+        }
+    }
+
+    fn class_of(&self, var_type: BasicTypeEnum<'ctx>) -> Result<(StructType<'ctx>, String), String> {
+        let BasicTypeEnum::StructType(struct_type) = var_type else {
+            return Err("Only objects have fields and methods".into());
+        };
+
+        let class = struct_type.get_name()
+            .and_then(|name| name.to_str().ok())
+            .ok_or("Objects must have a named class")?;
+        Ok((struct_type, class.to_string()))
+    }
+
+}
+
+// Each program runs in the interpreter and the JIT, see jit.rs
+#[cfg(test)]
+mod tests {
+    use crate::core::ir::jit::tests::assert_parity;
+
+    #[test]
+    fn construction_sets_or_zeroes_every_field() {
+        assert_parity(r#"
+            struct Point {
+                int x;
+                int y;
+            }
+
+            public int main() {
+                Point zero = Point();
+                Point p = Point(3, 4);
+                Point declared;
+                return zero.x + zero.y + p.x * 10 + p.y * 100 + declared.y;
+            }
+        "#, 430);
+    }
+
+    #[test]
+    fn field_writes_reach_nested_objects_and_elements() {
+        assert_parity(r#"
+            struct Point {
+                int x;
+                int y;
+            }
+
+            class Line {
+                Point from;
+                Point to;
+            }
+
+            public int main() {
+                Line line = Line(Point(1, 2), Point(3, 4));
+                line.from.x = 5;
+                line.to = Point(6, 7);
+                Point[2] points = [line.from, line.to];
+                points[1].y = 9;
+                Point copy = line.from;
+                copy.x = 8;
+                return line.from.x + line.from.y * 10 + line.to.x * 100 + line.to.y * 1000 + points[1].y * 10000 + copy.x * 100000;
+            }
+        "#, 897625);
+    }
+
+    #[test]
+    fn methods_change_the_object_they_are_called_on() {
+        assert_parity(r#"
+            class Counter {
+                int count;
+                string name;
+
+                public null add(int n) {
+                    this.count += n;
+                }
+
+                public int twice() {
+                    this.add(this.count);
+                    return this.count;
+                }
+
+                public string label() {
+                    return this.name + ":" + (this.count as string);
+                }
+            }
+
+            public int main() {
+                Counter c = Counter(1, "c");
+                c.add(2);
+                int doubled = c.twice();
+                Counter[2] counters = [Counter(), c];
+                counters[0].add(5);
+                return c.count + doubled * 10 + counters[0].count * 100 + len(c.label()) * 1000;
+            }
+        "#, 3566);
+    }
+}
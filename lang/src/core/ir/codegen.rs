@@ -3,28 +3,44 @@ use std::collections::HashMap;
 use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::module::Module;
-use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
+use inkwell::module::{Linkage, Module};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, StructType};
 use inkwell::values::{FunctionValue, PointerValue};
 use crate::core::analyzer::variable::VariableType;
 use crate::core::converter::stree::STree;
 use crate::core::lexer::token_type::TokenType;
+use crate::core::module::context::ModuleContext;
+use crate::core::util::diagnostic::{codes, Diagnostic};
+use crate::core::util::location::Span;
 use crate::core::util::logger::Logger;
 
 
+// Stack allocation and type of every visible variable, by name
+pub type Variables<'ctx> = HashMap<String, (PointerValue<'ctx>, BasicTypeEnum<'ctx>)>;
+
 pub struct CodeGen<'ctx> {
     pub logger: Logger,
     pub context: &'ctx Context,
     pub module: Module<'ctx>,
     pub builder: Builder<'ctx>,
     // Map from variable names to their stack allocations
-    pub variables: HashMap<String, (PointerValue<'ctx>, BasicTypeEnum<'ctx>)>,
+    pub variables: Variables<'ctx>,
     // Map from function names to LLVM functions
     pub functions: HashMap<String, FunctionValue<'ctx>>,
+    // Map from class names to their struct types and fields
+    pub classes: HashMap<String, (StructType<'ctx>, Vec<(String, VariableType)>)>,
     // Current function being compiled
     pub current_fn: Option<FunctionValue<'ctx>>,
-    // (break target, continue target)
-    pub loop_stack: Vec<(BasicBlock<'ctx>, BasicBlock<'ctx>, BasicBlock<'ctx>)>
+    // Source module of the current function, calls resolve there
+    pub current_module: ModuleContext,
+    // (break target, continue target, repeat target, number of blocks with deferred statements outside the loop)
+    pub loop_stack: Vec<(BasicBlock<'ctx>, BasicBlock<'ctx>, BasicBlock<'ctx>, usize)>,
+    // Deferred statements of every enclosing block, innermost last, with the variables visible where they were declared
+    pub deferred: Vec<Vec<(STree, Variables<'ctx>)>>,
+    // String variables of every enclosing block, freed when it exits, parameters first
+    pub owned: Vec<Vec<PointerValue<'ctx>>>,
+    // Span of the last node compiled, used to locate errors
+    pub span: Span
 }
 
 impl<'ctx> CodeGen<'ctx> {
@@ -40,32 +56,71 @@ impl<'ctx> CodeGen<'ctx> {
             builder,
             variables: HashMap::new(),
             functions: HashMap::new(),
+            classes: HashMap::new(),
             current_fn: None,
-            loop_stack: Vec::new()
+            current_module: ModuleContext::default(),
+            loop_stack: Vec::new(),
+            deferred: Vec::new(),
+            owned: Vec::new(),
+            span: Span::empty()
         }
     }
     
 
     // Compile a program and return the module
-    pub fn compile(&mut self, tree: &STree) -> Result<(), String> {
+    pub fn compile(&mut self, tree: &STree) -> Result<(), Diagnostic> {
         self.logger.info("compile()");
         self.logger.indent_inc();
         
         self.declare_printf();
         
 
-        if let STree::START { functions } = tree {
-            // First pass: declare all functions
-            for func in functions {
-                if let STree::FUNCTION { function_type: _, return_type, name, params, .. } = func {
-                    self.declare_function(name, params, return_type)?;
+        if let STree::START { functions, .. } = tree {
+            // Every imported module is linked into this one, each declaration keeps the module it came from
+            let mut declarations: Vec<(ModuleContext, &STree)> = Vec::new();
+            for declaration in functions {
+                match declaration {
+                    STree::MODULE { name, imports, declarations: members, .. } => {
+                        let module = ModuleContext::new(name, imports);
+                        declarations.extend(members.iter().map(|member| (module.clone(), member)));
+                    }
+                    declaration => declarations.push((ModuleContext::default(), declaration)),
+                }
+            }
+
+            let classes: Vec<&STree> = declarations.iter().map(|(_, declaration)| *declaration).collect();
+            self.declare_classes(&classes).map_err(|e| self.locate(e))?;
+
+            // Methods are compiled like any other function
+            let functions: Vec<(ModuleContext, &STree)> = declarations
+                .into_iter()
+                .flat_map(|(module, declaration)| match declaration {
+                    STree::CLASS { methods, .. } => methods.iter().map(|method| (module.clone(), method)).collect(),
+                    function => vec![(module, function)],
+                })
+                .collect();
+
+            // First pass: declare all functions, named module.function outside the entry module
+            for (module, func) in &functions {
+                if let STree::FUNCTION { function_type, return_type, name, params, span, .. } = func {
+                    self.span = *span;
+                    let linked = Self::linked_name(module, name, params);
+                    let function = self.declare_function(&linked, params, return_type).map_err(|e| self.locate(e))?;
+
+                    // Only public functions are visible outside the object file, main is always the entry point
+                    if *function_type != TokenType::PUBLIC && linked != "main" {
+                        function.set_linkage(Linkage::Internal);
+                    }
                 }
             }
 
             // Second pass: compile function bodies only (not top-level expressions)
-            for func in functions {
-                if let STree::FUNCTION { function_type:_, return_type: _, name, params, body } = func {
-                    self.compile_function(name, params, body)?;
+            for (module, func) in &functions {
+                if let STree::FUNCTION { function_type:_, return_type: _, name, params, body, span } = func {
+                    self.span = *span;
+                    self.current_module = module.clone();
+                    let linked = Self::linked_name(module, name, params);
+                    self.compile_function(&linked, params, body).map_err(|e| self.locate(e))?;
                 }
             }
         }
@@ -76,12 +131,25 @@ impl<'ctx> CodeGen<'ctx> {
         // Verify module
         self.module
             .verify()
-            .map_err(|e| format!("Module verification failed: {}", e.to_string()))?;
+            .map_err(|e| Diagnostic::error(codes::CODEGEN, format!("Module verification failed: {}", e.to_string()), Span::empty()))?;
 
         Ok(())
     }
 
 
+    // Methods are named Class.method in every module
+    fn linked_name(module: &ModuleContext, name: &str, params: &[(String, VariableType)]) -> String {
+        match params.first() {
+            Some((param, _)) if param == "this" => name.to_string(),
+            _ => module.qualify(name),
+        }
+    }
+
+    // Locate an error at the node that caused it
+    fn locate(&self, message: String) -> Diagnostic {
+        Diagnostic::error(codes::CODEGEN, message, self.span)
+    }
+
     pub fn llvm_type(&self, ty: &VariableType) -> Result<inkwell::types::BasicTypeEnum<'ctx>, String> {
         match ty {
             VariableType::INT => Ok(self.context.i32_type().into()),
@@ -89,6 +157,12 @@ impl<'ctx> CodeGen<'ctx> {
             VariableType::BOOLEAN => Ok(self.context.bool_type().into()),
             VariableType::STRING => Ok(self.context.i8_type().ptr_type(inkwell::AddressSpace::default()).into()),
             VariableType::CHAR => Ok(self.context.i16_type().into()),
+            VariableType::ARRAY(element, length) => Ok(self.llvm_type(element)?.array_type(*length as u32).into()),
+            VariableType::OBJECT(class) => {
+                let (struct_type, _) = self.classes.get(class).ok_or(format!("Undefined class {}", class))?;
+                Ok((*struct_type).into())
+            },
+            VariableType::NULLABLE(inner) => Ok(self.nullable_type(self.llvm_type(inner)?).into()),
 
             // VariableType::NULL => Ok(self.context.void_type().into()),
 
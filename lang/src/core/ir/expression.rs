@@ -1,4 +1,4 @@
-use inkwell::values::BasicValueEnum;
+use inkwell::values::{BasicValueEnum, IntValue};
 use crate::core::converter::stree::STree;
 use crate::core::ir::codegen::CodeGen;
 use crate::core::lexer::token_type::TokenType;
@@ -8,20 +8,18 @@ impl<'ctx> CodeGen<'ctx> {
     pub fn compile_expression(&mut self, node: &STree) -> Result<BasicValueEnum<'ctx>, String> {
         self.logger.info("compile_expression()");
         self.logger.info(&format!("compile_expression node = {:?}", node));
+        self.span = node.span();
 
         match node {
-            STree::LIT_INT { value } => Ok(BasicValueEnum::IntValue(self.context.i32_type().const_int(*value as u64, false))),
-            STree::LIT_FLOAT { value } => Ok(BasicValueEnum::FloatValue(self.context.f32_type().const_float(*value as f64))),
+            STree::LIT_INT { value, .. } => Ok(BasicValueEnum::IntValue(self.context.i32_type().const_int(*value as u64, false))),
+            STree::LIT_FLOAT { value, .. } => Ok(BasicValueEnum::FloatValue(self.context.f32_type().const_float(*value as f64))),
 
-            STree::LIT_CHAR { value } => Ok(BasicValueEnum::IntValue(self.context.i8_type().const_int(*value as u64, false))),
-            STree::LIT_STRING { value } => {
-                let str_val = self.builder.build_global_string_ptr(value, "str").unwrap();
-                Ok(str_val.as_pointer_value().into())
-            },
+            STree::LIT_CHAR { value, .. } => Ok(BasicValueEnum::IntValue(self.context.i16_type().const_int(*value as u64, false))),
+            STree::LIT_STRING { value, .. } => Ok(self.compile_string_literal(value).into()),
 
-            STree::LIT_BOOL { value } => Ok(BasicValueEnum::IntValue(self.context.bool_type().const_int(*value as u64, false))),
+            STree::LIT_BOOL { value, .. } => Ok(BasicValueEnum::IntValue(self.context.bool_type().const_int(*value as u64, false))),
 
-            STree::NULL => {
+            STree::NULL { .. } => {
                 let null_ptr = self.context
                     .i8_type()
                     .ptr_type(inkwell::AddressSpace::default())
@@ -30,14 +28,48 @@ impl<'ctx> CodeGen<'ctx> {
                 Ok(null_ptr.into())
             },
 
-            STree::ID { name } => {
+            STree::ID { name, .. } => {
                 let (ptr, ty) = self.variables.get(name).ok_or(format!("Undefined var {}", name))?;
                 let v = self.builder.build_load(*ty, *ptr, name).unwrap();
                 Ok(v)
             },
 
-            STree::PRFX_EXPR { operator, right } => {
+            STree::LIT_ARRAY { elements, .. } => self.compile_array_literal(elements),
+
+            STree::CAST { expression, target, span, .. } => {
+                let value = self.compile_expression(expression)?;
+                let value = self.unwrap_nullable(value);
+                self.compile_cast(value, target, *span)
+            },
+
+            STree::INDEX { array, index, span } => {
+                let (pointer, element_type) = self.compile_element_pointer(array, index, *span)?;
+                let element = self.builder.build_load(element_type, pointer, "element").unwrap();
+                // Bytes of a string are read as chars
+                match element {
+                    BasicValueEnum::IntValue(byte) if byte.get_type() == self.context.i8_type() => {
+                        Ok(self.builder.build_int_z_extend(byte, self.context.i16_type(), "char").unwrap().into())
+                    },
+                    _ => Ok(element),
+                }
+            },
+
+            STree::MEMBER_CALL { object, member, .. } => {
+                let (pointer, field_type) = self.compile_field_pointer(object, member)?;
+                Ok(self.builder.build_load(field_type, pointer, member).unwrap())
+            },
+
+            STree::PRFX_EXPR { operator: operator @ (TokenType::INCREMENT | TokenType::DECREMENT), right, .. } => {
+                self.compile_increment(right, operator, true)
+            },
+
+            STree::PTFX_EXPR { left, operator, .. } => {
+                self.compile_increment(left, operator, false)
+            },
+
+            STree::PRFX_EXPR { operator, right, .. } => {
                 let val = self.compile_expression(right)?;
+                let val = self.unwrap_nullable(val);
                 match operator {
                     TokenType::DASH => match val {
                         BasicValueEnum::IntValue(i) => Ok(self.builder.build_int_neg(i,"neg").unwrap().into()),
@@ -54,9 +86,30 @@ impl<'ctx> CodeGen<'ctx> {
                 }
             },
 
-            STree::EXPR { left, operator, right } => {
+            STree::EXPR { left, operator: operator @ (TokenType::AND | TokenType::OR | TokenType::XOR), right, .. } => {
+                self.compile_logical_expression(left, operator, right)
+            },
+
+            STree::EXPR { left, operator: operator @ (TokenType::POWER | TokenType::ROOT), right, .. } => {
+                let lhs = self.compile_expression(left)?;
+                let rhs = self.compile_expression(right)?;
+                let (lhs, rhs) = (self.unwrap_nullable(lhs), self.unwrap_nullable(rhs));
+                self.compile_power_expression(lhs, operator, rhs)
+            },
+
+            STree::EXPR { left, operator: operator @ (TokenType::EQUAL | TokenType::NOT_EQUAL), right, .. }
+                if matches!(left.as_ref(), STree::NULL { .. }) || matches!(right.as_ref(), STree::NULL { .. }) => {
+                self.compile_null_check(left, operator, right)
+            },
+
+            STree::EXPR { left, operator: TokenType::COALESCE, right, .. } => self.compile_coalesce(left, right),
+
+            STree::EXPR { left, operator, right, .. } => {
                 let lhs = self.compile_expression(left)?;
                 let rhs = self.compile_expression(right)?;
+                let (lhs, rhs) = (self.unwrap_nullable(lhs), self.unwrap_nullable(rhs));
+
+                let (lhs, rhs) = self.widen_operands(lhs, rhs);
 
                 match (lhs, rhs) {
                     (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
@@ -65,11 +118,20 @@ impl<'ctx> CodeGen<'ctx> {
                     (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => {
                         self.compile_float_expression(l, r, operator)
                     },
+                    // A join frees its temporary operands itself, a comparison has them freed here
+                    (BasicValueEnum::PointerValue(l), BasicValueEnum::PointerValue(r)) => {
+                        let result = self.compile_string_expression(l, r, operator)?;
+                        if *operator != TokenType::PLUS {
+                            self.build_string_drop(l)?;
+                            self.build_string_drop(r)?;
+                        }
+                        Ok(result)
+                    },
                     _ => Err(format!("Type mismatch in expression: {:?}", operator)),
                 }
             },
 
-            STree::FUNCTION_CALL { callee, args } => {
+            STree::FUNCTION_CALL { callee, args, .. } => {
                 match self.compile_function_call(callee, args)? {
                     Some(v) => Ok(v),
                     None => Err("Void function cannot be used in expression".into()),
@@ -80,4 +142,81 @@ impl<'ctx> CodeGen<'ctx> {
         }
     }
 
+    // AND and OR only evaluate the right side when the left one does not decide the result,
+    // the branches meet in a phi. XOR always needs both sides
+    fn compile_logical_expression(&mut self, left: &STree, operator: &TokenType, right: &STree) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_logical_expression()");
+
+        let lhs = self.compile_bool(left, operator)?;
+
+        if *operator == TokenType::XOR {
+            let rhs = self.compile_bool(right, operator)?;
+            return Ok(self.builder.build_xor(lhs, rhs, "xor").unwrap().into());
+        }
+
+        let function = self.current_fn.unwrap();
+        let lhs_bb = self.builder.get_insert_block().unwrap();
+        let rhs_bb = self.context.append_basic_block(function, "logic_rhs");
+        let end_bb = self.context.append_basic_block(function, "logic_end");
+
+        // The value the left side decides on its own
+        let short_circuit = match operator {
+            TokenType::AND => {
+                self.builder.build_conditional_branch(lhs, rhs_bb, end_bb).unwrap();
+                self.context.bool_type().const_int(0, false)
+            },
+            _ => {
+                self.builder.build_conditional_branch(lhs, end_bb, rhs_bb).unwrap();
+                self.context.bool_type().const_int(1, false)
+            },
+        };
+
+        self.builder.position_at_end(rhs_bb);
+        let rhs = self.compile_bool(right, operator)?;
+        // Nested logic in the right side moves the insert point, so take the block it ends in
+        let rhs_end_bb = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(end_bb).unwrap();
+
+        self.builder.position_at_end(end_bb);
+        let phi = self.builder.build_phi(self.context.bool_type(), "logic").unwrap();
+        phi.add_incoming(&[(&short_circuit, lhs_bb), (&rhs, rhs_end_bb)]);
+
+        Ok(phi.as_basic_value())
+    }
+
+    fn compile_bool(&mut self, node: &STree, operator: &TokenType) -> Result<IntValue<'ctx>, String> {
+        let value = self.compile_expression(node)?;
+        match self.unwrap_nullable(value) {
+            BasicValueEnum::IntValue(i) if i.get_type() == self.context.bool_type() => Ok(i),
+            _ => Err(format!("Logical operator {:?} requires boolean operands", operator)),
+        }
+    }
+
+    // Load, step by one and store, the prefix form evaluates to the new value and the postfix form to the old one
+    fn compile_increment(&mut self, operand: &STree, operator: &TokenType, prefix: bool) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_increment()");
+
+        let (ptr, ty) = self.compile_place(operand)?;
+        let old = self.builder.build_load(ty, ptr, "old").unwrap();
+
+        let new: BasicValueEnum<'ctx> = match (old, operator) {
+            (BasicValueEnum::IntValue(i), TokenType::INCREMENT) => {
+                self.builder.build_int_add(i, i.get_type().const_int(1, false), "inc").unwrap().into()
+            },
+            (BasicValueEnum::IntValue(i), TokenType::DECREMENT) => {
+                self.builder.build_int_sub(i, i.get_type().const_int(1, false), "dec").unwrap().into()
+            },
+            (BasicValueEnum::FloatValue(f), TokenType::INCREMENT) => {
+                self.builder.build_float_add(f, f.get_type().const_float(1.0), "finc").unwrap().into()
+            },
+            (BasicValueEnum::FloatValue(f), TokenType::DECREMENT) => {
+                self.builder.build_float_sub(f, f.get_type().const_float(1.0), "fdec").unwrap().into()
+            },
+            _ => return Err(format!("Unsupported type for {:?}", operator)),
+        };
+
+        self.builder.build_store(ptr, new).unwrap();
+        Ok(if prefix { new } else { old })
+    }
+
 }
\ No newline at end of file
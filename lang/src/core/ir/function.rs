@@ -1,5 +1,5 @@
-use inkwell::types::{BasicMetadataTypeEnum, BasicType};
-use inkwell::values::{BasicValueEnum, FunctionValue};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, FunctionValue};
 use crate::core::analyzer::variable::VariableType;
 use crate::core::converter::stree::STree;
 use crate::core::ir::codegen::CodeGen;
@@ -10,10 +10,10 @@ impl<'ctx> CodeGen<'ctx> {
         self.logger.info("declare_function()");
         self.logger.indent_inc();
 
-        let param_types: Vec<BasicMetadataTypeEnum> = params
-            .iter()
-            .map(|(_, t)| self.llvm_type(t).unwrap().into())
-            .collect();
+        let mut param_types: Vec<BasicMetadataTypeEnum> = Vec::new();
+        for (param_name, param_type) in params {
+            param_types.push(self.parameter_type(param_name, param_type)?.into());
+        }
 
         let fn_type = if *return_type == VariableType::NULL {
             self.context.void_type().fn_type(&param_types, false)
@@ -52,35 +52,61 @@ impl<'ctx> CodeGen<'ctx> {
         self.current_fn = Some(function);
         self.variables.clear();
 
+        // Parameters are the outermost scope, the body is a block inside it
+        self.deferred = vec![Vec::new()];
+        self.owned = vec![Vec::new()];
+
         // params
-        for (i, (param_name, _)) in params.iter().enumerate() {
+        for (i, (param_name, param_var_type)) in params.iter().enumerate() {
             let param_val = function.get_nth_param(i as u32).unwrap();
+
+            // Fields of 'this' are accessed through the pointer the caller passed
+            if let VariableType::OBJECT(_) = param_var_type {
+                if param_name == "this" {
+                    let object_type = self.llvm_type(param_var_type)?;
+                    self.variables.insert(param_name.clone(), (param_val.into_pointer_value(), object_type));
+                    continue;
+                }
+            }
+
             let param_type = function.get_nth_param(i as u32).unwrap().get_type();
             let alloca = self.create_entry_block_alloca(function, param_name, param_type);
 
+            // A string argument is held by the parameter, which frees it on return
+            let param_val = self.build_owned(param_val)?;
             self.builder.build_store(alloca, param_val).unwrap();
+            if let BasicValueEnum::PointerValue(_) = param_val {
+                self.owned[0].push(alloca);
+            }
             self.variables.insert(param_name.clone(), (alloca, param_type));
         }
 
         // body
-        let STree::BLOCK { statements } = body.as_ref() else {
+        let STree::BLOCK { statements, .. } = body.as_ref() else {
             return Err(format!("Function {} body must be BLOCK", name));
         };
 
-        for stmt in statements {
-            self.compile_statement(stmt)?;
-        }
+        self.compile_block(statements)?;
 
-        if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+        let last = self.builder.get_insert_block().unwrap();
+        if last.get_terminator().is_none() {
             let function = self.current_fn.unwrap();
 
-            if function.get_type().get_return_type().is_none() {
+            if last != entry && last.get_first_use().is_none() {
+                // Every path returned already, nothing jumps here
+                self.builder.build_unreachable().unwrap();
+            } else if let Some(return_type) = function.get_type().get_return_type() {
+                // non-void function falls off its end with a zeroed value, null when nullable
+                let zero = match return_type {
+                    BasicTypeEnum::PointerType(_) => self.compile_string_literal("").into(),
+                    _ => return_type.const_zero(),
+                };
+                self.compile_deferred(0)?;
+                self.builder.build_return(Some(&zero)).unwrap();
+            } else {
                 // void (null) function
+                self.compile_deferred(0)?;
                 self.builder.build_return(None).unwrap();
-            } else {
-                // non-void function
-                let zero = self.context.i32_type().const_int(0, false);
-                self.builder.build_return(Some(&zero)).unwrap();
             }
         }
 
@@ -92,23 +118,39 @@ impl<'ctx> CodeGen<'ctx> {
     pub fn compile_function_call(&mut self, callee: &Box<STree>, args: &Vec<STree>) -> Result<Option<BasicValueEnum<'ctx>>, String> {
 
         let func_name = match callee.as_ref() {
-            STree::ID { name } => name,
+            STree::ID { name, .. } if self.classes.contains_key(name) => return self.compile_construction(name, args).map(Some),
+            STree::ID { name, .. } if name == "len" && !self.functions.contains_key(&self.current_module.qualify(name)) => {
+                return self.compile_length(args).map(Some);
+            },
+            STree::ID { name, .. } => self.current_module.qualify(name),
+            // module.function, unless a variable hides the import
+            STree::MEMBER_CALL { object, member, .. } => match object.as_ref() {
+                STree::ID { name, .. } if !self.variables.contains_key(name) && self.current_module.imports.contains_key(name) => {
+                    self.current_module.imported(name, member).unwrap_or_default()
+                }
+                _ => return self.compile_method_call(object, member, args),
+            },
             _ => return Err("Only simple function calls supported".into()),
         };
 
         let function = *self.functions
-            .get(func_name)
+            .get(&func_name)
             .ok_or(format!("Undefined function '{}'", func_name))?;
 
         // Compile args
         let mut compiled_args = Vec::new();
-        for arg in args {
+        let param_types = function.get_type().get_param_types();
+        for (arg, param_type) in args.iter().zip(param_types) {
             let val = self.compile_expression(arg)?;
-            compiled_args.push(val.into());
+            compiled_args.push(self.coerce(val, param_type.try_into().map_err(|_| "Unsupported parameter type")?).into());
         }
 
+        self.build_function_call(function, &compiled_args)
+    }
+
+    pub fn build_function_call(&mut self, function: FunctionValue<'ctx>, compiled_args: &[BasicMetadataValueEnum<'ctx>]) -> Result<Option<BasicValueEnum<'ctx>>, String> {
         let call = self.builder
-            .build_call(function, &compiled_args, "calltmp")
+            .build_call(function, compiled_args, "calltmp")
             .unwrap();
 
         // Check return type
@@ -0,0 +1,527 @@
+use inkwell::OptimizationLevel;
+use inkwell::targets::{InitializationConfig, Target};
+use crate::core::ir::codegen::CodeGen;
+
+unsafe extern "C" {
+    // Used to flush C stdio after printf calls made by JIT code
+    fn fflush(stream: *mut std::ffi::c_void) -> i32;
+}
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // JIT compile the module and execute main, returning its exit code
+    pub fn run_jit(&self, level: OptimizationLevel) -> Result<i32, String> {
+        self.logger.info("run_jit()");
+
+        Target::initialize_native(&InitializationConfig::default())?;
+
+        let main = self.module
+            .get_function("main")
+            .ok_or("No 'main' function found")?;
+
+        if main.count_params() != 0 {
+            return Err("Function 'main' must not take parameters".into());
+        }
+
+        let engine = self.module
+            .create_jit_execution_engine(level)
+            .map_err(|e| format!("Failed to create execution engine: {}", e))?;
+
+        let code = match main.get_type().get_return_type() {
+            // null main
+            None => unsafe {
+                let function = engine
+                    .get_function::<unsafe extern "C" fn()>("main")
+                    .map_err(|e| e.to_string())?;
+                function.call();
+                0
+            },
+
+            // int main
+            Some(t) if t == self.context.i32_type().into() => unsafe {
+                let function = engine
+                    .get_function::<unsafe extern "C" fn() -> i32>("main")
+                    .map_err(|e| e.to_string())?;
+                function.call()
+            },
+
+            Some(t) => return Err(format!("Function 'main' must return int or null, found {}", t)),
+        };
+
+        unsafe {
+            fflush(std::ptr::null_mut());
+        }
+
+        Ok(code)
+    }
+
+}
+
+// The interpreter and the JIT must agree on every program, each main folds its results into the exit code
+#[cfg(test)]
+pub(crate) mod tests {
+    use inkwell::context::Context;
+    use inkwell::OptimizationLevel;
+    use crate::core::analyzer::analyzer::Analyzer;
+    use crate::core::converter::converter::Converter;
+    use crate::core::interp::interpreter::Interpreter;
+    use crate::core::ir::codegen::CodeGen;
+    use crate::core::lexer::lexer::Lexer;
+    use crate::core::parser::parser::Parser;
+
+    fn run_both(source: &str) -> (i32, i32) {
+        let mut parser = Parser::new(Lexer::new(source.to_string()), false);
+        let mtree = parser.analyze();
+        assert_eq!(parser.take_diagnostics(), Vec::new());
+
+        let stree = Converter::new(false).convert_tree(&mtree).unwrap();
+        if let Err((_, errors)) = Analyzer::new(false).analyze(stree.clone()) {
+            panic!("{:?}", errors);
+        }
+
+        let interpreted = Interpreter::new(false).run(&stree).unwrap();
+
+        let context = Context::create();
+        let mut codegen = CodeGen::new(&context, "ohl", false);
+        codegen.compile(&stree).unwrap();
+        let compiled = codegen.run_jit(OptimizationLevel::None).unwrap();
+
+        (interpreted, compiled)
+    }
+
+    pub(crate) fn assert_parity(source: &str, expected: i32) {
+        assert_eq!(run_both(source), (expected, expected));
+    }
+
+    #[test]
+    fn for_and_for_each() {
+        assert_parity(r#"
+            public int main() {
+                int sum = 0;
+                for (int i = 0; i < 10; i++) {
+                    if (i == 3) { continue; }
+                    if (i == 8) { break; }
+                    sum += i;
+                }
+                for j in 0..4 {
+                    sum = sum * 2 + j;
+                }
+                int repeated = 0;
+                for k in 1..=3 {
+                    repeated++;
+                    if (repeated == 2) { repeat; }
+                    sum += k;
+                }
+                int count = 0;
+                for m in 2147483645..=2147483647 {
+                    count++;
+                }
+                return sum * 10 + count;
+            }
+        "#, 4173);
+    }
+
+    #[test]
+    fn loop_variables_do_not_leak() {
+        assert_parity(r#"
+            public int main() {
+                int i = 100;
+                for (int i = 0; i < 3; i++) {}
+                int j = 200;
+                for j in 0..3 {}
+                return i + j;
+            }
+        "#, 300);
+    }
+
+    #[test]
+    fn shadowing_in_nested_blocks() {
+        assert_parity(r#"
+            public int twice(int n) {
+                {
+                    int n = 3;
+                    n = n + 1;
+                }
+                return n * 2;
+            }
+            public int main() {
+                int x = 1;
+                if (x == 1) {
+                    int x = 2;
+                    {
+                        int x = 3;
+                        x = x + 10;
+                    }
+                    x = x * 5;
+                } else {
+                    int x = 4;
+                }
+                int y = x;
+                while (y < 3) {
+                    int x = 100;
+                    y = y + 1;
+                }
+                match (y) {
+                    3 => { int x = 50; y = y + x; },
+                    default => y = 0,
+                }
+                return twice(5) * 1000 + x * 100 + y;
+            }
+        "#, 10153);
+    }
+
+    #[test]
+    fn shadowing_across_loops_and_defers() {
+        assert_parity(r#"
+            public int main() {
+                int result = 0;
+                int x = 1;
+                {
+                    defer result = result * 10 + x;
+                    int x = 5;
+                    result = x;
+                }
+                while (true) {
+                    defer result = result * 10 + x;
+                    {
+                        int x = 7;
+                        if (x == 7) { break; }
+                    }
+                }
+                int i = 100;
+                for (int i = 0; i < 3; i++) {
+                    int x = i * 2;
+                }
+                for i in 0..2 {
+                    int x = 9;
+                }
+                {
+                    int i = 4;
+                    {
+                        int i = 6;
+                    }
+                    result = result * 10 + i;
+                }
+                return result * 1000 + i + x;
+            }
+        "#, 5114101);
+    }
+
+    #[test]
+    fn match_arms() {
+        assert_parity(r#"
+            public int classify(int n) {
+                match (n) {
+                    0 => return 1,
+                    1..5 => return 2,
+                    5..=9 => return 3,
+                    default => return 4,
+                }
+                return 0;
+            }
+            public int letter(char c) {
+                match (c) {
+                    'a'..='z' => return 10,
+                    'A'..='Z' => return 20,
+                    default => return 30,
+                }
+                return 0;
+            }
+            public int word(string s) {
+                match (s) {
+                    "one" => return 100,
+                    "two" => return 200,
+                    default => return 300,
+                }
+                return 0;
+            }
+            public int main() {
+                return classify(0) + classify(4) + classify(9) + classify(12) + letter('q') + letter('Q') + letter('1') + word("two") + word("six");
+            }
+        "#, 570);
+    }
+
+    #[test]
+    fn match_arms_are_scopes() {
+        assert_parity(r#"
+            public int main() {
+                int y = 1;
+                int total = 0;
+                for n in 1..=3 {
+                    match (n) {
+                        1 => int y = 2,
+                        2 => { int y = 3; total = total + y; },
+                        default => total = total + y,
+                    }
+                }
+                return total * 10 + y;
+            }
+        "#, 41);
+    }
+
+    #[test]
+    fn defer_on_every_exit() {
+        assert_parity(r#"
+            public int main() {
+                int n = 0;
+                {
+                    defer n = n * 10 + 1;
+                    defer n = n * 10 + 2;
+                }
+                for (int i = 0; i < 4; i++) {
+                    defer n = n * 10 + 3;
+                    if (i == 1) { continue; }
+                    if (i == 2) { break; }
+                }
+                int r = 0;
+                while (r < 2) {
+                    r++;
+                    defer n = n + 1;
+                    if (r == 1) { repeat; }
+                }
+                defer n = 0;
+                return n;
+            }
+        "#, 21335);
+    }
+
+    #[test]
+    fn defer_reads_variables_visible_where_declared() {
+        assert_parity(r#"
+            public int main() {
+                int result = 0;
+                int x = 1;
+                {
+                    defer result = result * 10 + x;
+                    int x = 5;
+                    result = x;
+                }
+                while (true) {
+                    defer result = result * 10 + x;
+                    {
+                        int x = 7;
+                        if (x == 7) { break; }
+                    }
+                }
+                return result;
+            }
+        "#, 511);
+    }
+
+    #[test]
+    fn nullable_narrowing() {
+        assert_parity(r#"
+            public int? find(int[4] xs, int target) {
+                for (int i = 0; i < 4; i++) {
+                    if (xs[i] == target) {
+                        return i;
+                    }
+                }
+                return null;
+            }
+            public int main() {
+                int[4] xs = [5, 6, 7, 8];
+                int? a = find(xs, 7);
+                int? b = find(xs, 9);
+                int result = (a ?? -1) * 10 + (b ?? 4);
+                if (a != null) {
+                    result = result + a * 100;
+                }
+                if (b == null) {
+                    result = result + 1000;
+                }
+                int? c;
+                if (c == null) {
+                    c = 3;
+                }
+                return result + (c ?? 0);
+            }
+        "#, 1227);
+    }
+
+    #[test]
+    fn as_casts() {
+        assert_parity(r#"
+            public int main() {
+                int total = 3.9 as int;
+                total += -3.9 as int;
+                total += 'A' as int;
+                total += (65 as char) as int;
+                total += true as int;
+                total += "123" as int;
+                total += (" 2.5" as float * 2.0) as int;
+                total += ("x" as char) as int;
+                total += (1 + 2.5) as int;
+                if ((42 as string) == "42") { total += 1000; }
+                if (0 as boolean) { total += 5000; }
+                return total;
+            }
+        "#, 1382);
+    }
+
+    #[test]
+    fn falling_off_the_end_returns_zero() {
+        assert_parity(r#"
+            private boolean flag(int n) {
+                if (n > 0) { return true; }
+            }
+
+            private float half(int n) {
+                if (n > 0) { return n / 2.0; }
+            }
+
+            private string name(int n) {
+                if (n > 0) { return "some"; }
+            }
+
+            private int pick(int n) {
+                if (n > 0) { return 1; } else { return 2; }
+            }
+
+            public int main() {
+                int total = pick(0);
+                if (!flag(0)) { total += 10; }
+                if (half(0) == 0.0) { total += 100; }
+                return total + len(name(0)) + len(name(1)) * 1000;
+            }
+        "#, 4112);
+    }
+
+    #[test]
+    fn strings_convert_to_chars_by_utf8() {
+        assert_parity(r#"
+            public int main() {
+                int total = ("é" as char) as int;
+                total += ("€" as char) as int;
+                total += ("A" as char) as int;
+                string euro = "€";
+                if (("{euro}" as char) == '€') { total += 100000; }
+                return total;
+            }
+        "#, 108662);
+    }
+
+    #[test]
+    fn compound_assignment_to_elements_and_fields() {
+        assert_parity(r#"
+            class Counter {
+                int hits;
+                float scale;
+                string log;
+            }
+
+            public int main() {
+                int[3] xs = [1, 2, 3];
+                xs[0] += 10;
+                xs[1] *= xs[2];
+                xs[2]++;
+                ++xs[2];
+                int old = xs[0]--;
+                Counter c = Counter(0, 1.5, "");
+                c.hits += 5;
+                c.hits++;
+                c.scale *= 2.0;
+                c.log += "ab";
+                c.log += c.log;
+                Counter[2] cs = [c, Counter()];
+                cs[1].hits += 7;
+                cs[1].hits--;
+                return xs[0] + xs[1] * 10 + xs[2] * 100 + old * 1000 + c.hits * 100000 + (c.scale as int) * 1000000 + len(c.log) * 10000000 + cs[1].hits * 100000000;
+            }
+        "#, 643611570);
+    }
+
+    #[test]
+    fn string_operations() {
+        assert_parity(r#"
+            public int main() {
+                string a = "hello";
+                string b = a + ", " + "world";
+                b += "!";
+                int total = len(b) * 100 + (b[1] as int);
+                if (a < "help") { total += 10000; }
+                if ("abc" < "ab") { total += 20000; }
+                if (a != b) { total += 40000; }
+                string t = "{a}: {len(a) + 1} \{ok\}";
+                if (t == "hello: 6 \{ok\}") { total += 80000; }
+                string r = r"C:\dir";
+                total += len(r) * 1000000;
+                return total;
+            }
+        "#, 6131401);
+    }
+
+    #[test]
+    fn strings_survive_being_freed() {
+        assert_parity(r#"
+            class Box {
+                string label;
+            }
+
+            private string twice(string s) {
+                string doubled = s + s;
+                return doubled;
+            }
+
+            private string pick(string a, string b) {
+                string chosen = a;
+                if (len(b) > len(a)) {
+                    chosen = b;
+                }
+                return chosen;
+            }
+
+            public int main() {
+                string acc = "";
+                for (int i = 0; i < 5; i++) {
+                    string part = i as string;
+                    if (i == 3) {
+                        continue;
+                    }
+                    acc = acc + part;
+                    acc += "-";
+                }
+
+                string copy = acc;
+                acc = "reset";
+                string? maybe = copy + "!";
+                Box box = Box(copy);
+                copy = twice("ab");
+                string[2] names = [copy, box.label];
+                copy = "";
+
+                int total = len(acc) + len(names[0]) * 10 + len(names[1]) * 100;
+                {
+                    string inner = pick(names[0], box.label + "??");
+                    defer total += len(inner) * 1000;
+                    if (inner == box.label + "??") {
+                        total += 100000;
+                    }
+                }
+                print(twice(maybe ?? "x"));
+                return total + len(maybe ?? "") * 1000000;
+            }
+        "#, 9110845);
+    }
+
+    #[test]
+    fn replaced_strings_are_freed() {
+        let source = r#"
+            public int main() {
+                string s = "a";
+                for (int i = 0; i < 3; i++) {
+                    s = s + "b";
+                }
+                return len(s);
+            }
+        "#;
+        let mtree = Parser::new(Lexer::new(source.to_string()), false).analyze();
+        let stree = Converter::new(false).convert_tree(&mtree).unwrap();
+
+        let context = Context::create();
+        let mut codegen = CodeGen::new(&context, "ohl", false);
+        codegen.compile(&stree).unwrap();
+        assert!(codegen.print_ir().contains("call void @free"));
+        assert_eq!(codegen.run_jit(OptimizationLevel::None).unwrap(), 4);
+    }
+}
@@ -0,0 +1,130 @@
+use inkwell::IntPredicate;
+use inkwell::values::{BasicValueEnum, IntValue};
+use crate::core::analyzer::pattern::{Coverage, Pattern};
+use crate::core::converter::stree::STree;
+use crate::core::ir::codegen::CodeGen;
+use crate::core::lexer::token_type::TokenType;
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // Int and char literal arms become cases of a switch, whose default tests the range arms in order.
+    // Strings and booleans test every arm in order. Arms that earlier ones already cover are left out,
+    // so a literal inside an earlier range never gets its own case
+    pub fn compile_match(&mut self, scrutinee: &STree, arms: &[STree]) -> Result<(), String> {
+        let value = self.compile_expression(scrutinee)?;
+        let value = self.unwrap_nullable(value);
+        let switchable = matches!(value, BasicValueEnum::IntValue(i) if i.get_type() != self.context.bool_type());
+
+        let function = self.current_fn.unwrap();
+        let end_bb = self.context.append_basic_block(function, "match_end");
+
+        let mut coverage = Coverage::default();
+        let mut cases = Vec::new();
+        let mut tests = Vec::new();
+        let mut bodies = Vec::new();
+        let mut default_bb = None;
+
+        for arm in arms {
+            let STree::MATCH_ARM { pattern, body, span } = arm else {
+                return Err("Match may only contain arms".into());
+            };
+
+            let Some(pattern) = pattern else {
+                let arm_bb = self.context.append_basic_block(function, "match_default");
+                bodies.push((arm_bb, body));
+                coverage.default = Some(*span);
+                default_bb = Some(arm_bb);
+                // Nothing after the default arm is reachable
+                break;
+            };
+
+            let values = Pattern::from_tree(pattern).ok_or("Match patterns must be literals or ranges of literals")?;
+            if values.is_empty() || coverage.covers(&values) {
+                continue;
+            }
+            coverage.add(values, pattern.span());
+
+            let arm_bb = self.context.append_basic_block(function, "match_arm");
+            bodies.push((arm_bb, body));
+
+            if switchable && !matches!(pattern.as_ref(), STree::RANGE { .. }) {
+                match self.compile_expression(pattern)? {
+                    BasicValueEnum::IntValue(case) => cases.push((case, arm_bb)),
+                    _ => return Err("Match pattern type mismatch".into()),
+                }
+            } else {
+                tests.push((pattern.as_ref(), arm_bb));
+            }
+        }
+
+        let no_match_bb = default_bb.unwrap_or(end_bb);
+        let fallback_bb = match tests.is_empty() {
+            true => no_match_bb,
+            false => self.context.append_basic_block(function, "match_test"),
+        };
+
+        match value {
+            BasicValueEnum::IntValue(i) if switchable => {
+                self.builder.build_switch(i, fallback_bb, &cases).unwrap();
+            },
+            _ => {
+                self.builder.build_unconditional_branch(fallback_bb).unwrap();
+            },
+        }
+
+        // Compare chain, each failed test falls through to the next arm
+        let mut test_bb = fallback_bb;
+        for (index, (pattern, arm_bb)) in tests.iter().enumerate() {
+            self.builder.position_at_end(test_bb);
+            let matched = self.compile_pattern_test(value, pattern)?;
+
+            let next_bb = match index + 1 == tests.len() {
+                true => no_match_bb,
+                false => self.context.append_basic_block(function, "match_test"),
+            };
+            self.builder.build_conditional_branch(matched, *arm_bb, next_bb).unwrap();
+            test_bb = next_bb;
+        }
+
+        // Each arm is its own scope, like a block
+        let outer = self.variables.clone();
+        for (arm_bb, body) in bodies {
+            self.builder.position_at_end(arm_bb);
+            self.compile_statement(body)?;
+            self.variables = outer.clone();
+            if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+                self.builder.build_unconditional_branch(end_bb).unwrap();
+            }
+        }
+
+        self.builder.position_at_end(end_bb);
+        Ok(())
+    }
+
+    fn compile_pattern_test(&mut self, value: BasicValueEnum<'ctx>, pattern: &STree) -> Result<IntValue<'ctx>, String> {
+        if let STree::RANGE { start, end, inclusive, .. } = pattern {
+            let (value, start, end) = match (value, self.compile_expression(start)?, self.compile_expression(end)?) {
+                (BasicValueEnum::IntValue(v), BasicValueEnum::IntValue(s), BasicValueEnum::IntValue(e)) => {
+                    (self.promote_int(v), self.promote_int(s), self.promote_int(e))
+                },
+                _ => return Err("Range patterns need an int or char value".into()),
+            };
+
+            let above = self.builder.build_int_compare(IntPredicate::SGE, value, start, "range_start").unwrap();
+            let predicate = if *inclusive { IntPredicate::SLE } else { IntPredicate::SLT };
+            let below = self.builder.build_int_compare(predicate, value, end, "range_end").unwrap();
+            return Ok(self.builder.build_and(above, below, "in_range").unwrap());
+        }
+
+        match (value, self.compile_expression(pattern)?) {
+            (BasicValueEnum::IntValue(v), BasicValueEnum::IntValue(p)) => {
+                Ok(self.builder.build_int_compare(IntPredicate::EQ, v, p, "pattern_eq").unwrap())
+            },
+            (BasicValueEnum::PointerValue(v), BasicValueEnum::PointerValue(p)) => {
+                Ok(self.compile_string_expression(v, p, &TokenType::EQUAL)?.into_int_value())
+            },
+            _ => Err("Match pattern type mismatch".into()),
+        }
+    }
+
+}
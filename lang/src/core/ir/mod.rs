@@ -1,7 +1,17 @@
 pub mod codegen;
 pub mod print;
+pub mod jit;
+pub mod target;
+pub mod optimize;
 mod integer;
 mod float;
 mod statement;
 mod expression;
-mod function;
\ No newline at end of file
+mod function;
+mod power;
+mod matching;
+mod array;
+mod class;
+mod nullable;
+mod cast;
+mod string;
\ No newline at end of file
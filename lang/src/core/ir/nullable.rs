@@ -0,0 +1,141 @@
+use inkwell::types::{BasicTypeEnum, StructType};
+use inkwell::values::{BasicValue, BasicValueEnum, IntValue};
+use crate::core::converter::stree::STree;
+use crate::core::ir::codegen::CodeGen;
+use crate::core::lexer::token_type::TokenType;
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // T? is an unnamed { i1, T }, the flag is set when there is a value. Classes are named structs,
+    // so the two never mix up
+    pub fn nullable_type(&self, inner: BasicTypeEnum<'ctx>) -> StructType<'ctx> {
+        self.context.struct_type(&[self.context.bool_type().into(), inner], false)
+    }
+
+    pub fn is_nullable_type(&self, ty: BasicTypeEnum<'ctx>) -> bool {
+        match ty {
+            BasicTypeEnum::StructType(struct_type) => {
+                struct_type.get_name().is_none()
+                    && struct_type.count_fields() == 2
+                    && struct_type.get_field_type_at_index(0) == Some(self.context.bool_type().into())
+            },
+            _ => false,
+        }
+    }
+
+    // The analyzer only lets a nullable value through where a null check proved it is set
+    pub fn unwrap_nullable(&mut self, value: BasicValueEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        match value {
+            BasicValueEnum::StructValue(nullable) if self.is_nullable_type(value.get_type()) => {
+                self.builder.build_extract_value(nullable, 1, "unwrap").unwrap()
+            },
+            _ => value,
+        }
+    }
+
+    // Fits a value to the slot it is stored in, null and plain values are wrapped for a nullable slot
+    // and narrower numbers are widened
+    pub fn coerce(&mut self, value: BasicValueEnum<'ctx>, target: BasicTypeEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        if value.get_type() == target {
+            return value;
+        }
+
+        if !self.is_nullable_type(target) {
+            let value = self.unwrap_nullable(value);
+            return match self.scalar_type(target) {
+                Some(target) => self.widen(value, &target),
+                None => value,
+            };
+        }
+
+        match value {
+            BasicValueEnum::PointerValue(pointer) if pointer.is_null() => target.const_zero(),
+            _ => {
+                let struct_type = target.into_struct_type();
+                let inner = struct_type.get_field_type_at_index(1).unwrap();
+                let value = self.coerce(value, inner);
+                let set = self.context.bool_type().const_int(1, false);
+                let wrapped = self.builder.build_insert_value(struct_type.get_undef(), set, 0, "wrap").unwrap();
+                self.builder
+                    .build_insert_value(wrapped, value, 1, "wrap")
+                    .unwrap()
+                    .as_basic_value_enum()
+            },
+        }
+    }
+
+    // x == null and x != null only look at the flag
+    pub fn compile_null_check(&mut self, left: &STree, operator: &TokenType, right: &STree) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_null_check()");
+
+        let operand = if matches!(left, STree::NULL { .. }) { right } else { left };
+        let is_set = match operand {
+            STree::NULL { .. } => self.context.bool_type().const_zero(),
+            _ => {
+                let value = self.compile_expression(operand)?;
+                self.null_flag(value)
+            },
+        };
+
+        match operator {
+            TokenType::NOT_EQUAL => Ok(is_set.into()),
+            _ => Ok(self.builder.build_not(is_set, "is_null").unwrap().into()),
+        }
+    }
+
+    // The default is only computed when the value is null, both branches meet in a phi
+    pub fn compile_coalesce(&mut self, left: &STree, right: &STree) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_coalesce()");
+
+        if let STree::NULL { .. } = left {
+            return self.compile_expression(right);
+        }
+
+        let value = self.compile_expression(left)?;
+        if !self.is_nullable_type(value.get_type()) {
+            return Ok(value);
+        }
+
+        let function = self.current_fn.unwrap();
+        let value_bb = self.builder.get_insert_block().unwrap();
+        let default_bb = self.context.append_basic_block(function, "coalesce_default");
+        let end_bb = self.context.append_basic_block(function, "coalesce_end");
+
+        let is_set = self.null_flag(value);
+        self.builder.build_conditional_branch(is_set, end_bb, default_bb).unwrap();
+
+        self.builder.position_at_end(default_bb);
+        let default = self.compile_expression(right)?;
+        // A nullable default keeps the result nullable, T? ?? T? is T?
+        let result_type = match self.is_nullable_type(default.get_type()) || matches!(right, STree::NULL { .. }) {
+            true => value.get_type(),
+            false => value.get_type().into_struct_type().get_field_type_at_index(1).unwrap(),
+        };
+        let default = self.coerce(default, result_type);
+        let default_end_bb = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(end_bb).unwrap();
+
+        // The value is unwrapped where it is known to be set
+        self.builder.position_at_end(value_bb);
+        let terminator = value_bb.get_terminator().unwrap();
+        self.builder.position_before(&terminator);
+        let value = self.coerce(value, result_type);
+
+        self.builder.position_at_end(end_bb);
+        let phi = self.builder.build_phi(result_type, "coalesce").unwrap();
+        phi.add_incoming(&[(&value, value_bb), (&default, default_end_bb)]);
+
+        Ok(phi.as_basic_value())
+    }
+
+    fn null_flag(&mut self, value: BasicValueEnum<'ctx>) -> IntValue<'ctx> {
+        match value {
+            BasicValueEnum::StructValue(nullable) if self.is_nullable_type(value.get_type()) => {
+                self.builder.build_extract_value(nullable, 0, "is_set").unwrap().into_int_value()
+            },
+            BasicValueEnum::PointerValue(pointer) => self.builder.build_is_not_null(pointer, "is_set").unwrap(),
+            _ => self.context.bool_type().const_int(1, false),
+        }
+    }
+
+}
\ No newline at end of file
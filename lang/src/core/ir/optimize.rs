@@ -0,0 +1,57 @@
+use inkwell::OptimizationLevel;
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::TargetMachine;
+use crate::core::ir::codegen::CodeGen;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OptLevel {
+    #[value(name = "0")]
+    O0,
+    #[value(name = "1")]
+    O1,
+    #[value(name = "2")]
+    O2,
+    #[value(name = "3")]
+    O3,
+    #[value(name = "s")]
+    Os,
+}
+
+impl OptLevel {
+    // New pass manager pipeline for this level
+    pub fn pipeline(&self) -> &'static str {
+        match self {
+            OptLevel::O0 => "default<O0>",
+            OptLevel::O1 => "default<O1>",
+            OptLevel::O2 => "default<O2>",
+            OptLevel::O3 => "default<O3>",
+            OptLevel::Os => "default<Os>",
+        }
+    }
+
+    // Level used by the target machine and JIT when generating machine code
+    pub fn codegen_level(&self) -> OptimizationLevel {
+        match self {
+            OptLevel::O0 => OptimizationLevel::None,
+            OptLevel::O1 => OptimizationLevel::Less,
+            OptLevel::O2 | OptLevel::Os => OptimizationLevel::Default,
+            OptLevel::O3 => OptimizationLevel::Aggressive,
+        }
+    }
+}
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // Run the LLVM pass pipeline for the given level over the module
+    pub fn optimize(&self, machine: &TargetMachine, level: OptLevel) -> Result<(), String> {
+        self.logger.info(&format!("optimize({})", level.pipeline()));
+
+        let options = PassBuilderOptions::create();
+        options.set_verify_each(true);
+
+        self.module
+            .run_passes(level.pipeline(), machine, options)
+            .map_err(|e| format!("Optimization failed: {}", e))
+    }
+
+}
@@ -0,0 +1,146 @@
+use inkwell::{FloatPredicate, IntPredicate};
+use inkwell::intrinsics::Intrinsic;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, FloatValue, IntValue};
+use crate::core::ir::codegen::CodeGen;
+use crate::core::lexer::token_type::TokenType;
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // Power and root accept mixed int and float operands,
+    // int ^ int stays an int, everything else is computed as float
+    pub fn compile_power_expression(&mut self, lhs: BasicValueEnum<'ctx>, op: &TokenType, rhs: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_power_expression()");
+
+        if [lhs, rhs].iter().any(|v| matches!(v, BasicValueEnum::IntValue(i) if i.get_type() == self.context.bool_type())) {
+            return Err(format!(
+                "Type error: boolean value cannot be used in arithmetic expression '{:?}'", op
+            ));
+        }
+
+        match (op, lhs, rhs) {
+            (TokenType::POWER, BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
+                let (l, r) = (self.promote_int(l), self.promote_int(r));
+                Ok(self.compile_int_power(l, r).into())
+            },
+            (TokenType::POWER, BasicValueEnum::FloatValue(l), BasicValueEnum::IntValue(r)) => {
+                let r = self.promote_int(r);
+                self.call_intrinsic("llvm.powi", &[l.get_type().into(), r.get_type().into()], &[l.into(), r.into()])
+            },
+            (TokenType::POWER, l, r) => {
+                let (l, r) = (self.to_float(l)?, self.to_float(r)?);
+                self.call_intrinsic("llvm.pow", &[l.get_type().into()], &[l.into(), r.into()])
+            },
+            (TokenType::ROOT, l, r) => self.compile_root(l, r),
+
+            _ => Err(format!("Unsupported power operator: {:?}", op)),
+        }
+    }
+
+    // Exponentiation by squaring, exact up to i32 wrapping.
+    // A negative exponent gives 1 / base^-exp with integer division
+    fn compile_int_power(&mut self, base: IntValue<'ctx>, exponent: IntValue<'ctx>) -> IntValue<'ctx> {
+        let int_type = self.context.i32_type();
+        let zero = int_type.const_zero();
+        let one = int_type.const_int(1, false);
+
+        let function = self.current_fn.unwrap();
+        let entry_bb = self.builder.get_insert_block().unwrap();
+        let loop_bb = self.context.append_basic_block(function, "pow_loop");
+        let body_bb = self.context.append_basic_block(function, "pow_body");
+        let end_bb = self.context.append_basic_block(function, "pow_end");
+
+        let negative = self.builder.build_int_compare(IntPredicate::SLT, exponent, zero, "pow_neg").unwrap();
+        let negated = self.builder.build_int_neg(exponent, "pow_abs").unwrap();
+        let exponent = self.builder.build_select(negative, negated, exponent, "pow_exp").unwrap().into_int_value();
+        self.builder.build_unconditional_branch(loop_bb).unwrap();
+
+        self.builder.position_at_end(loop_bb);
+        let result = self.builder.build_phi(int_type, "pow_result").unwrap();
+        let square = self.builder.build_phi(int_type, "pow_square").unwrap();
+        let remaining = self.builder.build_phi(int_type, "pow_remaining").unwrap();
+        let remaining_value = remaining.as_basic_value().into_int_value();
+        let done = self.builder.build_int_compare(IntPredicate::EQ, remaining_value, zero, "pow_done").unwrap();
+        self.builder.build_conditional_branch(done, end_bb, body_bb).unwrap();
+
+        // Multiply in the current square for every set bit of the exponent
+        self.builder.position_at_end(body_bb);
+        let result_value = result.as_basic_value().into_int_value();
+        let square_value = square.as_basic_value().into_int_value();
+        let bit = self.builder.build_and(remaining_value, one, "pow_bit").unwrap();
+        let odd = self.builder.build_int_compare(IntPredicate::NE, bit, zero, "pow_odd").unwrap();
+        let product = self.builder.build_int_mul(result_value, square_value, "pow_mul").unwrap();
+        let next_result = self.builder.build_select(odd, product, result_value, "pow_next").unwrap().into_int_value();
+        let next_square = self.builder.build_int_mul(square_value, square_value, "pow_sq").unwrap();
+        let next_remaining = self.builder.build_right_shift(remaining_value, one, false, "pow_shr").unwrap();
+        self.builder.build_unconditional_branch(loop_bb).unwrap();
+
+        result.add_incoming(&[(&one, entry_bb), (&next_result, body_bb)]);
+        square.add_incoming(&[(&base, entry_bb), (&next_square, body_bb)]);
+        remaining.add_incoming(&[(&exponent, entry_bb), (&next_remaining, body_bb)]);
+
+        // Only divide when the exponent was negative, so a zero result is never a divisor
+        self.builder.position_at_end(end_bb);
+        let divisor = self.builder.build_select(negative, result_value, one, "pow_divisor").unwrap().into_int_value();
+        let inverse = self.builder.build_int_signed_div(one, divisor, "pow_inv").unwrap();
+        self.builder.build_select(negative, inverse, result_value, "pow").unwrap().into_int_value()
+    }
+
+    // x ^/ n is the n-th root of x. Square roots use llvm.sqrt, others pow(x, 1 / n).
+    // An odd integer degree also takes the root of a negative value
+    fn compile_root(&mut self, value: BasicValueEnum<'ctx>, degree: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+        let x = self.to_float(value)?;
+        let float_type = x.get_type();
+
+        if let BasicValueEnum::IntValue(n) = degree {
+            if n.get_sign_extended_constant() == Some(2) {
+                return self.call_intrinsic("llvm.sqrt", &[float_type.into()], &[x.into()]);
+            }
+        }
+
+        let n = self.to_float(degree)?;
+        let inverse = self.builder.build_float_div(float_type.const_float(1.0), n, "root_inv").unwrap();
+
+        let BasicValueEnum::IntValue(degree) = degree else {
+            return self.call_intrinsic("llvm.pow", &[float_type.into()], &[x.into(), inverse.into()]);
+        };
+
+        let degree = self.promote_int(degree);
+        let bit = self.builder.build_and(degree, degree.get_type().const_int(1, false), "root_bit").unwrap();
+        let odd = self.builder.build_int_compare(IntPredicate::NE, bit, degree.get_type().const_zero(), "root_odd").unwrap();
+        let below = self.builder.build_float_compare(FloatPredicate::OLT, x, float_type.const_zero(), "root_neg").unwrap();
+        let mirror = self.builder.build_and(odd, below, "root_mirror").unwrap();
+
+        let negated = self.builder.build_float_neg(x, "root_abs").unwrap();
+        let x = self.builder.build_select(mirror, negated, x, "root_x").unwrap().into_float_value();
+        let root = self.call_intrinsic("llvm.pow", &[float_type.into()], &[x.into(), inverse.into()])?.into_float_value();
+        let negated = self.builder.build_float_neg(root, "root_negate").unwrap();
+        Ok(self.builder.build_select(mirror, negated, root, "root").unwrap())
+    }
+
+    fn to_float(&self, value: BasicValueEnum<'ctx>) -> Result<FloatValue<'ctx>, String> {
+        match value {
+            BasicValueEnum::FloatValue(f) => Ok(f),
+            BasicValueEnum::IntValue(i) => {
+                let i = self.promote_int(i);
+                Ok(self.builder.build_signed_int_to_float(i, self.context.f32_type(), "itof").unwrap())
+            },
+            _ => Err("Power and root require numeric operands".to_string()),
+        }
+    }
+
+    pub fn call_intrinsic(&mut self, name: &str, types: &[BasicTypeEnum<'ctx>], args: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, String> {
+        let function = Intrinsic::find(name)
+            .and_then(|intrinsic| intrinsic.get_declaration(&self.module, types))
+            .ok_or(format!("Missing intrinsic {}", name))?;
+
+        let args: Vec<_> = args.iter().map(|arg| (*arg).into()).collect();
+        let call = self.builder.build_call(function, &args, name).unwrap();
+
+        match call.try_as_basic_value() {
+            inkwell::values::ValueKind::Basic(v) => Ok(v),
+            _ => Err(format!("Intrinsic {} returned no value", name)),
+        }
+    }
+
+}
@@ -35,7 +35,8 @@ impl<'ctx> CodeGen<'ctx> {
                     }
                 },
                 BasicValueEnum::FloatValue(_) => "%f\n",
-                BasicValueEnum::PointerValue(_) => "%s\n",
+                // Strings print by their length, so a 0 inside one does not end it
+                BasicValueEnum::PointerValue(_) => "%.*s\n",
 
                 _ => return Err("Unsupported print type".into()),
             };
@@ -65,11 +66,14 @@ impl<'ctx> CodeGen<'ctx> {
                 other => *other,
             };
 
-            self.builder.build_call(
-                printf,
-                &[fmt_ptr.into(), print_val.into()],
-                "printf_call"
-            ).unwrap();
+            let mut args = vec![fmt_ptr.into()];
+            if let BasicValueEnum::PointerValue(string) = print_val {
+                let length = self.build_string_length(string);
+                args.push(self.builder.build_int_truncate(length, self.context.i32_type(), "len").unwrap().into());
+            }
+            args.push(print_val.into());
+
+            self.builder.build_call(printf, &args, "printf_call").unwrap();
         }
 
         self.logger.indent_dec();
@@ -1,17 +1,22 @@
+use inkwell::IntPredicate;
+use inkwell::types::BasicTypeEnum;
 use inkwell::values::BasicValueEnum;
+use crate::core::analyzer::variable::VariableType;
 use crate::core::converter::stree::STree;
-use crate::core::ir::codegen::CodeGen;
+use crate::core::ir::codegen::{CodeGen, Variables};
 use crate::core::ir::statement;
+use crate::core::lexer::token_type::TokenType;
 
 impl<'ctx> CodeGen<'ctx> {
 
     pub fn compile_statement(&mut self, node: &STree) -> Result<Option<BasicValueEnum<'ctx>>, String> {
         self.logger.info("compile_statement()");
         self.logger.indent_inc();
+        self.span = node.span();
 
         match node {
 
-            STree::RETURN_STMT { expression } => {
+            STree::RETURN_STMT { expression, .. } => {
                 let func = self.current_fn.unwrap();
                 let ret_type = func.get_type().get_return_type();
 
@@ -20,13 +25,18 @@ impl<'ctx> CodeGen<'ctx> {
                         Err("Cannot return a value from a null (void) function".into())
                     }
 
+                    // Every enclosing block runs its deferred statements after the value is computed
                     (None, None) => {
+                        self.compile_deferred(0)?;
                         self.builder.build_return(None).unwrap();
                         Ok(None)
                     }
 
-                    (Some(_), Some(expr)) => {
+                    (Some(ret_type), Some(expr)) => {
                         let val = self.compile_expression(expr)?;
+                        let val = self.coerce(val, ret_type);
+                        let val = self.build_returned(val)?;
+                        self.compile_deferred(0)?;
                         self.builder.build_return(Some(&val)).unwrap();
                         Ok(Some(val))
                     }
@@ -38,25 +48,46 @@ impl<'ctx> CodeGen<'ctx> {
             }
 
             STree::VAR_DECL { id, expression, var_type, .. } => {
-                let val = self.compile_expression(expression)?;
-                let func = self.current_fn.unwrap();
-
                 let llvm_type = self.llvm_type(var_type)?;
-                let alloca = self.create_entry_block_alloca(func, id, llvm_type);
+                // Declarations without a value start zeroed, nullable ones as null
+                let val = match (expression.as_ref(), var_type) {
+                    (STree::BLANK { .. }, VariableType::STRING) => self.compile_string_literal("").into(),
+                    (STree::BLANK { .. }, _) => llvm_type.const_zero(),
+                    _ => {
+                        let val = self.compile_expression(expression)?;
+                        self.coerce(val, llvm_type)
+                    },
+                };
+                let func = self.current_fn.unwrap();
 
-                self.builder.build_store(alloca, val).unwrap();
-                self.variables.insert(id.clone(), (alloca, llvm_type));
+                match val {
+                    BasicValueEnum::PointerValue(string) => {
+                        let alloca = self.create_string_alloca(func, id);
+                        self.build_string_store(alloca, string)?;
+                        if let Some(owned) = self.owned.last_mut() {
+                            owned.push(alloca);
+                        }
+                        self.variables.insert(id.clone(), (alloca, llvm_type));
+                    },
+                    _ => {
+                        let alloca = self.create_entry_block_alloca(func, id, llvm_type);
+                        let val = self.build_owned(val)?;
+                        self.builder.build_store(alloca, val).unwrap();
+                        self.variables.insert(id.clone(), (alloca, llvm_type));
+                    },
+                }
                 self.logger.indent_dec();
                 Ok(Some(val))
             },
 
-            STree::VAR_ASSIGN { id, expression } => {
+            STree::VAR_ASSIGN { id, expression, .. } => {
                 let val = self.compile_expression(expression)?;
-                let (ptr, expected_typ) = self.variables.get(id).ok_or(format!("Undefined var {}", id))?;
+                let (ptr, expected_typ) = *self.variables.get(id).ok_or(format!("Undefined var {}", id))?;
+                let val = self.coerce(val, expected_typ);
 
                 let store_val = match (val, expected_typ) {
                     (BasicValueEnum::IntValue(i), inkwell::types::BasicTypeEnum::IntType(t))
-                    if *t == self.context.bool_type() => {
+                    if t == self.context.bool_type() => {
                         self.builder
                             .build_int_truncate(i, self.context.bool_type(), "to_bool")
                             .unwrap()
@@ -65,16 +96,91 @@ impl<'ctx> CodeGen<'ctx> {
                     _ => val,
                 };
 
-                self.builder.build_store(*ptr, store_val).unwrap();
+                match store_val {
+                    BasicValueEnum::PointerValue(string) => self.build_string_store(ptr, string)?,
+                    _ => {
+                        let store_val = self.build_owned(store_val)?;
+                        self.builder.build_store(ptr, store_val).unwrap();
+                    },
+                }
+                self.logger.indent_dec();
+                Ok(Some(val))
+            },
+
+            STree::INDEX_ASSIGN { array, index, expression, span } => {
+                self.logger.info("compile_index_assignment()");
+
+                let val = self.compile_expression(expression)?;
+                let (pointer, element_type) = self.compile_element_pointer(array, index, *span)?;
+                let val = self.coerce(val, element_type);
+                let val = self.build_owned(val)?;
+
+                self.builder.build_store(pointer, val).unwrap();
                 self.logger.indent_dec();
                 Ok(Some(val))
             },
 
-            STree::IF_STMT { condition, then_block, else_block } => {
+            STree::MEMBER_ASSIGN { object, member, expression, .. } => {
+                self.logger.info("compile_member_assignment()");
+
+                let val = self.compile_expression(expression)?;
+                let (pointer, field_type) = self.compile_field_pointer(object, member)?;
+                let val = self.coerce(val, field_type);
+                let val = self.build_owned(val)?;
+
+                self.builder.build_store(pointer, val).unwrap();
+                self.logger.indent_dec();
+                Ok(Some(val))
+            },
+
+            STree::COMPOUND_ASSIGN { target, operator, expression, .. } => {
+                self.logger.info("compile_compound_assignment()");
+
+                let (ptr, ty) = self.compile_place(target)?;
+                let current = self.builder.build_load(ty, ptr, "current").unwrap();
+                let val = self.compile_expression(expression)?;
+                let (current, val) = self.widen_operands(current, val);
+
+                let result = match (current, val) {
+                    (l, r) if matches!(operator, TokenType::POWER | TokenType::ROOT) => {
+                        self.compile_power_expression(l, operator, r)?
+                    },
+                    (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
+                        self.compile_int_expression(l, r, operator)?
+                    },
+                    (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => {
+                        self.compile_float_expression(l, r, operator)?
+                    },
+                    (BasicValueEnum::PointerValue(l), BasicValueEnum::PointerValue(r)) => {
+                        self.compile_string_expression(l, r, operator)?
+                    },
+                    _ => return Err(format!("Type mismatch in assignment: {:?}", operator)),
+                };
+
+                // Only a plain string variable frees what it held, a nullable one keeps its string inside
+                let variable = match target.as_ref() {
+                    STree::ID { name, .. } => self.variables.get(name).map(|(_, ty)| *ty),
+                    _ => None,
+                };
+                match (result, variable) {
+                    (BasicValueEnum::PointerValue(string), Some(BasicTypeEnum::PointerType(_))) => {
+                        self.build_string_store(ptr, string)?;
+                    },
+                    _ => {
+                        let result = self.build_owned(result)?;
+                        self.builder.build_store(ptr, result).unwrap();
+                    },
+                }
+                self.logger.indent_dec();
+                Ok(Some(result))
+            },
+
+            STree::IF_STMT { condition, then_block, else_block, .. } => {
                 self.logger.info("compile_if()");
                 self.logger.indent_inc();
 
                 let cond_val = self.compile_expression(condition)?;
+                let cond_val = self.unwrap_nullable(cond_val);
 
                 let condition_bool = match cond_val {
                     BasicValueEnum::IntValue(i) if i.get_type() == self.context.bool_type() => i,
@@ -128,7 +234,17 @@ impl<'ctx> CodeGen<'ctx> {
                 Ok(None)
             },
 
-            STree::WHILE_STMT { condition, body } => {
+            STree::MATCH_STMT { scrutinee, arms, .. } => {
+                self.logger.info("compile_match()");
+                self.logger.indent_inc();
+
+                self.compile_match(scrutinee, arms)?;
+
+                self.logger.indent_dec();
+                Ok(None)
+            },
+
+            STree::WHILE_STMT { condition, body, .. } => {
                 self.logger.info("compile_while()");
                 self.logger.indent_inc();
 
@@ -144,19 +260,20 @@ impl<'ctx> CodeGen<'ctx> {
                 // Condition
                 self.builder.position_at_end(cond_bb);
                 let cond_val = self.compile_expression(condition)?;
+                let cond_val = self.unwrap_nullable(cond_val);
 
                 let cond_bool = match cond_val {
                     BasicValueEnum::IntValue(i) if i.get_type() == self.context.bool_type() => i,
                     _ => return Err("While condition must be boolean".into()),
                 };
                 self.builder
-                    .build_conditional_branch(cond_bool, body_bb, body_bb)
+                    .build_conditional_branch(cond_bool, body_bb, end_bb)
                     .unwrap();
 
                 // Body
                 self.builder.position_at_end(body_bb);
 
-                self.loop_stack.push((end_bb, cond_bb, cond_bb));
+                self.loop_stack.push((end_bb, cond_bb, cond_bb, self.deferred.len()));
                 let result = self.compile_statement(body);
                 self.loop_stack.pop();
                 result?;
@@ -178,7 +295,7 @@ impl<'ctx> CodeGen<'ctx> {
                 Ok(None)
             },
 
-            STree::DO_WHILE_STMT { body, condition } => {
+            STree::DO_WHILE_STMT { body, condition, .. } => {
                 let function = self.current_fn.unwrap();
 
                 let body_bb = self.context.append_basic_block(function, "do_body");
@@ -191,7 +308,7 @@ impl<'ctx> CodeGen<'ctx> {
 
                 // Body
                 self.builder.position_at_end(body_bb);
-                self.loop_stack.push((end_bb, cond_bb, cond_bb));
+                self.loop_stack.push((end_bb, cond_bb, cond_bb, self.deferred.len()));
                 let result = self.compile_statement(body);
                 self.loop_stack.pop();
                 result?;
@@ -204,6 +321,7 @@ impl<'ctx> CodeGen<'ctx> {
                 self.builder.position_at_end(cond_bb);
 
                 let cond_val = self.compile_expression(condition)?;
+                let cond_val = self.unwrap_nullable(cond_val);
                 let cond_bool = match cond_val {
                     BasicValueEnum::IntValue(i) if i.get_type() == self.context.bool_type() => i,
                     _ => return Err("Do-while condition must be boolean".into()),
@@ -219,74 +337,225 @@ impl<'ctx> CodeGen<'ctx> {
                 Ok(None)
             },
 
-            STree::BREAK => {
+            STree::FOR_STMT { init, condition, step, body, .. } => {
+                self.logger.info("compile_for()");
+                self.logger.indent_inc();
+
+                // The loop variable is only visible inside the loop
+                let outer = self.variables.clone();
+                if let Some(init) = init {
+                    self.compile_statement(init)?;
+                }
+
+                let function = self.current_fn.unwrap();
+                let cond_bb = self.context.append_basic_block(function, "for_cond");
+                let body_bb = self.context.append_basic_block(function, "for_body");
+                let step_bb = self.context.append_basic_block(function, "for_step");
+                let end_bb = self.context.append_basic_block(function, "for_end");
+
+                if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+                    self.builder.build_unconditional_branch(cond_bb).unwrap();
+                }
+
+                // Condition
+                self.builder.position_at_end(cond_bb);
+                let cond_val = self.compile_expression(condition)?;
+                let cond_val = self.unwrap_nullable(cond_val);
+
+                let cond_bool = match cond_val {
+                    BasicValueEnum::IntValue(i) if i.get_type() == self.context.bool_type() => i,
+                    _ => return Err("For condition must be boolean".into()),
+                };
+                self.builder
+                    .build_conditional_branch(cond_bool, body_bb, end_bb)
+                    .unwrap();
+
+                // Body, continue runs the step and repeat skips it
+                self.builder.position_at_end(body_bb);
+
+                self.loop_stack.push((end_bb, step_bb, cond_bb, self.deferred.len()));
+                let result = self.compile_statement(body);
+                self.loop_stack.pop();
+                result?;
+
+                if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+                    self.builder.build_unconditional_branch(step_bb).unwrap();
+                }
+
+                // Step
+                self.builder.position_at_end(step_bb);
+                if let Some(step) = step {
+                    self.compile_statement(step)?;
+                }
+                self.builder.build_unconditional_branch(cond_bb).unwrap();
+
+                // End
+                self.builder.position_at_end(end_bb);
+                self.variables = outer;
+
+                self.logger.indent_dec();
+
+                Ok(None)
+            },
+
+            STree::FOR_EACH_STMT { id, start, end, inclusive, body, .. } => {
+                self.logger.info("compile_for_each()");
+                self.logger.indent_inc();
+
+                // Bounds are evaluated once, before the first pass
+                let start_val = match self.compile_expression(start)? {
+                    BasicValueEnum::IntValue(i) => i,
+                    _ => return Err("Range start must be an int".into()),
+                };
+                let end_val = match self.compile_expression(end)? {
+                    BasicValueEnum::IntValue(i) => i,
+                    _ => return Err("Range end must be an int".into()),
+                };
+
+                let function = self.current_fn.unwrap();
+                let int_type = self.context.i32_type();
+                let alloca = self.create_entry_block_alloca(function, id, int_type.into());
+                self.builder.build_store(alloca, start_val).unwrap();
+
+                let shadowed = self.variables.insert(id.clone(), (alloca, int_type.into()));
+
+                let cond_bb = self.context.append_basic_block(function, "for_each_cond");
+                let body_bb = self.context.append_basic_block(function, "for_each_body");
+                let step_bb = self.context.append_basic_block(function, "for_each_step");
+                let end_bb = self.context.append_basic_block(function, "for_each_end");
+
+                self.builder.build_unconditional_branch(cond_bb).unwrap();
+
+                // Condition
+                self.builder.position_at_end(cond_bb);
+                let current = self.builder.build_load(int_type, alloca, id).unwrap().into_int_value();
+                let predicate = if *inclusive { IntPredicate::SLE } else { IntPredicate::SLT };
+                let in_range = self.builder
+                    .build_int_compare(predicate, current, end_val, "in_range")
+                    .unwrap();
+                self.builder
+                    .build_conditional_branch(in_range, body_bb, end_bb)
+                    .unwrap();
+
+                // Body
+                self.builder.position_at_end(body_bb);
+
+                self.loop_stack.push((end_bb, step_bb, cond_bb, self.deferred.len()));
+                let result = self.compile_statement(body);
+                self.loop_stack.pop();
+                result?;
+
+                if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+                    self.builder.build_unconditional_branch(step_bb).unwrap();
+                }
+
+                // Step, an inclusive range stops at its end so '..= 2147483647' cannot overflow
+                self.builder.position_at_end(step_bb);
+                let current = self.builder.build_load(int_type, alloca, id).unwrap().into_int_value();
+                if *inclusive {
+                    let increment_bb = self.context.append_basic_block(function, "for_each_increment");
+                    let at_end = self.builder
+                        .build_int_compare(IntPredicate::EQ, current, end_val, "at_end")
+                        .unwrap();
+                    self.builder
+                        .build_conditional_branch(at_end, end_bb, increment_bb)
+                        .unwrap();
+                    self.builder.position_at_end(increment_bb);
+                }
+                let next = self.builder
+                    .build_int_add(current, int_type.const_int(1, false), "next")
+                    .unwrap();
+                self.builder.build_store(alloca, next).unwrap();
+                self.builder.build_unconditional_branch(cond_bb).unwrap();
+
+                // End
+                self.builder.position_at_end(end_bb);
+
+                match shadowed {
+                    Some(variable) => self.variables.insert(id.clone(), variable),
+                    None => self.variables.remove(id),
+                };
+
+                self.logger.indent_dec();
+
+                Ok(None)
+            },
+
+            STree::BREAK { .. } => {
                 self.logger.info("compile_break()");
 
-                let (break_target, ..) = self
+                let (break_target, .., depth) = *self
                     .loop_stack
                     .last()
                     .ok_or("break used outside of loop")?;
 
+                self.compile_deferred(depth)?;
                 self.builder
-                    .build_unconditional_branch(*break_target)
+                    .build_unconditional_branch(break_target)
                     .unwrap();
 
                 Ok(None)
             },
 
-            STree::CONTINUE => {
+            STree::CONTINUE { .. } => {
                 self.logger.info("compile_continue()");
 
-                let (_, continue_target, _) = self
+                let (_, continue_target, _, depth) = *self
                     .loop_stack
                     .last()
                     .ok_or("continue used outside of loop")?;
 
+                self.compile_deferred(depth)?;
                 self.builder
-                    .build_unconditional_branch(*continue_target)
+                    .build_unconditional_branch(continue_target)
                     .unwrap();
 
                 Ok(None)
             },
 
-            STree::REPEAT => {
+            STree::REPEAT { .. } => {
                 self.logger.info("compile_repeat()");
 
-                let (.., repeat_target) = self
+                let (.., repeat_target, depth) = *self
                     .loop_stack
                     .last()
                     .ok_or("repeat used outside of loop")?;
 
+                self.compile_deferred(depth)?;
                 self.builder
-                    .build_unconditional_branch(*repeat_target)
+                    .build_unconditional_branch(repeat_target)
                     .unwrap();
 
                 Ok(None)
             },
 
-            STree::FUNCTION_CALL { callee, args } => {
-                self.compile_function_call(callee, args)?; // ignore result
+            // The result is ignored, a returned string is freed right away
+            STree::FUNCTION_CALL { callee, args, .. } => {
+                if let Some(BasicValueEnum::PointerValue(string)) = self.compile_function_call(callee, args)? {
+                    self.build_string_drop(string)?;
+                }
                 Ok(None)
             },
 
-            STree::BLOCK { statements } => {
-                self.logger.info("compile_block()");
-                self.logger.indent_inc();
-                let mut last = None;
-                for s in statements {
-                    last = self.compile_statement(s)?;
-                }
-                self.logger.indent_dec();
-                Ok(last)
+            STree::BLOCK { statements, .. } => self.compile_block(statements),
+
+            // Outside a block the deferred statement is its own scope, so it runs right away
+            STree::DEFER { statement, .. } => {
+                self.compile_statement(statement)?;
+                Ok(None)
             },
 
             STree::VAR_TYPE { .. } => Ok(None),
-            STree::BLANK => Ok(None),
-            STree::NULL => Ok(None),
+            STree::BLANK { .. } => Ok(None),
+            STree::NULL { .. } => Ok(None),
 
-            STree::PRINT { expression } => {
+            STree::PRINT { expression, .. } => {
                 let val = self.compile_expression(expression)?;
+                let val = self.unwrap_nullable(val);
                 self.build_print(&[val])?;
+                if let BasicValueEnum::PointerValue(string) = val {
+                    self.build_string_drop(string)?;
+                }
                 Ok(None)
             },
             STree::LIT_INT { .. }
@@ -294,7 +563,8 @@ impl<'ctx> CodeGen<'ctx> {
             | STree::LIT_BOOL { .. }
             | STree::ID { .. }
             | STree::EXPR { .. }
-            | STree::PRFX_EXPR { .. } => {
+            | STree::PRFX_EXPR { .. }
+            | STree::PTFX_EXPR { .. } => {
                 let v = self.compile_expression(node)?;
                 Ok(Some(v))
             }
@@ -306,4 +576,65 @@ impl<'ctx> CodeGen<'ctx> {
         }
     }
 
+    // Deferred statements are emitted on every edge leaving the block, the latest first
+    // Declarations inside the block are dropped at its end, so shadowed variables come back
+    pub fn compile_block(&mut self, statements: &[STree]) -> Result<Option<BasicValueEnum<'ctx>>, String> {
+        self.logger.info("compile_block()");
+        self.logger.indent_inc();
+
+        let outer = self.variables.clone();
+        self.deferred.push(Vec::new());
+        self.owned.push(Vec::new());
+        let mut last = Ok(None);
+        for statement in statements {
+            if let STree::DEFER { statement, .. } = statement {
+                let visible = self.variables.clone();
+                if let Some(scope) = self.deferred.last_mut() {
+                    scope.push((statement.as_ref().clone(), visible));
+                }
+                continue;
+            }
+
+            last = self.compile_statement(statement);
+            if last.is_err() {
+                break;
+            }
+        }
+
+        // Falling off the end, jumps and returns have already emitted them
+        if last.is_ok() && self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+            let depth = self.deferred.len() - 1;
+            last = self.compile_deferred(depth).and(last);
+        }
+        self.deferred.pop();
+        self.owned.pop();
+        self.variables = outer;
+
+        self.logger.indent_dec();
+        last
+    }
+
+    // Emits the deferred statements of every block from depth inwards, innermost block first,
+    // then frees the strings of its variables. Each one sees the variables of the place it was declared
+    pub fn compile_deferred(&mut self, depth: usize) -> Result<(), String> {
+        let current = self.variables.clone();
+        for level in (depth..self.deferred.len()).rev() {
+            let pending: Vec<(STree, Variables<'ctx>)> = self.deferred[level].iter().rev().cloned().collect();
+            for (statement, visible) in pending {
+                self.variables = visible;
+                let result = self.compile_statement(&statement);
+                if result.is_err() {
+                    self.variables = current;
+                    return result.map(|_| ());
+                }
+            }
+
+            for place in self.owned[level].clone() {
+                self.build_string_release(place)?;
+            }
+        }
+        self.variables = current;
+        Ok(())
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,308 @@
+use inkwell::IntPredicate;
+use inkwell::module::Linkage;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue};
+use crate::core::converter::stree::STree;
+use crate::core::ir::codegen::CodeGen;
+use crate::core::lexer::token_type::TokenType;
+use crate::core::util::location::Span;
+
+// A string points at its bytes, which end in a 0 so C functions can read them.
+// The length in bytes is stored as an i64 right before the first byte, and before that an i64 owner word.
+// No two variables, elements or fields share a heap string: storing one that is already held stores a copy.
+// String variables free theirs when reassigned and when they go out of scope, a temporary is freed by the
+// join, comparison or print that uses it. Strings held by elements, fields and nullable variables are never freed
+
+// Owner words, literals are never freed
+const STRING_LITERAL: u64 = 0;
+// A new heap string nothing holds yet
+const STRING_TEMPORARY: u64 = 1;
+// A heap string a variable, element or field holds
+const STRING_HELD: u64 = 2;
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // Literals are constant globals laid out like any other string
+    pub fn compile_string_literal(&mut self, value: &str) -> PointerValue<'ctx> {
+        let owner = self.context.i64_type().const_int(STRING_LITERAL, false);
+        let length = self.context.i64_type().const_int(value.len() as u64, false);
+        let bytes = self.context.const_string(value.as_bytes(), true);
+        let literal = self.context.const_struct(&[owner.into(), length.into(), bytes.into()], true);
+
+        let global = self.module.add_global(literal.get_type(), None, "str");
+        global.set_initializer(&literal);
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+        global.set_unnamed_addr(true);
+
+        self.builder.build_struct_gep(literal.get_type(), global.as_pointer_value(), 2, "str").unwrap()
+    }
+
+    // Room for length bytes and the closing 0, with the length already set. The string starts as a temporary
+    pub fn build_string_alloc(&mut self, length: IntValue<'ctx>) -> Result<PointerValue<'ctx>, String> {
+        let size_type = self.context.i64_type();
+        let ptr_type = self.context.ptr_type(inkwell::AddressSpace::default());
+        let malloc = self.external("malloc", ptr_type.fn_type(&[size_type.into()], false));
+
+        let size = self.builder.build_int_add(length, size_type.const_int(17, false), "str_size").unwrap();
+        let block = self.call_value(malloc, &[size.into()])?.into_pointer_value();
+        self.builder.build_store(block, size_type.const_int(STRING_TEMPORARY, false)).unwrap();
+        let prefix = self.byte_offset(block, size_type.const_int(8, false), "str_len_ptr");
+        self.builder.build_store(prefix, length).unwrap();
+
+        let data = self.byte_offset(block, size_type.const_int(16, false), "str_data");
+        let end = self.byte_offset(data, length, "str_end");
+        self.builder.build_store(end, self.context.i8_type().const_zero()).unwrap();
+        Ok(data)
+    }
+
+    // String variables start out null, so freeing one that was never assigned does nothing
+    pub fn create_string_alloca(&self, function: FunctionValue<'ctx>, name: &str) -> PointerValue<'ctx> {
+        let ptr_type = self.context.ptr_type(inkwell::AddressSpace::default());
+        let alloca = self.create_entry_block_alloca(function, name, ptr_type.into());
+
+        let builder = self.context.create_builder();
+        match alloca.as_instruction().and_then(|alloca| alloca.get_next_instruction()) {
+            Some(next) => builder.position_before(&next),
+            None => builder.position_at_end(function.get_first_basic_block().unwrap()),
+        }
+        builder.build_store(alloca, ptr_type.const_null()).unwrap();
+        alloca
+    }
+
+    // Stores into a string variable, freeing the string it held before
+    pub fn build_string_store(&mut self, place: PointerValue<'ctx>, string: PointerValue<'ctx>) -> Result<(), String> {
+        let ptr_type = self.context.ptr_type(inkwell::AddressSpace::default());
+        let string = self.build_string_hold(string, STRING_HELD, true)?;
+        let old = self.builder.build_load(ptr_type, place, "old_str").unwrap().into_pointer_value();
+        self.builder.build_store(place, string).unwrap();
+        self.build_string_free(old, STRING_HELD)
+    }
+
+    // Frees the string a variable holds when it goes out of scope
+    pub fn build_string_release(&mut self, place: PointerValue<'ctx>) -> Result<(), String> {
+        let ptr_type = self.context.ptr_type(inkwell::AddressSpace::default());
+        let string = self.builder.build_load(ptr_type, place, "str").unwrap().into_pointer_value();
+        self.build_string_free(string, STRING_HELD)?;
+        self.builder.build_store(place, ptr_type.const_null()).unwrap();
+        Ok(())
+    }
+
+    // Frees a string that was only needed to compute something else
+    pub fn build_string_drop(&mut self, string: PointerValue<'ctx>) -> Result<(), String> {
+        self.build_string_free(string, STRING_TEMPORARY)
+    }
+
+    // The value an element, field or nullable variable stores, a string in it is taken over or copied
+    pub fn build_owned(&mut self, value: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+        self.map_string(value, |codegen, string| codegen.build_string_hold(string, STRING_HELD, true))
+    }
+
+    // A returned string is a temporary of the caller, the variables of the callee free theirs on the way out
+    pub fn build_returned(&mut self, value: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+        self.map_string(value, |codegen, string| codegen.build_string_hold(string, STRING_TEMPORARY, false))
+    }
+
+    fn map_string(
+        &mut self,
+        value: BasicValueEnum<'ctx>,
+        f: impl FnOnce(&mut Self, PointerValue<'ctx>) -> Result<PointerValue<'ctx>, String>
+    ) -> Result<BasicValueEnum<'ctx>, String> {
+        match value {
+            BasicValueEnum::PointerValue(string) => Ok(f(self, string)?.into()),
+            BasicValueEnum::StructValue(nullable) if self.is_nullable_type(value.get_type()) => {
+                let BasicValueEnum::PointerValue(string) = self.builder.build_extract_value(nullable, 1, "str").unwrap() else {
+                    return Ok(value);
+                };
+                let string = f(self, string)?;
+                Ok(self.builder.build_insert_value(nullable, string, 1, "str").unwrap().into_struct_value().into())
+            },
+            _ => Ok(value),
+        }
+    }
+
+    // A held string is copied, the copy gets the given owner word. With adopt a temporary is held in place
+    fn build_string_hold(&mut self, string: PointerValue<'ctx>, copy_owner: u64, adopt: bool) -> Result<PointerValue<'ctx>, String> {
+        let size_type = self.context.i64_type();
+        let function = self.current_fn.unwrap();
+        let start_bb = self.builder.get_insert_block().unwrap();
+        let check_bb = self.context.append_basic_block(function, "str_check");
+        let copy_bb = self.context.append_basic_block(function, "str_copy");
+        let end_bb = self.context.append_basic_block(function, "str_held");
+
+        let is_null = self.builder.build_is_null(string, "str_null").unwrap();
+        self.builder.build_conditional_branch(is_null, end_bb, check_bb).unwrap();
+
+        self.builder.position_at_end(check_bb);
+        let (owner_ptr, owner) = self.build_string_owner(string);
+        let mut cases = vec![(size_type.const_int(STRING_HELD, false), copy_bb)];
+        let mut incoming = vec![(string, start_bb), (string, check_bb)];
+        if adopt {
+            let adopt_bb = self.context.append_basic_block(function, "str_adopt");
+            cases.push((size_type.const_int(STRING_TEMPORARY, false), adopt_bb));
+            incoming.push((string, adopt_bb));
+
+            self.builder.position_at_end(adopt_bb);
+            self.builder.build_store(owner_ptr, size_type.const_int(STRING_HELD, false)).unwrap();
+            self.builder.build_unconditional_branch(end_bb).unwrap();
+            self.builder.position_at_end(check_bb);
+        }
+        self.builder.build_switch(owner, end_bb, &cases).unwrap();
+
+        self.builder.position_at_end(copy_bb);
+        let length = self.build_string_length(string);
+        let copy = self.build_string_alloc(length)?;
+        self.builder.build_memcpy(copy, 1, string, 1, length).unwrap();
+        let (copy_owner_ptr, _) = self.build_string_owner(copy);
+        self.builder.build_store(copy_owner_ptr, size_type.const_int(copy_owner, false)).unwrap();
+        let copy_end_bb = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(end_bb).unwrap();
+
+        self.builder.position_at_end(end_bb);
+        incoming.push((copy, copy_end_bb));
+        let phi = self.builder.build_phi(string.get_type(), "str_held").unwrap();
+        for (value, block) in &incoming {
+            phi.add_incoming(&[(value, *block)]);
+        }
+        Ok(phi.as_basic_value().into_pointer_value())
+    }
+
+    // Frees a string with the given owner word, null and any other string are left alone
+    fn build_string_free(&mut self, string: PointerValue<'ctx>, owner: u64) -> Result<(), String> {
+        let size_type = self.context.i64_type();
+        let ptr_type = self.context.ptr_type(inkwell::AddressSpace::default());
+        let free = self.external("free", self.context.void_type().fn_type(&[ptr_type.into()], false));
+
+        let function = self.current_fn.unwrap();
+        let check_bb = self.context.append_basic_block(function, "str_check");
+        let free_bb = self.context.append_basic_block(function, "str_free");
+        let end_bb = self.context.append_basic_block(function, "str_kept");
+
+        let is_null = self.builder.build_is_null(string, "str_null").unwrap();
+        self.builder.build_conditional_branch(is_null, end_bb, check_bb).unwrap();
+
+        self.builder.position_at_end(check_bb);
+        let (block, word) = self.build_string_owner(string);
+        let matches = self.builder
+            .build_int_compare(IntPredicate::EQ, word, size_type.const_int(owner, false), "str_owned")
+            .unwrap();
+        self.builder.build_conditional_branch(matches, free_bb, end_bb).unwrap();
+
+        self.builder.position_at_end(free_bb);
+        self.builder.build_call(free, &[block.into()], "").unwrap();
+        self.builder.build_unconditional_branch(end_bb).unwrap();
+
+        self.builder.position_at_end(end_bb);
+        Ok(())
+    }
+
+    // The owner word is the start of the heap block
+    fn build_string_owner(&mut self, string: PointerValue<'ctx>) -> (PointerValue<'ctx>, IntValue<'ctx>) {
+        let size_type = self.context.i64_type();
+        let pointer = self.byte_offset(string, size_type.const_int(-16i64 as u64, true), "str_owner_ptr");
+        let owner = self.builder.build_load(size_type, pointer, "str_owner").unwrap().into_int_value();
+        (pointer, owner)
+    }
+
+    pub fn build_string_length(&mut self, string: PointerValue<'ctx>) -> IntValue<'ctx> {
+        let size_type = self.context.i64_type();
+        let prefix = self.byte_offset(string, size_type.const_int(-8i64 as u64, true), "str_len_ptr");
+        self.builder.build_load(size_type, prefix, "str_len").unwrap().into_int_value()
+    }
+
+    // + joins two strings into a new one, the comparisons order them byte by byte
+    pub fn compile_string_expression(&mut self, l: PointerValue<'ctx>, r: PointerValue<'ctx>, op: &TokenType) -> Result<BasicValueEnum<'ctx>, String> {
+        if *op == TokenType::PLUS {
+            return Ok(self.build_string_concat(l, r)?.into());
+        }
+
+        let predicate = match op {
+            TokenType::EQUAL => IntPredicate::EQ,
+            TokenType::NOT_EQUAL => IntPredicate::NE,
+            TokenType::LESS => IntPredicate::SLT,
+            TokenType::GREATER => IntPredicate::SGT,
+            TokenType::LESS_EQUAL => IntPredicate::SLE,
+            TokenType::GREATER_EQUAL => IntPredicate::SGE,
+            _ => return Err(format!("Unsupported string operator: {:?}", op)),
+        };
+
+        let order = self.build_string_compare(l, r)?;
+        let zero = self.context.i32_type().const_zero();
+        Ok(self.builder.build_int_compare(predicate, order, zero, "str_cmp").unwrap().into())
+    }
+
+    // s[i] is the byte at i as a char, checked against the length
+    pub fn compile_string_element_pointer(&mut self, string: PointerValue<'ctx>, index: IntValue<'ctx>, span: Span) -> Result<PointerValue<'ctx>, String> {
+        let int_type = self.context.i32_type();
+        let length = self.build_string_length(string);
+        let length = self.builder.build_int_truncate(length, int_type, "len").unwrap();
+        self.build_bounds_check(index, length, span)?;
+
+        let index = self.builder.build_int_s_extend(index, self.context.i64_type(), "index").unwrap();
+        Ok(self.byte_offset(string, index, "char_ptr"))
+    }
+
+    // len(x) of a string reads its prefix, an array's length is known from its type
+    pub fn compile_length(&mut self, args: &[STree]) -> Result<BasicValueEnum<'ctx>, String> {
+        self.logger.info("compile_length()");
+
+        let int_type = self.context.i32_type();
+        let [arg] = args else {
+            return Err("len takes one argument".into());
+        };
+
+        match self.compile_place(arg)? {
+            (_, BasicTypeEnum::ArrayType(array_type)) => Ok(int_type.const_int(array_type.len() as u64, false).into()),
+            (place, BasicTypeEnum::PointerType(string_type)) => {
+                let string = self.builder.build_load(string_type, place, "string").unwrap().into_pointer_value();
+                let length = self.build_string_length(string);
+                Ok(self.builder.build_int_truncate(length, int_type, "len").unwrap().into())
+            },
+            _ => Err("len needs a string or an array".into()),
+        }
+    }
+
+    fn build_string_concat(&mut self, l: PointerValue<'ctx>, r: PointerValue<'ctx>) -> Result<PointerValue<'ctx>, String> {
+        let l_length = self.build_string_length(l);
+        let r_length = self.build_string_length(r);
+        let length = self.builder.build_int_add(l_length, r_length, "concat_len").unwrap();
+
+        let result = self.build_string_alloc(length)?;
+        self.builder.build_memcpy(result, 1, l, 1, l_length).unwrap();
+        let rest = self.byte_offset(result, l_length, "concat_rest");
+        self.builder.build_memcpy(rest, 1, r, 1, r_length).unwrap();
+
+        self.build_string_drop(l)?;
+        self.build_string_drop(r)?;
+        Ok(result)
+    }
+
+    // Negative, zero or positive like memcmp, a string that is a prefix of the other comes first
+    fn build_string_compare(&mut self, l: PointerValue<'ctx>, r: PointerValue<'ctx>) -> Result<IntValue<'ctx>, String> {
+        let int_type = self.context.i32_type();
+        let size_type = self.context.i64_type();
+        let ptr_type = self.context.ptr_type(inkwell::AddressSpace::default());
+        let memcmp = self.external("memcmp", int_type.fn_type(&[ptr_type.into(), ptr_type.into(), size_type.into()], false));
+
+        let l_length = self.build_string_length(l);
+        let r_length = self.build_string_length(r);
+        let shorter = self.builder.build_int_compare(IntPredicate::ULT, l_length, r_length, "shorter").unwrap();
+        let common = self.builder.build_select(shorter, l_length, r_length, "common").unwrap();
+        let bytes = self.call_value(memcmp, &[l.into(), r.into(), common])?.into_int_value();
+
+        let longer = self.builder.build_int_compare(IntPredicate::UGT, l_length, r_length, "longer").unwrap();
+        let longer = self.builder.build_int_z_extend(longer, int_type, "longer").unwrap();
+        let shorter = self.builder.build_int_z_extend(shorter, int_type, "shorter").unwrap();
+        let by_length = self.builder.build_int_sub(longer, shorter, "by_length").unwrap();
+
+        let differ = self.builder.build_int_compare(IntPredicate::NE, bytes, int_type.const_zero(), "differ").unwrap();
+        Ok(self.builder.build_select(differ, bytes, by_length, "order").unwrap().into_int_value())
+    }
+
+    fn byte_offset(&mut self, pointer: PointerValue<'ctx>, offset: IntValue<'ctx>, name: &str) -> PointerValue<'ctx> {
+        unsafe {
+            self.builder.build_in_bounds_gep(self.context.i8_type(), pointer, &[offset], name).unwrap()
+        }
+    }
+
+}
@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use inkwell::OptimizationLevel;
+use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple};
+use crate::core::ir::codegen::CodeGen;
+
+impl<'ctx> CodeGen<'ctx> {
+
+    // Create a target machine for the given triple, or the host when none is given
+    pub fn create_target_machine(triple: Option<&str>, level: OptimizationLevel) -> Result<TargetMachine, String> {
+        Target::initialize_x86(&InitializationConfig::default());
+
+        let (triple, cpu, features) = match triple {
+            // Only the x86 backend is built in, other triples are turned away before LLVM sees them
+            Some(t) if !is_x86(t) => {
+                return Err(format!("Unsupported target '{}': only x86 and x86_64 targets are available", t));
+            },
+            Some(t) => (TargetTriple::create(t), "generic".to_string(), String::new()),
+            None => (
+                TargetMachine::get_default_triple(),
+                TargetMachine::get_host_cpu_name().to_string(),
+                TargetMachine::get_host_cpu_features().to_string()
+            ),
+        };
+
+        let target = Target::from_triple(&triple)
+            .map_err(|e| format!("Unsupported target '{}': {}", triple, e))?;
+
+        target
+            .create_target_machine(&triple, &cpu, &features, level, RelocMode::PIC, CodeModel::Default)
+            .ok_or(format!("Failed to create target machine for '{}'", triple))
+    }
+
+    // Point the module at the machine's triple and data layout before emitting
+    pub fn set_target(&self, machine: &TargetMachine) {
+        self.module.set_triple(&machine.get_triple());
+        self.module.set_data_layout(&machine.get_target_data().get_data_layout());
+    }
+
+    pub fn write_object(&self, machine: &TargetMachine, path: &Path) -> Result<(), String> {
+        self.logger.info("write_object()");
+        machine
+            .write_to_file(&self.module, FileType::Object, path)
+            .map_err(|e| format!("Failed to write object file: {}", e))
+    }
+
+    pub fn write_assembly(&self, machine: &TargetMachine, path: &Path) -> Result<(), String> {
+        self.logger.info("write_assembly()");
+        machine
+            .write_to_file(&self.module, FileType::Assembly, path)
+            .map_err(|e| format!("Failed to write assembly file: {}", e))
+    }
+
+    pub fn write_ir(&self, path: &Path) -> Result<(), String> {
+        self.logger.info("write_ir()");
+        self.module
+            .print_to_file(path)
+            .map_err(|e| format!("Failed to write IR file: {}", e))
+    }
+
+    pub fn write_bitcode(&self, path: &Path) -> Result<(), String> {
+        self.logger.info("write_bitcode()");
+        if self.module.write_bitcode_to_path(path) {
+            Ok(())
+        } else {
+            Err(format!("Failed to write bitcode file '{}'", path.display()))
+        }
+    }
+
+}
+
+// The architecture is the first part of a triple, x86_64-unknown-linux-gnu
+fn is_x86(triple: &str) -> bool {
+    let arch = triple.split('-').next().unwrap_or("");
+    matches!(arch, "x86_64" | "x86" | "amd64" | "i386" | "i486" | "i586" | "i686")
+}
+
+// Link object files into an executable using the system C toolchain
+pub fn link_executable(objects: &[&Path], output: &Path, machine: &TargetMachine) -> Result<(), String> {
+    let target = machine.get_triple().as_str().to_string_lossy().to_string();
+    let host = TargetMachine::get_default_triple().as_str().to_string_lossy().to_string();
+
+    let compiler = cc::Build::new()
+        .cargo_metadata(false)
+        .cargo_warnings(false)
+        .opt_level(0)
+        .host(&host)
+        .target(&target)
+        .try_get_compiler()
+        .map_err(|e| format!("No C toolchain found for '{}': {}", target, e))?;
+
+    let mut command = compiler.to_command();
+    command.args(objects).arg("-o").arg(output);
+
+    let status = command
+        .status()
+        .map_err(|e| format!("Failed to run linker '{}': {}", compiler.path().display(), e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Linker '{}' exited with {}", compiler.path().display(), status))
+    }
+}
@@ -1,7 +1,7 @@
 use crate::core::lexer::token::Token;
 use crate::core::lexer::token_type::TokenType;
-use crate::core::util::error::Error;
-use crate::core::util::location::Location;
+use crate::core::util::diagnostic::{codes, Diagnostic};
+use crate::core::util::location::{Location, Span};
 
 #[derive(Clone)]
 enum LexerState {
@@ -19,9 +19,22 @@ enum LexerState {
     END_BLOCK_COMMENT,
 
     STRING,
+    RAW_STRING,
     CHAR,
+    ESCAPE,
+    UNICODE,
 
+    PLUS,
+    DASH,
+    STAR,
+    PERCENT,
     CARAT,
+    CARAT_SLASH,
+    AMPERSAND,
+    PIPE,
+    QUESTION,
+    PERIOD,
+    RANGE,
     EXCLAIM,
     EQUAL,
     GREATER,
@@ -30,36 +43,56 @@ enum LexerState {
 
 #[derive(Clone)]
 pub struct Lexer {
-    input: String,
+    // Positions count chars, not bytes
+    input: Vec<char>,
     position: usize,
     state: LexerState,
     current: Token,
     buffer: String,
     line: usize,
     col: usize,
+    file_id: usize,
+    diagnostics: Vec<Diagnostic>,
 
     string_line: usize,
-    string_col: usize
+    string_col: usize,
+
+    // String or char literal an escape sequence belongs to, and where the escape started
+    literal: LexerState,
+    escape: String,
+    escape_start: Location,
+    // Brace depth inside every open interpolation, innermost last
+    templates: Vec<usize>,
+    // The string being read continues a template after an interpolation
+    template: bool
 }
 
 impl Lexer {
     pub fn new(input: String) -> Lexer {
         Lexer {
-            input,
+            input: input.chars().collect(),
             position: 0,
             state: LexerState::START,
             current: Token::from(TokenType::EOI),
             buffer: String::new(),
             line: 1,
             col: 0,
+            file_id: 0,
+            diagnostics: Vec::new(),
 
             string_line: 0,
-            string_col: 0
+            string_col: 0,
+
+            literal: LexerState::STRING,
+            escape: String::new(),
+            escape_start: Location::empty(),
+            templates: Vec::new(),
+            template: false
         }
     }
     
     pub fn set_input(&mut self, input: String) {
-        self.input = input;
+        self.input = input.chars().collect();
         self.position = 0;
         self.state = LexerState::START;
         self.current = Token::from(TokenType::EOI);
@@ -69,9 +102,17 @@ impl Lexer {
 
         self.string_line = 0;
         self.string_line = 0;
+
+        self.templates.clear();
+        self.template = false;
+    }
+
+    pub fn set_file_id(&mut self, file_id: usize) {
+        self.file_id = file_id;
     }
 
     pub fn reset(&mut self) {
+        self.diagnostics.clear();
         self.position = 0;
         self.state = LexerState::START;
         self.current = Token::from(TokenType::EOI);
@@ -81,8 +122,15 @@ impl Lexer {
 
         self.string_line = 0;
         self.string_line = 0;
+
+        self.templates.clear();
+        self.template = false;
     }
     
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
     pub fn current(&self) -> Token {
         self.current.clone()
     }
@@ -104,9 +152,13 @@ impl Lexer {
             // Reached End of File While Lexing Token
             if self.position >= self.input.len() {
                 
-                if !self.buffer.is_empty() {
+                if !self.buffer.is_empty() && !self.in_literal() {
+                    let token_type: TokenType = match self.state {
+                        LexerState::NUMBERS => TokenType::LIT_INT { value: self.buffer.parse().unwrap() },
+                        LexerState::NUMPOINT | LexerState::DECIMALS => TokenType::LIT_FLOAT { value: self.buffer.parse().unwrap() },
+                        _ => self.match_buffer(),
+                    };
                     self.state = LexerState::END;
-                    let token_type: TokenType = self.match_buffer();
                     self.current = self.create_token_with_location(token_type, self.line, self.col - self.buffer.len());
                     self.buffer = String::new();
                     break;
@@ -114,9 +166,23 @@ impl Lexer {
 
                 match self.state {
                     LexerState::SLASH => self.current = self.create_token(TokenType::SLASH),
-                    LexerState::STRING => {
-                        Error::new(self.line, self.col, "Unterminated string".to_string()).report();
+                    LexerState::STRING | LexerState::RAW_STRING | LexerState::CHAR | LexerState::ESCAPE | LexerState::UNICODE => {
+                        let start = Location::new(self.string_line, self.string_col);
+                        let end = Location::new(self.line, self.col);
+                        let in_char = match self.state {
+                            LexerState::CHAR => true,
+                            LexerState::ESCAPE | LexerState::UNICODE => matches!(self.literal, LexerState::CHAR),
+                            _ => false,
+                        };
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                codes::UNTERMINATED_STRING,
+                                if in_char { "Unterminated char literal" } else { "Unterminated string" },
+                                Span::new(self.file_id, start, end)
+                            ).with_label("missing closing quote")
+                        );
                         self.buffer = String::new();
+                        self.state = LexerState::END;
                         self.current = self.create_token(TokenType::EOI);
                         break;
                     }
@@ -124,12 +190,15 @@ impl Lexer {
                     _ => {}
                 }              
                 
+                if !self.templates.is_empty() {
+                    self.unclosed_interpolation();
+                }
                 self.state = LexerState::END;
                 self.current = self.create_token(TokenType::EOI);
                 break;
             }
             
-            let char = self.input.chars().nth(self.position).unwrap();
+            let char = self.input[self.position];
             self.position += 1;
             self.col += 1;
             
@@ -156,10 +225,15 @@ impl Lexer {
                     },
                     '"' => {
                         self.state = LexerState::STRING;
+                        self.template = false;
                         self.string_line = self.line.clone();
                         self.string_col = self.col.clone();
                     },
-                    '\'' => self.state = LexerState::CHAR,
+                    '\'' => {
+                        self.state = LexerState::CHAR;
+                        self.string_line = self.line;
+                        self.string_col = self.col;
+                    },
                     
                     // Containers
                     '(' => {
@@ -171,46 +245,57 @@ impl Lexer {
                         break;
                     },
                     '{' => {
+                        if let Some(depth) = self.templates.last_mut() {
+                            *depth += 1;
+                        }
                         self.current = self.create_token(TokenType::BRACE_L);
                         break;
                     },
+                    // Closes an interpolation, the rest of its string follows
+                    '}' if self.templates.last() == Some(&0) => {
+                        self.templates.pop();
+                        self.state = LexerState::STRING;
+                        self.template = true;
+                        self.string_line = self.line;
+                        self.string_col = self.col;
+                    },
                     '}' => {
+                        if let Some(depth) = self.templates.last_mut() {
+                            *depth -= 1;
+                        }
                         self.current = self.create_token(TokenType::BRACE_R);
                         break;
                     },
+                    '[' => {
+                        self.current = self.create_token(TokenType::BRACKET_L);
+                        break;
+                    },
+                    ']' => {
+                        self.current = self.create_token(TokenType::BRACKET_R);
+                        break;
+                    },
                     
                     // Separators
                     ';' => {
+                        // Interpolations never hold a statement end, so one left open is reported and closed here
+                        if !self.templates.is_empty() {
+                            self.unclosed_interpolation();
+                        }
                         self.current = self.create_token(TokenType::SEMICOLON);
                         break;
                     },
-                    '.' => {
-                        self.current = self.create_token(TokenType::PERIOD);
-                        break;
-                    },
+                    '.' => self.state = LexerState::PERIOD,
                     ',' => {
                         self.current = self.create_token(TokenType::COMMA);
                         break;
                     },
                     
                     // Arithmetic Operators
-                    '+' => {
-                        self.current = self.create_token(TokenType::PLUS);
-                        break;
-                    },
-                    '-' => {
-                        self.current = self.create_token(TokenType::DASH);
-                        break;
-                    },
-                    '*' => {
-                        self.current = self.create_token(TokenType::STAR);
-                        break;
-                    },
+                    '+' => self.state = LexerState::PLUS,
+                    '-' => self.state = LexerState::DASH,
+                    '*' => self.state = LexerState::STAR,
                     '/' => self.state = LexerState::SLASH,
-                    '%' => {
-                        self.current = self.create_token(TokenType::PERCENT);
-                        break;
-                    }
+                    '%' => self.state = LexerState::PERCENT,
                     '^' => self.state = LexerState::CARAT,
                     
                     // Assignment Operators
@@ -218,18 +303,35 @@ impl Lexer {
                     '<' => self.state = LexerState::LESS,
                     '>' => self.state = LexerState::GREATER,
                     '!' => self.state = LexerState::EXCLAIM,
+
+                    // Logical Operators
+                    '&' => self.state = LexerState::AMPERSAND,
+                    '|' => self.state = LexerState::PIPE,
+
+                    // Nullable Types and Coalescing
+                    '?' => self.state = LexerState::QUESTION,
                     
                     _ => {
-                        Error::new(
-                            self.line,
-                            self.col,
-                            format!("Unrecognized character '{}'", char).to_string()
-                        ).report();
+                        let token = self.create_token(TokenType::ERROR);
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                codes::UNRECOGNIZED_CHARACTER,
+                                format!("Unrecognized character '{}'", char),
+                                token.span
+                            ).with_label("not valid here")
+                        );
                     }
                     
                 },
                 LexerState::WORDS => match char {
                     'A'..='Z' | 'a'..='z' | '_' | '0'..='9' => self.buffer.push(char),
+                    // r"..." keeps every character as written
+                    '"' if self.buffer == "r" => {
+                        self.state = LexerState::RAW_STRING;
+                        self.string_line = self.line;
+                        self.string_col = self.col - 1;
+                        self.buffer = String::new();
+                    },
                     
                     _ => {
                         self.state = LexerState::START;
@@ -279,6 +381,22 @@ impl Lexer {
                         self.buffer.push('.');
                         self.buffer.push(char);
                     },
+
+                    // Start of a range, the number before it is an int
+                    '.' => {
+                        self.state = LexerState::START;
+                        let value: i32 = self.buffer.parse().unwrap();
+                        self.current = self.create_token_with_location(
+                            TokenType::LIT_INT { value },
+                            self.line,
+                            self.col - self.buffer.len() - 1
+                        );
+                        self.buffer = String::new();
+
+                        self.position -= 2;
+                        self.col -= 2;
+                        break;
+                    }
                     
                     _ => {
                         self.state = LexerState::START;
@@ -328,6 +446,15 @@ impl Lexer {
                 LexerState::SLASH => match char {
                     '/' => self.state = LexerState::COMMENT,
                     '*' => self.state = LexerState::START_BLOCK_COMMENT,
+                    '=' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::DIV_ASSIGN,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
                     
                     _ => {
                         self.state = LexerState::START;
@@ -363,46 +490,307 @@ impl Lexer {
                     
                     _ => self.state = LexerState::START_BLOCK_COMMENT
                 },
-                LexerState::STRING => {
-                    if char == '"' {
+                LexerState::STRING => match char {
+                    '"' => {
                         self.state = LexerState::START;
-                        let value: String = self.buffer.clone();
+                        let value: String = std::mem::take(&mut self.buffer);
+                        let token_type = match self.template {
+                            true => TokenType::TEMPLATE_END { value },
+                            false => TokenType::LIT_STRING { value },
+                        };
                         self.current = self.create_token_with_location(
-                            TokenType::LIT_STRING { value },
+                            token_type,
                             self.string_line,
                             self.string_col
                         );
-                        self.buffer = String::new();
 
+                        self.template = false;
                         self.string_line = 0;
                         self.string_col = 0;
                         break;
-                    } else {
+                    },
+                    // Interpolation, the expression is lexed as usual up to its closing '}'
+                    '{' => {
+                        self.state = LexerState::START;
+                        let value: String = std::mem::take(&mut self.buffer);
+                        let token_type = match self.template {
+                            true => TokenType::TEMPLATE_MIDDLE { value },
+                            false => TokenType::TEMPLATE_START { value },
+                        };
+                        self.current = self.create_token_with_location(
+                            token_type,
+                            self.string_line,
+                            self.string_col
+                        );
+
+                        self.templates.push(0);
+                        self.template = false;
+                        break;
+                    },
+                    '\\' => self.start_escape(LexerState::STRING),
+                    '\n' => {
+                        self.line += 1;
+                        self.col = 0;
                         self.buffer.push(char);
-                    }
+                    },
+                    _ => self.buffer.push(char),
+                },
+                LexerState::RAW_STRING => match char {
+                    '"' => {
+                        self.state = LexerState::START;
+                        let value: String = std::mem::take(&mut self.buffer);
+                        self.current = self.create_token_with_location(
+                            TokenType::LIT_STRING { value },
+                            self.string_line,
+                            self.string_col
+                        );
+                        break;
+                    },
+                    '\n' => {
+                        self.line += 1;
+                        self.col = 0;
+                        self.buffer.push(char);
+                    },
+                    _ => self.buffer.push(char),
                 },
                 LexerState::CHAR => match char {
                     '\'' => {
                         self.state = LexerState::START;
-                        if self.buffer.len() == 1 {
-                            let value = self.buffer.chars().nth(0).unwrap();
-                            self.current = self.create_token_with_location(
-                                TokenType::LIT_CHAR { value }, 
-                                self.string_line, 
-                                self.string_col
-                            );
-                            self.buffer = String::new();
-                            break;
-                        }
+                        let text: String = std::mem::take(&mut self.buffer);
+                        let mut chars = text.chars();
+                        let value = match (chars.next(), chars.next()) {
+                            // Chars are 16 bit codes, like 'as char' keeps them
+                            (Some(value), None) if value as u32 > 0xFFFF => {
+                                let start = Location::new(self.string_line, self.string_col);
+                                let end = Location::new(self.line, self.col);
+                                self.diagnostics.push(
+                                    Diagnostic::error(
+                                        codes::INVALID_CHAR,
+                                        format!("Char literals hold characters up to U+FFFF, found U+{:X}", value as u32),
+                                        Span::new(self.file_id, start, end)
+                                    ).with_label("character out of range")
+                                        .with_help("use double quotes for a string")
+                                );
+                                '\0'
+                            },
+                            (Some(value), None) => value,
+                            _ => {
+                                let start = Location::new(self.string_line, self.string_col);
+                                let end = Location::new(self.line, self.col);
+                                self.diagnostics.push(
+                                    Diagnostic::error(
+                                        codes::INVALID_CHAR,
+                                        format!("Char literals hold exactly one character, found {}", text.chars().count()),
+                                        Span::new(self.file_id, start, end)
+                                    ).with_label("not a single character")
+                                        .with_help("use double quotes for a string")
+                                );
+                                text.chars().next().unwrap_or('\0')
+                            }
+                        };
+                        self.current = self.create_token_with_location(
+                            TokenType::LIT_CHAR { value }, 
+                            self.string_line, 
+                            self.string_col
+                        );
+                        break;
                     },
+                    '\\' => self.start_escape(LexerState::CHAR),
                     _ => self.buffer.push(char),
-                }
-                LexerState::CARAT => match char {
-                    '/' => {
+                },
+                LexerState::ESCAPE => {
+                    self.state = self.literal.clone();
+                    match char {
+                        'n' => self.buffer.push('\n'),
+                        't' => self.buffer.push('\t'),
+                        'r' => self.buffer.push('\r'),
+                        '0' => self.buffer.push('\0'),
+                        '\\' | '"' | '\'' | '{' | '}' => self.buffer.push(char),
+                        'u' => self.state = LexerState::UNICODE,
+                        _ => {
+                            self.invalid_escape(format!("Unknown escape sequence '\\{}'", char.escape_default()));
+                            self.position -= 1;
+                            self.col -= 1;
+                        }
+                    }
+                },
+                // \u{1F600}, up to six hex digits
+                LexerState::UNICODE => match char {
+                    '{' if self.escape.is_empty() => self.escape.push(char),
+                    '0'..='9' | 'a'..='f' | 'A'..='F' if self.escape.starts_with('{') && self.escape.len() <= 6 => {
+                        self.escape.push(char);
+                    },
+                    '}' if self.escape.len() > 1 => {
+                        self.state = self.literal.clone();
+                        let code = u32::from_str_radix(&self.escape[1..], 16).unwrap();
+                        match char::from_u32(code) {
+                            Some(value) => self.buffer.push(value),
+                            None => self.invalid_escape(format!("'\\u{{{:X}}}' is not a unicode character", code)),
+                        }
+                        self.escape = String::new();
+                    },
+                    _ => {
+                        self.state = self.literal.clone();
+                        self.invalid_escape("Unicode escapes are written '\\u{...}' with one to six hex digits".to_string());
+                        self.escape = String::new();
+                        self.position -= 1;
+                        self.col -= 1;
+                    }
+                },
+                LexerState::PLUS => match char {
+                    '+' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::INCREMENT,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+                    '=' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::ADD_ASSIGN,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+
+                    _ => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::PLUS,
+                            self.line,
+                            self.col - 1
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                        break;
+                    }
+                },
+                LexerState::DASH => match char {
+                    '-' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::DECREMENT,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+                    '=' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::SUB_ASSIGN,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+
+                    _ => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::DASH,
+                            self.line,
+                            self.col - 1
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                        break;
+                    }
+                },
+                LexerState::STAR => match char {
+                    '=' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::MULT_ASSIGN,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+
+                    _ => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::STAR,
+                            self.line,
+                            self.col - 1
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                        break;
+                    }
+                },
+                LexerState::PERCENT => match char {
+                    '=' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::REM_ASSIGN,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+
+                    _ => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::PERCENT,
+                            self.line,
+                            self.col - 1
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                        break;
+                    }
+                },
+                LexerState::CARAT_SLASH => match char {
+                    '=' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::ROOT_ASSIGN,
+                            self.line,
+                            self.col - 2
+                        );
+                        break;
+                    },
+
+                    _ => {
                         self.state = LexerState::START;
                         self.current = self.create_token_with_location(
                             TokenType::ROOT,
                             self.line,
+                            self.col - 2
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                        break;
+                    }
+                },
+                LexerState::CARAT => match char {
+                    '/' => self.state = LexerState::CARAT_SLASH,
+                    '^' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::XOR,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    }
+                    '=' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::POWER_ASSIGN,
+                            self.line,
                             self.col - 1
                         );
                         break;
@@ -421,6 +809,126 @@ impl Lexer {
                         break;
                     }
                 },
+                LexerState::AMPERSAND => match char {
+                    '&' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::AND,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+
+                    // Only the doubled form is an operator
+                    _ => {
+                        self.state = LexerState::START;
+                        let token = self.create_token_with_location(TokenType::ERROR, self.line, self.col - 1);
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                codes::UNRECOGNIZED_CHARACTER,
+                                "Unrecognized character '&'",
+                                token.span
+                            ).with_label("not valid here")
+                            .with_help("use '&&' for logical and")
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                    }
+                },
+                LexerState::PIPE => match char {
+                    '|' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::OR,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+
+                    // Only the doubled form is an operator
+                    _ => {
+                        self.state = LexerState::START;
+                        let token = self.create_token_with_location(TokenType::ERROR, self.line, self.col - 1);
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                codes::UNRECOGNIZED_CHARACTER,
+                                "Unrecognized character '|'",
+                                token.span
+                            ).with_label("not valid here")
+                            .with_help("use '||' for logical or")
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                    }
+                },
+                LexerState::QUESTION => match char {
+                    '?' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::COALESCE,
+                            self.line,
+                            self.col - 1
+                        );
+                        break;
+                    },
+
+                    _ => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::QUESTION,
+                            self.line,
+                            self.col - 1
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                        break;
+                    }
+                },
+                LexerState::PERIOD => match char {
+                    '.' => self.state = LexerState::RANGE,
+
+                    _ => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::PERIOD,
+                            self.line,
+                            self.col - 1
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                        break;
+                    }
+                },
+                LexerState::RANGE => match char {
+                    '=' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::RANGE_INCLUSIVE,
+                            self.line,
+                            self.col - 2
+                        );
+                        break;
+                    },
+
+                    _ => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::RANGE,
+                            self.line,
+                            self.col - 2
+                        );
+
+                        self.position -= 1;
+                        self.col -= 1;
+                        break;
+                    }
+                },
                 LexerState::EXCLAIM => match char {
                     '=' => {
                         self.state = LexerState::START;
@@ -453,6 +961,14 @@ impl Lexer {
                             self.col - 1);
                         break;
                     },
+                    '>' => {
+                        self.state = LexerState::START;
+                        self.current = self.create_token_with_location(
+                            TokenType::ARROW,
+                            self.line,
+                            self.col - 1);
+                        break;
+                    },
 
                     _ => {
                         self.state = LexerState::START;
@@ -518,9 +1034,29 @@ impl Lexer {
             }
             
         }
+
+        // Lookahead has been given back, so the last consumed character ends the token
+        if self.current.token_type != TokenType::EOI {
+            self.current.span.end_line = self.line;
+            self.current.span.end_col = self.col;
+        }
         self.current.clone()
     }
     
+    // Reserved words recognized by match_buffer
+    pub const KEYWORDS: &'static [&'static str] = &[
+        "not", "and", "or", "xor", "as",
+        "null", "true", "false",
+        "var", "const", "string", "char", "int", "float", "boolean",
+        "public", "protected", "private",
+        "class", "struct", "this",
+        "import",
+        "return", "defer",
+        "if", "else", "do", "while", "loop", "for", "in", "break", "continue", "repeat",
+        "match", "default",
+        "print",
+    ];
+
     fn match_buffer(&mut self) -> TokenType {
         let string = self.buffer.as_str();
         match string {
@@ -528,6 +1064,7 @@ impl Lexer {
             "and" => TokenType::AND,
             "or" => TokenType::OR,
             "xor" => TokenType::XOR,
+            "as" => TokenType::AS,
             
             "null" => TokenType::NULL,
             "true" => TokenType::TRUE,
@@ -542,17 +1079,30 @@ impl Lexer {
             "boolean" => TokenType::BOOLEAN,
 
             "public" => TokenType::PUBLIC,
+            "protected" => TokenType::PROTECTED,
+            "private" => TokenType::PRIVATE,
+
+            "class" => TokenType::CLASS,
+            "struct" => TokenType::STRUCT,
+            "this" => TokenType::THIS,
+
+            "import" => TokenType::IMPORT,
 
             "return" => TokenType::RETURN,
+            "defer" => TokenType::DEFER,
 
             "if" => TokenType::IF,
             "else" => TokenType::ELSE,
             "do" => TokenType::DO,
             "while" => TokenType::WHILE,
             "loop" => TokenType::LOOP,
+            "for" => TokenType::FOR,
+            "in" => TokenType::IN,
             "break" => TokenType::BREAK,
             "continue" => TokenType::CONTINUE,
             "repeat" => TokenType::REPEAT,
+            "match" => TokenType::MATCH,
+            "default" => TokenType::DEFAULT,
 
             "print" => TokenType::PRINT,
             
@@ -562,18 +1112,177 @@ impl Lexer {
         }
     }
     
+    fn in_literal(&self) -> bool {
+        matches!(
+            self.state,
+            LexerState::STRING | LexerState::RAW_STRING | LexerState::CHAR | LexerState::ESCAPE | LexerState::UNICODE
+        )
+    }
+
+    fn start_escape(&mut self, literal: LexerState) {
+        self.literal = literal;
+        self.state = LexerState::ESCAPE;
+        self.escape_start = Location::new(self.line, self.col);
+    }
+
+    fn invalid_escape(&mut self, message: String) {
+        let span = Span::new(self.file_id, self.escape_start, Location::new(self.line, self.col));
+        self.diagnostics.push(
+            Diagnostic::error(codes::INVALID_ESCAPE, message, span)
+                .with_label("invalid escape")
+                .with_note("valid escapes are \\n \\t \\r \\0 \\\\ \\\" \\' \\{ \\} and \\u{...}")
+        );
+    }
+
+    fn unclosed_interpolation(&mut self) {
+        self.templates.clear();
+        let token = self.create_token(TokenType::ERROR);
+        self.diagnostics.push(
+            Diagnostic::error(codes::UNCLOSED_INTERPOLATION, "Unclosed interpolation in string", token.span)
+                .with_label("expected '}' before this")
+                .with_help("write '\\{' for a literal brace")
+        );
+    }
+
     fn create_token(&mut self, token_type: TokenType) -> Token {
-        Token {
-            token_type,
-            location: Location::new(self.line, self.col),
-        }
+        let location = Location::new(self.line, self.col);
+        Token::new(token_type, Span::new(self.file_id, location, location))
     }
 
     fn create_token_with_location(&mut self, token_type: TokenType, line: usize, col: usize) -> Token {
-        Token {
-            token_type,
-            location: Location::new(line, col),
-        }
+        let location = Location::new(line, col);
+        Token::new(token_type, Span::new(self.file_id, location, location))
     }
     
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(input: &str) -> (Vec<TokenType>, Vec<Diagnostic>) {
+        let mut lexer = Lexer::new(input.to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.advance();
+            if token.token_type == TokenType::EOI {
+                break;
+            }
+            tokens.push(token.token_type);
+        }
+        (tokens, lexer.take_diagnostics())
+    }
+
+    fn string(value: &str) -> TokenType {
+        TokenType::LIT_STRING { value: value.to_string() }
+    }
+
+    #[test]
+    fn escapes() {
+        let (tokens, diagnostics) = lex(r#""a\n\t\"\\\{\}\u{e9}" '\''"#);
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens, vec![string("a\n\t\"\\{}é"), TokenType::LIT_CHAR { value: '\'' }]);
+    }
+
+    #[test]
+    fn unknown_escape_is_reported() {
+        let (_, diagnostics) = lex(r#""\q""#);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::INVALID_ESCAPE);
+    }
+
+    #[test]
+    fn raw_strings_keep_backslashes_and_braces() {
+        let (tokens, diagnostics) = lex(r#"r"C:\dir\{x}""#);
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens, vec![string(r"C:\dir\{x}")]);
+    }
+
+    #[test]
+    fn templates() {
+        let (tokens, diagnostics) = lex(r#""a {x} b {"c"}""#);
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens, vec![
+            TokenType::TEMPLATE_START { value: "a ".to_string() },
+            TokenType::ID { name: "x".to_string() },
+            TokenType::TEMPLATE_MIDDLE { value: " b ".to_string() },
+            string("c"),
+            TokenType::TEMPLATE_END { value: "".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn unclosed_interpolation_is_reported() {
+        let (tokens, diagnostics) = lex(r#"print("a {x); int y;"#);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::UNCLOSED_INTERPOLATION);
+        assert_eq!(tokens.last(), Some(&TokenType::SEMICOLON));
+    }
+
+    #[test]
+    fn unterminated_literals_are_reported() {
+        let (_, diagnostics) = lex("\"abc");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unterminated string");
+
+        let (_, diagnostics) = lex("'a");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unterminated char literal");
+    }
+
+    #[test]
+    fn chars_are_limited_to_16_bits() {
+        let (tokens, diagnostics) = lex(r"'\u{FFFF}' '\u{e9}'");
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens, vec![TokenType::LIT_CHAR { value: '\u{FFFF}' }, TokenType::LIT_CHAR { value: 'é' }]);
+
+        let (_, diagnostics) = lex(r"'\u{1F600}' '😀'");
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.code == codes::INVALID_CHAR));
+
+        // Surrogates are never characters
+        let (_, diagnostics) = lex(r"'\u{D800}'");
+        assert_eq!(diagnostics[0].code, codes::INVALID_ESCAPE);
+    }
+
+    #[test]
+    fn non_ascii_source() {
+        let (tokens, diagnostics) = lex("// café ☕\n\"é😀\" x");
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens, vec![string("é😀"), TokenType::ID { name: "x".to_string() }]);
+    }
+
+    #[test]
+    fn compound_assignments() {
+        let (tokens, diagnostics) = lex("+= -= *= /= %= ^= ^/= ^ ^/ ^^");
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens, vec![
+            TokenType::ADD_ASSIGN,
+            TokenType::SUB_ASSIGN,
+            TokenType::MULT_ASSIGN,
+            TokenType::DIV_ASSIGN,
+            TokenType::REM_ASSIGN,
+            TokenType::POWER_ASSIGN,
+            TokenType::ROOT_ASSIGN,
+            TokenType::POWER,
+            TokenType::ROOT,
+            TokenType::XOR,
+        ]);
+    }
+
+    #[test]
+    fn ranges() {
+        let (tokens, diagnostics) = lex("0..10 1..=n 1.5");
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens, vec![
+            TokenType::LIT_INT { value: 0 },
+            TokenType::RANGE,
+            TokenType::LIT_INT { value: 10 },
+            TokenType::LIT_INT { value: 1 },
+            TokenType::RANGE_INCLUSIVE,
+            TokenType::ID { name: "n".to_string() },
+            TokenType::LIT_FLOAT { value: 1.5 },
+        ]);
+    }
+}
@@ -1,43 +1,47 @@
 use crate::core::lexer::token_type::TokenType;
-use crate::core::util::location::Location;
+use crate::core::util::location::{Location, Span};
 
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
-    pub location: Location,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, location: Location) -> Token {
+    pub fn new(token_type: TokenType, span: Span) -> Token {
         Token {
             token_type,
-            location
+            span
         }
     }
 
     pub fn from(token_type: TokenType) -> Token {
         Token {
             token_type,
-            location: Location::empty()
+            span: Span::empty()
         }
     }
 
     pub fn using_location(token_type: TokenType, token: Token) -> Token {
         Token {
             token_type,
-            location: token.location
+            span: token.span
         }
     }
 
-    pub fn id(name: &str, location: Location) -> Token {
+    pub fn id(name: &str, span: Span) -> Token {
         Token {
             token_type: TokenType::ID { name: String::from(name) },
-            location
+            span
         }
     }
 
+    pub fn location(&self) -> Location {
+        self.span.start()
+    }
+
     pub fn to_string(&self) -> String {
-        format!("{} {:?}", self.location.to_string(), self.token_type)
+        format!("{} {:?}", self.location().to_string(), self.token_type)
     }
 
-}
\ No newline at end of file
+}
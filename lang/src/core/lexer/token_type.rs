@@ -6,11 +6,18 @@ pub enum TokenType {
     PAREN_R,
     BRACE_L,
     BRACE_R,
+    BRACKET_L,
+    BRACKET_R,
 
     // Separators
     COMMA,
     PERIOD,
     SEMICOLON,
+    RANGE,
+    RANGE_INCLUSIVE,
+    ARROW,
+    // Marks a nullable type, int?
+    QUESTION,
 
     // Arithmetic Symbols
     PLUS,
@@ -20,9 +27,18 @@ pub enum TokenType {
     PERCENT,
     POWER,
     ROOT,
+    INCREMENT,
+    DECREMENT,
 
     // Assignment
     ASSIGN,
+    ADD_ASSIGN,
+    SUB_ASSIGN,
+    MULT_ASSIGN,
+    DIV_ASSIGN,
+    REM_ASSIGN,
+    POWER_ASSIGN,
+    ROOT_ASSIGN,
 
     // Relational Operators
     EQUAL,
@@ -37,10 +53,16 @@ pub enum TokenType {
     AND,
     OR,
     XOR,
+    COALESCE,
+
+    // Conversion, x as float
+    AS,
 
     // Keywords
     PRINT,
     RETURN,
+    DEFER,
+    IMPORT,
 
     // Variables
     VAR,
@@ -56,6 +78,13 @@ pub enum TokenType {
 
     // Member Types
     PUBLIC,
+    PROTECTED,
+    PRIVATE,
+
+    // Classes
+    CLASS,
+    STRUCT,
+    THIS,
 
     // Literals
     ID { name: String },
@@ -63,6 +92,10 @@ pub enum TokenType {
     LIT_CHAR { value: char },
     LIT_INT { value: i32 },
     LIT_FLOAT { value: f32 },
+    // "a {x} b {y} c" is TEMPLATE_START "a ", x, TEMPLATE_MIDDLE " b ", y, TEMPLATE_END " c"
+    TEMPLATE_START { value: String },
+    TEMPLATE_MIDDLE { value: String },
+    TEMPLATE_END { value: String },
     TRUE,
     FALSE,
 
@@ -72,9 +105,13 @@ pub enum TokenType {
     DO,
     WHILE,
     LOOP,
+    FOR,
+    IN,
     BREAK,
     CONTINUE,
     REPEAT,
+    MATCH,
+    DEFAULT,
 
     // Meta
     START,
@@ -82,7 +119,14 @@ pub enum TokenType {
     ERROR,
     BLOCK,
     VAR_DECL,
+    FOR_EACH,
+    MATCH_ARM,
+    ARRAY,
+    INDEX,
+    TEMPLATE,
     FUNC_DECL,
+    CLASS_DECL,
+    FIELD,
     PARAM,
     PARAM_LIST,
     ARG_LIST,
@@ -112,11 +156,19 @@ impl TokenType {
 
     pub fn is_member_type(&self) -> bool {
         match self {
-            TokenType::PUBLIC => true,
+            TokenType::PUBLIC | TokenType::PROTECTED | TokenType::PRIVATE => true,
             _ => false
         }
     }
 
+    // Tokens that start a top level declaration
+    pub fn is_declaration(&self) -> bool {
+        match self {
+            TokenType::CLASS | TokenType::STRUCT | TokenType::IMPORT => true,
+            _ => self.is_member_type()
+        }
+    }
+
     pub fn is_logical_operator(&self) -> bool {
         match self { 
             TokenType::NOT => true,
@@ -143,7 +195,21 @@ impl TokenType {
     pub fn is_assignment_operator(&self) -> bool {
         match self {
             TokenType::ASSIGN => true,
-            _ => false
+            _ => self.compound_operator().is_some()
+        }
+    }
+
+    // Arithmetic operator applied by a compound assignment
+    pub fn compound_operator(&self) -> Option<TokenType> {
+        match self {
+            TokenType::ADD_ASSIGN => Some(TokenType::PLUS),
+            TokenType::SUB_ASSIGN => Some(TokenType::DASH),
+            TokenType::MULT_ASSIGN => Some(TokenType::STAR),
+            TokenType::DIV_ASSIGN => Some(TokenType::SLASH),
+            TokenType::REM_ASSIGN => Some(TokenType::PERCENT),
+            TokenType::POWER_ASSIGN => Some(TokenType::POWER),
+            TokenType::ROOT_ASSIGN => Some(TokenType::ROOT),
+            _ => None
         }
     }
     
@@ -164,13 +230,20 @@ impl TokenType {
             TokenType::DASH => true,
             TokenType::SLASH => true,
             TokenType::NOT => true,
+            TokenType::INCREMENT => true,
+            TokenType::DECREMENT => true,
 
             _ => false
         }
     }
 
     pub fn is_postfix_operator(&self) -> bool {
-        false
+        match self {
+            TokenType::INCREMENT => true,
+            TokenType::DECREMENT => true,
+
+            _ => false
+        }
     }
 
     pub fn is_identifier(&self) -> bool {
@@ -213,8 +286,13 @@ impl TokenType {
     pub fn binding_power(&self) -> BindingPower {
         match self {
 
-            TokenType::ASSIGN => BindingPower { left: 5, right: 4, unary: 0 },
+            TokenType::ASSIGN
+            | TokenType::ADD_ASSIGN | TokenType::SUB_ASSIGN
+            | TokenType::MULT_ASSIGN | TokenType::DIV_ASSIGN | TokenType::REM_ASSIGN
+            | TokenType::POWER_ASSIGN | TokenType::ROOT_ASSIGN => BindingPower { left: 5, right: 4, unary: 0 },
 
+            // a ?? b ?? c groups to the right
+            TokenType::COALESCE => BindingPower { left: 10, right: 9, unary: 0 },
             TokenType::OR | TokenType::XOR => BindingPower { left: 15, right: 16, unary: 0 },
             TokenType::AND => BindingPower { left: 20, right: 21, unary: 0 },
 
@@ -228,9 +306,12 @@ impl TokenType {
             TokenType::PLUS | TokenType::DASH => BindingPower { left: 40, right: 41, unary: 70 },
             TokenType::STAR | TokenType::PERCENT => BindingPower { left: 50, right: 51, unary: 0 },
             TokenType::SLASH => BindingPower { left: 50, right: 51, unary: 70 },
+            // -x as float converts the negated value, a * b as float only b
+            TokenType::AS => BindingPower { left: 60, right: 0, unary: 0 },
             TokenType::POWER | TokenType::ROOT => BindingPower { left: 90, right: 89, unary: 0 },
 
             TokenType::NOT => BindingPower { left: 0, right: 0, unary: 70 },
+            TokenType::INCREMENT | TokenType::DECREMENT => BindingPower { left: 0, right: 0, unary: 70 },
 
             TokenType::PAREN_L => BindingPower { left: 100, right: 0, unary: 0 },
             TokenType::PERIOD => BindingPower { left: 100, right: 99, unary: 0 },
@@ -3,4 +3,6 @@ pub mod lexer;
 pub mod parser;
 pub mod converter;
 pub mod ir;
-pub mod analyzer;
\ No newline at end of file
+pub mod analyzer;
+pub mod interp;
+pub mod module;
\ No newline at end of file
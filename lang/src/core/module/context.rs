@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+
+// Names visible while compiling one module, imports map the alias used in the file to the module name
+#[derive(Debug, Clone, Default)]
+pub struct ModuleContext {
+    pub name: String,
+    pub imports: HashMap<String, String>
+}
+
+impl ModuleContext {
+    pub fn new(name: &str, imports: &[(String, String)]) -> ModuleContext {
+        ModuleContext {
+            name: name.to_string(),
+            imports: imports.iter().cloned().collect()
+        }
+    }
+
+    // Functions of the entry module keep their name, everything else is prefixed with its module
+    pub fn link_name(module: &str, name: &str) -> String {
+        if module.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", module, name)
+        }
+    }
+
+    pub fn qualify(&self, name: &str) -> String {
+        Self::link_name(&self.name, name)
+    }
+
+    // Linked name of alias.name when alias is an import of this module
+    pub fn imported(&self, alias: &str, name: &str) -> Option<String> {
+        self.imports
+            .get(alias)
+            .map(|module| Self::link_name(module, name))
+    }
+}
@@ -0,0 +1,253 @@
+use std::path::{Path, PathBuf};
+
+use crate::core::converter::converter::Converter;
+use crate::core::converter::stree::STree;
+use crate::core::lexer::lexer::Lexer;
+use crate::core::parser::parser::Parser;
+use crate::core::util::diagnostic::{codes, Diagnostic};
+use crate::core::util::location::Span;
+use crate::core::util::logger::Logger;
+use crate::core::util::source::SourceMap;
+
+// Loads a program and every module it imports
+// Imports resolve relative to the importing file, import a.b; reads a/b.ohl
+pub struct Loader {
+    log: Logger,
+    _debug: bool,
+    // Directory of the entry file, module names are dotted paths relative to it
+    root: PathBuf,
+    // Loaded modules, dependencies first
+    modules: Vec<STree>,
+    // Files being loaded, innermost last, to find import cycles
+    loading: Vec<(PathBuf, String)>,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl Loader {
+    pub fn new(_debug: bool) -> Loader {
+        Loader {
+            log: Logger::new(_debug),
+            _debug,
+            root: PathBuf::new(),
+            modules: Vec::new(),
+            loading: Vec::new(),
+            diagnostics: Vec::new()
+        }
+    }
+
+    // Program root holding one MODULE per file, the entry module is last and unnamed
+    pub fn load(&mut self, path: &Path, sources: &mut SourceMap) -> STree {
+        self.log.info("load_program()");
+        self.log.indent_inc();
+
+        self.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let span = self.load_module(path, String::new(), sources);
+
+        self.log.indent_dec();
+        STree::START { functions: std::mem::take(&mut self.modules), span: span.unwrap_or_default() }
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+
+    fn load_module(&mut self, path: &Path, name: String, sources: &mut SourceMap) -> Option<Span> {
+        self.log.info(&format!("load_module({})", path.display()));
+        self.log.indent_inc();
+
+        let contents = std::fs::read_to_string(path).ok()?;
+        let file_id = sources.add(path.display().to_string(), contents.clone());
+
+        let mut lexer = Lexer::new(contents);
+        lexer.set_file_id(file_id);
+        let mut parser = Parser::new(lexer, self._debug);
+        let mtree = parser.analyze();
+
+        // A file with syntax errors is not converted, but the rest of the program still loads
+        let diagnostics = parser.take_diagnostics();
+        let failed = diagnostics.iter().any(Diagnostic::is_error);
+        self.diagnostics.extend(diagnostics);
+        if failed {
+            self.log.indent_dec();
+            return None;
+        }
+
+        let tree = match Converter::new(self._debug).convert_tree(&mtree) {
+            Ok(tree) => tree,
+            Err(diagnostic) => {
+                self.diagnostics.push(diagnostic);
+                self.log.indent_dec();
+                return None;
+            }
+        };
+        let STree::START { functions, span } = tree else {
+            self.log.indent_dec();
+            return None;
+        };
+
+        let display = match name.is_empty() {
+            true => path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+            false => name.clone(),
+        };
+        self.loading.push((path.to_path_buf(), display));
+
+        let mut imports: Vec<(String, String)> = Vec::new();
+        let mut declarations = Vec::new();
+        for declaration in functions {
+            let STree::IMPORT { path: segments, span: import_span } = &declaration else {
+                declarations.push(declaration);
+                continue;
+            };
+
+            let alias = segments.last().cloned().unwrap_or_default();
+            if imports.iter().any(|(existing, _)| *existing == alias) {
+                self.diagnostics.push(
+                    Diagnostic::error(codes::DUPLICATE_IMPORT, format!("Module name '{}' is imported twice", alias), *import_span)
+                        .with_label("imported again here")
+                        .with_help("modules are accessed by the last segment of their path, which must be unique in a file")
+                );
+                continue;
+            }
+
+            let mut file = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            file.extend(segments);
+            file.set_extension("ohl");
+
+            if let Some(position) = self.loading.iter().position(|(loading, _)| *loading == file) {
+                let mut cycle: Vec<String> = self.loading[position..].iter().map(|(_, module)| module.clone()).collect();
+                cycle.push(self.loading[position].1.clone());
+                self.diagnostics.push(
+                    Diagnostic::error(codes::IMPORT_CYCLE, format!("Import cycle: {}", cycle.join(" -> ")), *import_span)
+                        .with_label("imported here")
+                        .with_note("modules cannot import each other, move the shared functions into a third module")
+                );
+                continue;
+            }
+
+            if !file.is_file() {
+                self.diagnostics.push(
+                    Diagnostic::error(codes::UNRESOLVED_IMPORT, format!("Cannot find module '{}'", segments.join(".")), *import_span)
+                        .with_label(format!("expected {}", file.display()))
+                );
+                continue;
+            }
+
+            let module = self.module_name(&file);
+            let loaded = self.modules.iter().any(|loaded| matches!(loaded, STree::MODULE { name, .. } if *name == module));
+            if !loaded {
+                self.load_module(&file, module.clone(), sources);
+            }
+            imports.push((alias, module));
+        }
+
+        self.loading.pop();
+        self.modules.push(STree::MODULE { name, imports, declarations, span });
+
+        self.log.indent_dec();
+        Some(span)
+    }
+
+    // a/b.ohl below the entry directory is module a.b
+    fn module_name(&self, file: &Path) -> String {
+        let relative = file.strip_prefix(&self.root).unwrap_or(file).with_extension("");
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes the files of a program to a fresh directory and loads main.ohl from it
+    fn load(test: &str, files: &[(&str, &str)]) -> (STree, Vec<Diagnostic>) {
+        let root = std::env::temp_dir().join(format!("ohl_loader_{}_{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, contents) in files {
+            let file = root.join(path);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, contents).unwrap();
+        }
+
+        let mut loader = Loader::new(false);
+        let program = loader.load(&root.join("main.ohl"), &mut SourceMap::new());
+        let _ = std::fs::remove_dir_all(&root);
+        (program, loader.take_diagnostics())
+    }
+
+    fn modules(program: &STree) -> Vec<(String, Vec<(String, String)>)> {
+        let STree::START { functions, .. } = program else { return Vec::new() };
+        functions
+            .iter()
+            .filter_map(|module| match module {
+                STree::MODULE { name, imports, .. } => Some((name.clone(), imports.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn imports(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(alias, module)| (alias.to_string(), module.to_string())).collect()
+    }
+
+    #[test]
+    fn dependencies_load_first_and_once() {
+        let (program, diagnostics) = load("dependencies", &[
+            ("main.ohl", "import util.geo;\nimport util.shapes;\npublic int main() { return geo.area(2); }"),
+            ("util/geo.ohl", "import shapes;\npublic int area(int r) { return r * r; }"),
+            ("util/shapes.ohl", "public int sides() { return 4; }"),
+        ]);
+
+        assert_eq!(diagnostics, Vec::new());
+        assert_eq!(modules(&program), vec![
+            ("util.shapes".to_string(), Vec::new()),
+            ("util.geo".to_string(), imports(&[("shapes", "util.shapes")])),
+            (String::new(), imports(&[("geo", "util.geo"), ("shapes", "util.shapes")])),
+        ]);
+    }
+
+    #[test]
+    fn import_cycles_are_reported() {
+        let (program, diagnostics) = load("cycle", &[
+            ("main.ohl", "import a;\npublic int main() { return 0; }"),
+            ("a.ohl", "import b;\npublic int f() { return 1; }"),
+            ("b.ohl", "import a;\npublic int g() { return 2; }"),
+        ]);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::IMPORT_CYCLE);
+        assert_eq!(diagnostics[0].message, "Import cycle: a -> b -> a");
+        // The rest of the program still loads
+        assert_eq!(modules(&program).len(), 3);
+    }
+
+    #[test]
+    fn duplicate_aliases_are_reported() {
+        let (program, diagnostics) = load("duplicate", &[
+            ("main.ohl", "import x.geo;\nimport y.geo;\npublic int main() { return 0; }"),
+            ("x/geo.ohl", "public int f() { return 1; }"),
+            ("y/geo.ohl", "public int f() { return 2; }"),
+        ]);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::DUPLICATE_IMPORT);
+        assert_eq!(modules(&program).last().unwrap().1, imports(&[("geo", "x.geo")]));
+    }
+
+    #[test]
+    fn missing_modules_are_reported() {
+        let (_, diagnostics) = load("missing", &[
+            ("main.ohl", "import nowhere;\npublic int main() { return 0; }"),
+        ]);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::UNRESOLVED_IMPORT);
+    }
+}
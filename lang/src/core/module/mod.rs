@@ -0,0 +1,2 @@
+pub mod context;
+pub mod loader;
\ No newline at end of file
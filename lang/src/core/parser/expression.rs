@@ -2,68 +2,128 @@ use crate::core::parser::mtree::MTree;
 use crate::core::parser::parser::Parser;
 use crate::core::lexer::token::Token;
 use crate::core::lexer::token_type::TokenType;
-use crate::core::util::error::Error;
+use crate::core::util::diagnostic::{codes, Diagnostic};
 
 // Pratt Expression Parser
 
 impl Parser {
-    pub fn parse_expression(&mut self) -> MTree {
+    pub fn parse_expression(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_expression()");
         self.log.indent_inc();
 
-        let child = self.parse_expression_token(1);
+        let child = self.parse_expression_token(1)?;
         self.log.indent_dec();
-        child
+        Ok(child)
     }
 
-    fn parse_expression_token(&mut self, rbl: u8) -> MTree {
+    fn parse_expression_token(&mut self, rbl: u8) -> Result<MTree, Diagnostic> {
         let token = self.current();
-        let token_type = token.token_type;
+        let token_type = token.token_type.clone();
 
         if token_type.is_prefix_operator() {
-            let tree_prefix = self.parse_prefix_expression();
+            let tree_prefix = self.parse_prefix_expression()?;
             self.parse_infix_expression(tree_prefix, rbl)
         } else if token_type == TokenType::PAREN_L {
-            let tree_parens = self.parse_parenthesis_expression();
+            let tree_parens = self.parse_parenthesis_expression()?;
             self.parse_infix_expression(tree_parens, rbl)
-        } else if token_type.is_identifier() || token_type.is_literal() {
-            let tree_atom = self.parse_atom_expression();
+        } else if token_type == TokenType::BRACKET_L {
+            let tree_array = self.parse_array_expression()?;
+            self.parse_infix_expression(tree_array, rbl)
+        } else if let TokenType::TEMPLATE_START { .. } = token_type {
+            let tree_template = self.parse_template_expression()?;
+            self.parse_infix_expression(tree_template, rbl)
+        } else if token_type.is_identifier() || token_type.is_literal() || token_type == TokenType::THIS {
+            let tree_atom = self.parse_atom_expression()?;
             self.parse_infix_expression(tree_atom, rbl)
         } else {
-            Error::new(token.location.get_line(), token.location.get_col(), "Invalid Expression".to_string()).report();
-            MTree::new(Token::new(TokenType::ERROR, token.location))
+            Err(
+                Diagnostic::error(codes::INVALID_EXPRESSION, "Invalid Expression", token.span)
+                    .with_label("expected an expression")
+            )
         }
     }
 
-    fn parse_prefix_expression(&mut self) -> MTree {
+    fn parse_prefix_expression(&mut self) -> Result<MTree, Diagnostic> {
         let token = self.current();
         self.advance();
-        let child = self.parse_expression_token(token.token_type.binding_power().unary);
-        MTree{ token, children: vec![child]}
+        let child = self.parse_expression_token(token.token_type.binding_power().unary)?;
+        Ok(MTree::with_children(token, vec![child]))
     }
 
-    fn parse_parenthesis_expression(&mut self) -> MTree {
-        self.expect(TokenType::PAREN_L);
-        let child = self.parse_expression();
-        self.expect(TokenType::PAREN_R);
-        child
+    fn parse_parenthesis_expression(&mut self) -> Result<MTree, Diagnostic> {
+        self.expect(TokenType::PAREN_L)?;
+        let mut child = self.parse_expression()?;
+        self.expect(TokenType::PAREN_R)?;
+        child.extend(self.previous());
+        Ok(child)
     }
 
-    fn parse_atom_expression(&mut self) -> MTree {
+    // [ element, ... ]
+    fn parse_array_expression(&mut self) -> Result<MTree, Diagnostic> {
+        let mut node = MTree::new(Token::using_location(TokenType::ARRAY, self.current()));
+
+        self.expect(TokenType::BRACKET_L)?;
+        if !self.is(TokenType::BRACKET_R) {
+            node._push(self.parse_expression()?);
+            while self.accept(TokenType::COMMA) {
+                node._push(self.parse_expression()?);
+            }
+        }
+        self.expect(TokenType::BRACKET_R)?;
+        node.extend(self.previous());
+        Ok(node)
+    }
+
+    // Expected Children: [ text, value, text, ..., text ]
+    fn parse_template_expression(&mut self) -> Result<MTree, Diagnostic> {
+        let mut node = MTree::new(Token::using_location(TokenType::TEMPLATE, self.current()));
+
+        let mut part = self.current();
+        loop {
+            let (TokenType::TEMPLATE_START { value } | TokenType::TEMPLATE_MIDDLE { value }) = part.token_type.clone() else {
+                break;
+            };
+            self.advance();
+            node._push(MTree::new(Token::using_location(TokenType::LIT_STRING { value }, part)));
+            node._push(self.parse_expression()?);
+            part = self.current();
+        }
+
+        let TokenType::TEMPLATE_END { value } = part.token_type.clone() else {
+            return Err(
+                Diagnostic::error(
+                    codes::EXPECTED_TOKEN,
+                    format!("Expected '}}' to close the interpolation, found '{:?}'", part.token_type),
+                    part.span
+                ).with_label("expected '}'")
+            );
+        };
+        self.advance();
+        node._push(MTree::new(Token::using_location(TokenType::LIT_STRING { value }, part)));
+        Ok(node)
+    }
+
+    fn parse_atom_expression(&mut self) -> Result<MTree, Diagnostic> {
         let atom = self.current();
         self.advance();
-        MTree::new(atom)
+        Ok(MTree::new(atom))
     }
 
 
 
-    fn parse_infix_expression(&mut self, mut left: MTree, rbl: u8) -> MTree {
+    fn parse_infix_expression(&mut self, mut left: MTree, rbl: u8) -> Result<MTree, Diagnostic> {
         loop {
             let current = self.current();
 
             // call
             if current.token_type == TokenType::PAREN_L {
-                left = self.parse_call_expression(left);
+                left = self.parse_call_expression(left)?;
+                continue;
+            }
+
+            // index
+            if current.token_type == TokenType::BRACKET_L {
+                left = self.parse_index_expression(left)?;
                 continue;
             }
 
@@ -71,46 +131,80 @@ impl Parser {
             if current.token_type == TokenType::PERIOD {
                 self.advance();
                 let id = self.current();
-                self.expect(TokenType::id());
+                self.expect(TokenType::id())?;
 
-                left = MTree {
-                    token: Token::using_location(TokenType::PERIOD, current),
-                    children: vec![left, MTree::new(id)],
-                };
+                left = MTree::with_children(
+                    Token::using_location(TokenType::PERIOD, current),
+                    vec![left, MTree::new(id)]
+                );
                 continue;
             }
 
             // postfix ops
             if current.token_type.is_postfix_operator() {
                 self.advance();
-                left = MTree { token: current, children: vec![left] };
+                left = MTree::with_children(current, vec![left]);
                 continue;
             }
 
             // infix ops
             if rbl > current.token_type.binding_power().left {
-                return left;
+                return Ok(left);
+            }
+
+            // cast, the right side is a type
+            if current.token_type == TokenType::AS {
+                left = self.parse_cast_expression(left)?;
+                continue;
             }
 
             self.advance();
 
 
-            let right = self.parse_expression_token(current.token_type.binding_power().right);
-            left = MTree { token: current, children: vec![left, right] };
+            let right = self.parse_expression_token(current.token_type.binding_power().right)?;
+            left = MTree::with_children(current, vec![left, right]);
         }
     }
 
-    fn parse_call_expression(&mut self, callee: MTree) -> MTree {
+    // Expected Children: [ value, type ]
+    fn parse_cast_expression(&mut self, value: MTree) -> Result<MTree, Diagnostic> {
+        let mut node = MTree::new(self.current());
+        self.expect(TokenType::AS)?;
+
+        let type_token = self.current();
+        self.expect_type(false, false)?;
+        let type_node = self.parse_type_suffix(MTree::new(type_token))?;
+
+        node._push(value);
+        node._push(type_node);
+        Ok(node)
+    }
+
+    // Expected Children: [ array, index ]
+    fn parse_index_expression(&mut self, array: MTree) -> Result<MTree, Diagnostic> {
+        let mut node = MTree::new(Token::using_location(TokenType::INDEX, array.token.clone()));
+
+        node._push(array);
+
+        self.expect(TokenType::BRACKET_L)?;
+        node._push(self.parse_expression()?);
+        self.expect(TokenType::BRACKET_R)?;
+        node.extend(self.previous());
+        Ok(node)
+    }
+
+    fn parse_call_expression(&mut self, callee: MTree) -> Result<MTree, Diagnostic> {
         let mut node = MTree::new(Token::using_location(TokenType::CALL, callee.token.clone()));
 
         node.children.push(callee);
 
-        self.expect(TokenType::PAREN_L);
+        self.expect(TokenType::PAREN_L)?;
 
-        node._push(self.parse_argument_list());
+        node._push(self.parse_argument_list()?);
 
-        self.expect(TokenType::PAREN_R);
-        node
+        self.expect(TokenType::PAREN_R)?;
+        node.extend(self.previous());
+        Ok(node)
     }
 
 }
\ No newline at end of file
@@ -1,7 +1,9 @@
 use crate::core::lexer::token::Token;
+use crate::core::util::location::Span;
 
 pub struct MTree {
     pub token: Token,
+    pub span: Span,
     pub children: Vec<MTree>
 }
 
@@ -10,15 +12,30 @@ pub struct MTree {
 impl MTree {
     pub fn new(token: Token) -> MTree {
         MTree {
+            span: token.span,
             token,
             children: vec![]
         }
     }
 
+    pub fn with_children(token: Token, children: Vec<MTree>) -> MTree {
+        let mut tree = MTree::new(token);
+        for child in children {
+            tree._push(child);
+        }
+        tree
+    }
+
     pub fn _push(&mut self, tree: MTree) {
+        self.span = self.span.to(tree.span);
         self.children.push(tree);
     }
 
+    // Grow the node to cover tokens that are not part of its children, like closing braces
+    pub fn extend(&mut self, span: Span) {
+        self.span = self.span.to(span);
+    }
+
     pub fn node_string(&self, print_whole: bool) -> String {
         if print_whole {
             format!("{:?}", self.token)
@@ -39,4 +56,4 @@ impl MTree {
     pub fn print(&self, print_whole: bool) {
         self.print_recursively(0, print_whole);
     }
-}
\ No newline at end of file
+}
@@ -3,24 +3,60 @@ use crate::core::lexer::token_type::TokenType;
 use crate::core::util::logger::Logger;
 use crate::core::parser::mtree::MTree;
 use crate::core::lexer::token::Token;
+use crate::core::util::diagnostic::{codes, Diagnostic};
+use crate::core::util::location::Span;
 
 
 pub struct Parser {
     lexer: Lexer,
-    pub log: Logger
+    pub log: Logger,
+    // Allows the final statement of the input to omit its semicolon
+    open_end: bool,
+    // Span of the last consumed token
+    previous: Span,
+    // Syntax errors recovered from while parsing
+    diagnostics: Vec<Diagnostic>,
+    // A statement that is the body of a match arm may also end at ',' or '}'
+    pub arm_end: bool
 }
 
 impl Parser {
     pub fn new(lexer: Lexer, _debug: bool) -> Parser {
         let log = Logger::new(_debug);
-        Parser { lexer, log }
+        Parser { lexer, log, open_end: false, previous: Span::empty(), diagnostics: Vec::new(), arm_end: false }
     }
 
+    // Always produces a tree, syntax errors leave ERROR nodes behind
     pub fn analyze(&mut self) -> MTree {
         self.advance();
-        let tree = self.parse();
-        self.expect(TokenType::EOI);
-        tree
+        self.parse()
+    }
+
+    pub fn analyze_line(&mut self) -> MTree {
+        self.open_end = true;
+        self.advance();
+        self.parse_line()
+    }
+
+    // Lexer and parser errors, in the order they were found
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.lexer.take_diagnostics();
+        diagnostics.append(&mut self.diagnostics);
+        diagnostics
+    }
+
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn describe(token_type: &TokenType) -> String {
+        match token_type {
+            TokenType::ID { name } if name.is_empty() => "identifier".to_string(),
+            TokenType::ID { name } => format!("identifier '{}'", name),
+            TokenType::LIT_INT { .. } => "integer literal".to_string(),
+            TokenType::EOI => "end of input".to_string(),
+            other => format!("'{:?}'", other),
+        }
     }
 }
 
@@ -31,48 +67,102 @@ impl Parser {
     }
 
     pub fn advance(&mut self) {
+        self.previous = self.lexer.current().span;
         self.lexer.advance();
     }
 
+    pub fn previous(&self) -> Span {
+        self.previous
+    }
+
     pub fn is(&self, token: TokenType) -> bool {
         self.lexer.current().token_type == token
     }
 
-    pub fn expect(&mut self, token: TokenType) {
+    pub fn expect(&mut self, token: TokenType) -> Result<(), Diagnostic> {
         let current = self.current();
         if std::mem::discriminant(&current.token_type) == std::mem::discriminant(&token) {
             self.log.info(&format!("expect({current:?})"));
             self.advance();
+            Ok(())
         } else {
-            panic!("Expected '{token:?}', currently '{:?}'!", current.token_type);
+            Err(
+                Diagnostic::error(
+                    codes::EXPECTED_TOKEN,
+                    format!("Expected {}, found {}", Self::describe(&token), Self::describe(&current.token_type)),
+                    current.span
+                ).with_label(format!("expected {}", Self::describe(&token)))
+            )
         }
     }
 
-    pub fn expect_type(&mut self, allow_null: bool, implicit: bool) {
-        let current = self.current().token_type;
-        if current.is_type(implicit) {
+    pub fn expect_type(&mut self, allow_null: bool, implicit: bool) -> Result<(), Diagnostic> {
+        let token = self.current();
+        let current = token.token_type.clone();
+        // Class names are types as well
+        if current.is_type(implicit) || current.is_identifier() {
             self.log.info(&format!("expect({current:?})"));
             self.advance();
+            Ok(())
         } else {
             if current == TokenType::NULL && allow_null {
                 self.log.info("expect(NULL)");
                 self.advance();
+                Ok(())
             } else {
-                panic!("Expected variable type, current token is '{current:?}'!");
+                Err(
+                    Diagnostic::error(
+                        codes::EXPECTED_TYPE,
+                        format!("Expected variable type, found {}", Self::describe(&current)),
+                        token.span
+                    ).with_label("expected a type")
+                    .with_note("types are int, float, char, string, boolean and class names")
+                )
             }
         }
     }
 
-    pub fn expect_function_type(&mut self) {
-        let current = self.current().token_type;
+    pub fn expect_function_type(&mut self) -> Result<(), Diagnostic> {
+        let token = self.current();
+        let current = token.token_type.clone();
         if current.is_member_type() {
             self.log.info(&format!("expect({current:?})"));
             self.advance();
+            Ok(())
         } else {
-            panic!("Expected function type, current token is '{current:?}'!");
+            Err(
+                Diagnostic::error(
+                    codes::EXPECTED_FUNCTION_TYPE,
+                    format!("Expected function declaration, found {}", Self::describe(&current)),
+                    token.span
+                ).with_label("expected a function type")
+                .with_help("functions are declared as 'public|protected|private <type> <name>(<params>) { ... }'")
+            )
         }
     }
 
+    pub fn expect_statement_end(&mut self) -> Result<(), Diagnostic> {
+        if self.open_end && self.is(TokenType::EOI) {
+            return Ok(());
+        }
+        if self.arm_end && (self.is(TokenType::COMMA) || self.is(TokenType::BRACE_R)) {
+            return Ok(());
+        }
+
+        // A semicolon missing at the end of a line is reported as if it had been
+        // inserted, so the statement on the next line still parses
+        let current = self.current();
+        if current.token_type != TokenType::SEMICOLON && current.span.start_line > self.previous.end_line {
+            self.report(
+                Diagnostic::error(codes::EXPECTED_TOKEN, "Expected ';' at end of statement", self.previous)
+                    .with_label("expected ';' after this")
+            );
+            return Ok(());
+        }
+
+        self.expect(TokenType::SEMICOLON)
+    }
+
     pub fn accept(&mut self, token: TokenType) -> bool {
         if self.current().token_type == token {
             self.advance();
@@ -91,7 +181,21 @@ impl Parser {
         self.log.info("parse()");
         self.log.indent_inc();
         while !self.accept(TokenType::EOI) {
-            tree._push(self.parse_function());
+            let start = self.current().span;
+            let indent = self.log.get_indent();
+            let result = match self.current().token_type {
+                TokenType::CLASS | TokenType::STRUCT => self.parse_class(),
+                TokenType::IMPORT => self.parse_import(),
+                _ => self.parse_function(),
+            };
+            match result {
+                Ok(declaration) => tree._push(declaration),
+                Err(diagnostic) => {
+                    self.log.set_indent(indent);
+                    self.report(diagnostic);
+                    tree._push(self.synchronize_function(start));
+                }
+            }
         }
 
         self.log.info("");
@@ -99,42 +203,158 @@ impl Parser {
         tree
     }
 
-    pub fn parse_function(&mut self) -> MTree {
+    // REPL input: any mix of function declarations and statements
+    pub fn parse_line(&mut self) -> MTree {
+        let mut tree = MTree::new(Token::from(TokenType::START));
+        self.log.info("parse_line()");
+        self.log.indent_inc();
+        while !self.accept(TokenType::EOI) {
+            let start = self.current().span;
+            let indent = self.log.get_indent();
+            let token_type = self.current().token_type;
+            let result = if token_type.is_member_type() {
+                self.parse_function()
+            } else if token_type == TokenType::IMPORT {
+                self.parse_import()
+            } else if token_type.is_declaration() {
+                self.parse_class()
+            } else {
+                self.parse_statement()
+            };
+            match result {
+                Ok(child) => tree._push(child),
+                Err(diagnostic) => {
+                    self.log.set_indent(indent);
+                    self.report(diagnostic);
+                    tree._push(self.synchronize(start));
+                }
+            }
+        }
+        self.log.indent_dec();
+
+        tree
+    }
+
+    pub fn parse_function(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_function()");
         self.log.indent_inc();
 
         let mut child = MTree::new(Token::using_location(TokenType::FUNC_DECL, self.current()));
 
         let func_type = self.current();
-        self.expect_function_type();
+        self.expect_function_type()?;
         child._push(MTree::new(func_type));
 
         let return_type = self.current();
-        self.expect_type(true, false);
-        child._push(MTree::new(return_type));
+        self.expect_type(true, false)?;
+        child._push(self.parse_nullable(MTree::new(return_type))?);
 
         let id = self.current();
-        self.expect(TokenType::id());
+        self.expect(TokenType::id())?;
         child._push(MTree::new(id));
 
-        child._push(self.parse_parameter_list());
-        child._push(self.parse_block());
+        child._push(self.parse_parameter_list()?);
+        child._push(self.parse_block()?);
+
+        self.log.indent_dec();
+        Ok(child)
+    }
+
+    // Expected Children: [ ID(segment)... ], import path.to.module;
+    pub fn parse_import(&mut self) -> Result<MTree, Diagnostic> {
+        self.log.info("parse_import()");
+        self.log.indent_inc();
+
+        let mut child = MTree::new(self.current());
+        self.expect(TokenType::IMPORT)?;
+
+        loop {
+            let id = self.current();
+            self.expect(TokenType::id())?;
+            child._push(MTree::new(id));
+
+            if !self.accept(TokenType::PERIOD) {
+                break;
+            }
+        }
+        self.expect_statement_end()?;
+
+        self.log.indent_dec();
+        Ok(child)
+    }
+
+    // Expected Children: [ ID(name), FIELD(Type, ID)..., FUNC_DECL... ]
+    // Classes and structs are declared the same way
+    pub fn parse_class(&mut self) -> Result<MTree, Diagnostic> {
+        self.log.info("parse_class()");
+        self.log.indent_inc();
+
+        let mut child = MTree::new(Token::using_location(TokenType::CLASS_DECL, self.current()));
+
+        if !self.accept(TokenType::STRUCT) {
+            self.expect(TokenType::CLASS)?;
+        }
+
+        let id = self.current();
+        self.expect(TokenType::id())?;
+        child._push(MTree::new(id));
+
+        self.expect(TokenType::BRACE_L)?;
+        while !self.is(TokenType::BRACE_R) && !self.is(TokenType::EOI) && !self.is(TokenType::CLASS) && !self.is(TokenType::STRUCT) {
+            let start = self.current().span;
+            let indent = self.log.get_indent();
+            let result = match self.current().token_type.is_member_type() {
+                true => self.parse_function(),
+                false => self.parse_field(),
+            };
+            match result {
+                Ok(member) => child._push(member),
+                Err(diagnostic) => {
+                    self.log.set_indent(indent);
+                    self.report(diagnostic);
+                    child._push(self.synchronize(start));
+                }
+            }
+        }
+
+        self.expect(TokenType::BRACE_R)?;
+        child.extend(self.previous());
+
+        self.log.indent_dec();
+        Ok(child)
+    }
+
+    // Expected Children: [ Type, ID(name) ]
+    fn parse_field(&mut self) -> Result<MTree, Diagnostic> {
+        self.log.info("parse_field()");
+        self.log.indent_inc();
+
+        let mut child = MTree::new(Token::using_location(TokenType::FIELD, self.current()));
+
+        let type_token = self.current();
+        self.expect_type(false, false)?;
+        child._push(self.parse_type_suffix(MTree::new(type_token))?);
+
+        let id = self.current();
+        self.expect(TokenType::id())?;
+        child._push(MTree::new(id));
+        self.expect_statement_end()?;
 
         self.log.indent_dec();
-        child
+        Ok(child)
     }
 
-    pub fn parse_parameter_list(&mut self) -> MTree {
+    pub fn parse_parameter_list(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_parameter_list()");
         self.log.indent_inc();
 
         let mut child = MTree::new(Token::using_location(TokenType::PARAM_LIST, self.current()));
 
-        self.expect(TokenType::PAREN_L);
+        self.expect(TokenType::PAREN_L)?;
 
         if !self.is(TokenType::PAREN_R) {
             loop {
-                child._push(self.parse_parameter());
+                child._push(self.parse_parameter()?);
 
                 // break if no comma follows
                 if !self.accept(TokenType::COMMA) {
@@ -143,63 +363,242 @@ impl Parser {
             }
         }
 
-        self.expect(TokenType::PAREN_R);
+        self.expect(TokenType::PAREN_R)?;
+        child.extend(self.previous());
 
         self.log.indent_dec();
-        child
+        Ok(child)
     }
 
-    pub fn parse_parameter(&mut self) -> MTree {
+    pub fn parse_parameter(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_parameter()");
         self.log.indent_inc();
 
         let mut child = MTree::new(Token::using_location(TokenType::PARAM, self.current()));
 
         let type_token = self.current();
-        self.expect_type(false, false);
-        child._push(MTree::new(type_token));
+        self.expect_type(false, false)?;
+        child._push(self.parse_type_suffix(MTree::new(type_token))?);
 
         let id = self.current();
-        self.expect(TokenType::id());
+        self.expect(TokenType::id())?;
         child._push(MTree::new(id));
 
         self.log.indent_dec();
 
-        child
+        Ok(child)
     }
 
-    pub fn parse_argument_list(&mut self) -> MTree {
+    // Nullable marker and array sizes after a type, int?[3]
+    pub fn parse_type_suffix(&mut self, type_node: MTree) -> Result<MTree, Diagnostic> {
+        let type_node = self.parse_nullable(type_node)?;
+        self.parse_array_sizes(type_node)
+    }
+
+    pub fn parse_nullable(&mut self, mut type_node: MTree) -> Result<MTree, Diagnostic> {
+        let question = self.current();
+        if self.accept(TokenType::QUESTION) {
+            type_node._push(MTree::new(question));
+            type_node.extend(self.previous());
+        }
+        Ok(type_node)
+    }
+
+    // Sizes following a type become its children, int[3][2] is three arrays of two ints
+    pub fn parse_array_sizes(&mut self, mut type_node: MTree) -> Result<MTree, Diagnostic> {
+        while self.accept(TokenType::BRACKET_L) {
+            let size = self.current();
+            self.expect(TokenType::LIT_INT { value: 0 })?;
+            type_node._push(MTree::new(size));
+
+            self.expect(TokenType::BRACKET_R)?;
+            type_node.extend(self.previous());
+        }
+
+        Ok(type_node)
+    }
+
+    pub fn parse_argument_list(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_argument_list()");
         self.log.indent_inc();
 
         let mut child = MTree::new(Token::using_location(TokenType::ARG_LIST, self.current()));
 
         if !self.is(TokenType::PAREN_R) {
-            child.children.push(self.parse_expression());
+            child.children.push(self.parse_expression()?);
             while self.accept(TokenType::COMMA) {
-                child.children.push(self.parse_expression());
+                child.children.push(self.parse_expression()?);
             }
         }
 
         self.log.indent_dec();
 
-        child
+        Ok(child)
     }
 
-    pub fn parse_block(&mut self) -> MTree {
+    pub fn parse_block(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_block");
         self.log.indent_inc();
 
         let mut child = MTree::new(Token::using_location(TokenType::BLOCK, self.current()));
 
-        self.expect(TokenType::BRACE_L);
-        while !self.is(TokenType::BRACE_R) {
-            child._push(self.parse_statement());
+        self.expect(TokenType::BRACE_L)?;
+        let outer = std::mem::replace(&mut self.arm_end, false);
+        while !self.at_block_end() {
+            let start = self.current().span;
+            let indent = self.log.get_indent();
+            match self.parse_statement() {
+                Ok(statement) => child._push(statement),
+                Err(diagnostic) => {
+                    self.log.set_indent(indent);
+                    self.report(diagnostic);
+                    child._push(self.synchronize(start));
+                }
+            }
+        }
+
+        // An unclosed block keeps the statements parsed so far
+        match self.expect(TokenType::BRACE_R) {
+            Ok(_) => child.extend(self.previous()),
+            Err(diagnostic) => self.report(diagnostic),
         }
-        self.expect(TokenType::BRACE_R);
+        self.arm_end = outer;
 
         self.log.indent_dec();
 
-        child
+        Ok(child)
+    }
+
+    fn at_block_end(&self) -> bool {
+        let current = self.current().token_type;
+        current == TokenType::BRACE_R || current == TokenType::EOI || current.is_declaration()
+    }
+}
+
+// Error recovery
+impl Parser {
+
+    // Skip the rest of a broken statement: past the next ';' or balanced block,
+    // stopping before a '}' that closes the enclosing block or the next function
+    pub fn synchronize(&mut self, start: Span) -> MTree {
+        let mut error = MTree::new(Token::new(TokenType::ERROR, start));
+        let mut depth = 0;
+
+        loop {
+            let current = self.current().token_type;
+            match current {
+                TokenType::EOI => break,
+                _ if current.is_declaration() => break,
+                TokenType::BRACE_R if depth == 0 => break,
+
+                TokenType::SEMICOLON => {
+                    self.advance();
+                    error.extend(self.previous());
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                TokenType::BRACE_L => {
+                    depth += 1;
+                    self.advance();
+                    error.extend(self.previous());
+                }
+                TokenType::BRACE_R => {
+                    depth -= 1;
+                    self.advance();
+                    error.extend(self.previous());
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {
+                    self.advance();
+                    error.extend(self.previous());
+                }
+            }
+        }
+
+        self.ensure_progress(start, &mut error);
+        error
     }
-}
\ No newline at end of file
+
+    // Skip to the next function or class declaration
+    pub fn synchronize_function(&mut self, start: Span) -> MTree {
+        let mut error = MTree::new(Token::new(TokenType::ERROR, start));
+
+        while !self.is(TokenType::EOI) && !self.current().token_type.is_declaration() {
+            self.advance();
+            error.extend(self.previous());
+        }
+
+        self.ensure_progress(start, &mut error);
+        error
+    }
+
+    // A statement that failed on its first token, e.g. a stray '}', would be
+    // parsed again forever, so skip that token as part of the error
+    fn ensure_progress(&mut self, start: Span, error: &mut MTree) {
+        if self.current().span == start && !self.is(TokenType::EOI) {
+            self.advance();
+            error.extend(self.previous());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(input: &str) -> (MTree, Vec<Diagnostic>) {
+        let mut parser = Parser::new(Lexer::new(input.to_string()), false);
+        let tree = parser.analyze_line();
+        (tree, parser.take_diagnostics())
+    }
+
+    fn parse(input: &str) -> (MTree, Vec<Diagnostic>) {
+        let mut parser = Parser::new(Lexer::new(input.to_string()), false);
+        let tree = parser.analyze();
+        (tree, parser.take_diagnostics())
+    }
+
+    #[test]
+    fn stray_brace_in_line_is_skipped() {
+        let (tree, diagnostics) = parse_line("}");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(tree.children.len(), 1);
+        assert_eq!(tree.children[0].token.token_type, TokenType::ERROR);
+    }
+
+    #[test]
+    fn line_recovers_after_stray_brace() {
+        let (tree, diagnostics) = parse_line("} int x = 1;");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(tree.children.len(), 2);
+        assert_eq!(tree.children[1].token.token_type, TokenType::VAR_DECL);
+    }
+
+    #[test]
+    fn import_in_line_is_parsed() {
+        let (tree, diagnostics) = parse_line("import math;");
+        assert!(diagnostics.is_empty());
+        assert_eq!(tree.children[0].token.token_type, TokenType::IMPORT);
+    }
+
+    #[test]
+    fn import_in_class_body_is_skipped() {
+        let (_, diagnostics) = parse("class A { int x; import math; int y; } public int main() { return 0; }");
+        assert!(!diagnostics.is_empty());
+    }
+
+    #[test]
+    fn broken_statement_recovers_at_semicolon() {
+        let (_, diagnostics) = parse("public int main() { int = 1; return 0; }");
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn stray_brace_at_top_level_is_skipped() {
+        let (_, diagnostics) = parse("} public int main() { return 0; }");
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
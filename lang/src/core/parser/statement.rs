@@ -2,9 +2,10 @@ use crate::core::lexer::token::Token;
 use crate::core::lexer::token_type::TokenType;
 use crate::core::parser::parser::Parser;
 use crate::core::parser::mtree::MTree;
+use crate::core::util::diagnostic::Diagnostic;
 
 impl Parser {
-    pub fn parse_statement(&mut self) -> MTree {
+    pub fn parse_statement(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_statement()");
         self.log.indent_inc();
 
@@ -13,176 +14,377 @@ impl Parser {
 
         match token_type {
             
-            TokenType::PRINT => child = self.parse_print(),
-            TokenType::SEMICOLON => child = self.parse_blank(),
-            TokenType::BRACE_L => child = self.parse_block(),
-            TokenType::RETURN => child = self.parse_return(),
-            TokenType::IF => child = self.parse_if(),
-            TokenType::WHILE => child = self.parse_while(),
-            TokenType::DO => child = self.parse_do_while(),
-            TokenType::LOOP => child = self.parse_loop(),
+            TokenType::PRINT => child = self.parse_print()?,
+            TokenType::SEMICOLON => child = self.parse_blank()?,
+            TokenType::BRACE_L => child = self.parse_block()?,
+            TokenType::RETURN => child = self.parse_return()?,
+            TokenType::DEFER => child = self.parse_defer()?,
+            TokenType::IF => child = self.parse_if()?,
+            TokenType::WHILE => child = self.parse_while()?,
+            TokenType::DO => child = self.parse_do_while()?,
+            TokenType::LOOP => child = self.parse_loop()?,
+            TokenType::FOR => child = self.parse_for()?,
+            TokenType::MATCH => child = self.parse_match()?,
             TokenType::BREAK | TokenType::CONTINUE | TokenType::REPEAT => {
-                self.expect(token_type.clone());
-                child = MTree::new(Token::using_location(token_type.clone(), self.current()));
-                self.expect(TokenType::SEMICOLON);
+                child = MTree::new(self.current());
+                self.expect(token_type.clone())?;
+                self.expect_statement_end()?;
             }
             _ => {
                 if token_type.is_type(true) {
-                    child = self.parse_variable_declaration();
-                    self.expect(TokenType::SEMICOLON)
+                    child = self.parse_variable_declaration()?;
+                    self.expect_statement_end()?;
                 } else {
-                    child = self.parse_expression();
-                    self.expect(TokenType::SEMICOLON);
+                    child = match self.parse_expression()? {
+                        // A class name followed by a name declares an object, Point p;
+                        expression if self.current().token_type.is_identifier() => match Self::expression_to_type(&expression) {
+                            Some(type_node) => self.parse_declarator(type_node)?,
+                            None => expression,
+                        },
+                        // Point? p; and Point?[2] ps;
+                        expression if self.is(TokenType::QUESTION) => match Self::expression_to_type(&expression) {
+                            Some(type_node) if type_node.children.is_empty() => {
+                                let type_node = self.parse_type_suffix(type_node)?;
+                                self.parse_declarator(type_node)?
+                            },
+                            _ => expression,
+                        },
+                        expression => expression,
+                    };
+                    self.expect_statement_end()?;
                 }
             }
         }
         self.log.indent_dec();
 
-        child
+        Ok(child)
     }
 
-    pub fn parse_variable_declaration(&mut self) -> MTree {
+    pub fn parse_variable_declaration(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_variable_declaration()");
         self.log.indent_inc();
 
-        let mut child = MTree::new(Token::using_location(TokenType::VAR_DECL, self.current()));
-
         let token = self.current();
-        self.expect_type(false, true);
-        child._push(MTree::new(token));
+        self.expect_type(false, true)?;
+        let type_node = match token.token_type.is_type(false) {
+            true => self.parse_type_suffix(MTree::new(token))?,
+            false => MTree::new(token),
+        };
+
+        let child = self.parse_declarator(type_node)?;
+
+        self.log.indent_dec();
+
+        Ok(child)
+    }
+
+    // Expected Children: [ Type, ID(name), Option<Expression> ]
+    fn parse_declarator(&mut self, type_node: MTree) -> Result<MTree, Diagnostic> {
+        let mut child = MTree::new(Token::using_location(TokenType::VAR_DECL, type_node.token.clone()));
+        child._push(type_node);
 
         let id = self.current();
-        self.expect(TokenType::id());
+        self.expect(TokenType::id())?;
         child._push(MTree::new(id));
 
         if self.accept(TokenType::ASSIGN) {
-            child._push(self.parse_expression());
+            child._push(self.parse_expression()?);
         }
 
-        self.log.indent_dec();
+        Ok(child)
+    }
 
-        child
+    // Class types are first parsed as expressions, Point[3] is an index into Point
+    fn expression_to_type(expression: &MTree) -> Option<MTree> {
+        match &expression.token.token_type {
+            TokenType::ID { .. } if expression.children.is_empty() => Some(MTree::new(expression.token.clone())),
+            TokenType::INDEX => {
+                let mut type_node = Self::expression_to_type(expression.children.first()?)?;
+                let size = expression.children.get(1)?;
+                if !matches!(size.token.token_type, TokenType::LIT_INT { .. }) {
+                    return None;
+                }
+                type_node._push(MTree::new(size.token.clone()));
+                type_node.extend(expression.span);
+                Some(type_node)
+            },
+            _ => None,
+        }
     }
     
-    pub fn parse_print(&mut self) -> MTree {
+    pub fn parse_print(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_print()");
         self.log.indent_inc();
 
         
         let mut child = MTree::new(self.current());
-        self.expect(TokenType::PRINT);
+        self.expect(TokenType::PRINT)?;
 
-        self.expect(TokenType::PAREN_L);
+        self.expect(TokenType::PAREN_L)?;
 
-        child._push(self.parse_expression());
+        child._push(self.parse_expression()?);
 
-        self.expect(TokenType::PAREN_R);
-        self.expect(TokenType::SEMICOLON);
+        self.expect(TokenType::PAREN_R)?;
+        child.extend(self.previous());
+        self.expect_statement_end()?;
 
         self.log.indent_dec();
-        child
+        Ok(child)
     }
 
-    pub fn parse_return(&mut self) -> MTree {
+    pub fn parse_return(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_return()");
         self.log.indent_inc();
 
         let mut child = MTree::new(self.current());
 
-        self.expect(TokenType::RETURN);
+        self.expect(TokenType::RETURN)?;
         if !self.accept(TokenType::SEMICOLON) {
-            child._push(self.parse_expression());
-            self.expect(TokenType::SEMICOLON);
+            child._push(self.parse_expression()?);
+            self.expect_statement_end()?;
         }
 
         self.log.indent_dec();
 
-        child
+        Ok(child)
+    }
+
+    // Expected Children: [ Statement ], defer <statement>;
+    pub fn parse_defer(&mut self) -> Result<MTree, Diagnostic> {
+        self.log.info("parse_defer()");
+        self.log.indent_inc();
+
+        let mut child = MTree::new(self.current());
+        self.expect(TokenType::DEFER)?;
+
+        child._push(self.parse_statement()?);
+        child.extend(self.previous());
+
+        self.log.indent_dec();
+        Ok(child)
     }
 
-    pub fn parse_if(&mut self) -> MTree {
+    pub fn parse_if(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_if()");
         self.log.indent_inc();
 
         let mut child = MTree::new(self.current());
 
-        self.expect(TokenType::IF);
-        self.expect(TokenType::PAREN_L);
-        child._push(self.parse_expression());
-        self.expect(TokenType::PAREN_R);
+        self.expect(TokenType::IF)?;
+        self.expect(TokenType::PAREN_L)?;
+        child._push(self.parse_expression()?);
+        self.expect(TokenType::PAREN_R)?;
         // Then
-        child._push(self.parse_optional_block());
+        child._push(self.parse_optional_block()?);
 
         if self.accept(TokenType::ELSE) {
             if self.is(TokenType::IF) {
-                child._push(self.parse_if());
+                child._push(self.parse_if()?);
             } else {
-                child._push(self.parse_optional_block());
+                child._push(self.parse_optional_block()?);
             }
         }
 
         self.log.indent_dec();
 
-        child
+        Ok(child)
     }
 
-    pub fn parse_while(&mut self) -> MTree {
+    pub fn parse_while(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_while()");
         self.log.indent_inc();
 
         let mut child = MTree::new(self.current());
 
-        self.expect(TokenType::WHILE);
+        self.expect(TokenType::WHILE)?;
 
-        self.expect(TokenType::PAREN_L);
-        child._push(self.parse_expression());
-        self.expect(TokenType::PAREN_R);
+        self.expect(TokenType::PAREN_L)?;
+        child._push(self.parse_expression()?);
+        self.expect(TokenType::PAREN_R)?;
 
-        child._push(self.parse_optional_block());
+        child._push(self.parse_optional_block()?);
 
         self.log.indent_dec();
 
-        child
+        Ok(child)
     }
 
-    pub fn parse_do_while(&mut self) -> MTree {
+    pub fn parse_do_while(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_do_while()");
         self.log.indent_inc();
 
         let mut child = MTree::new(self.current());
 
-        self.expect(TokenType::DO);
-        child._push(self.parse_optional_block());
+        self.expect(TokenType::DO)?;
+        child._push(self.parse_optional_block()?);
 
-        self.expect(TokenType::WHILE);
+        self.expect(TokenType::WHILE)?;
 
-        self.expect(TokenType::PAREN_L);
-        child._push(self.parse_expression());
-        self.expect(TokenType::PAREN_R);
-        self.expect(TokenType::SEMICOLON);
+        self.expect(TokenType::PAREN_L)?;
+        child._push(self.parse_expression()?);
+        self.expect(TokenType::PAREN_R)?;
+        child.extend(self.previous());
+        self.expect(TokenType::SEMICOLON)?;
 
         self.log.indent_dec();
 
-        child
+        Ok(child)
     }
 
-    pub fn parse_loop(&mut self) -> MTree {
+    pub fn parse_loop(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_loop()");
         self.log.indent_inc();
 
         let mut child = MTree::new(Token::using_location(TokenType::WHILE, self.current()));
 
-        self.expect(TokenType::LOOP);
+        self.expect(TokenType::LOOP)?;
         child._push(MTree::new(Token::using_location(TokenType::TRUE, self.current())));
 
-        child._push(self.parse_block());
+        child._push(self.parse_block()?);
 
         self.log.indent_dec();
 
-        child
+        Ok(child)
+    }
+
+    // for (init; condition; step) body
+    // for id in start..end body
+    pub fn parse_for(&mut self) -> Result<MTree, Diagnostic> {
+        self.log.info("parse_for()");
+        self.log.indent_inc();
+
+        let token = self.current();
+        self.expect(TokenType::FOR)?;
+
+        let child = if self.is(TokenType::PAREN_L) {
+            self.parse_for_loop(token)?
+        } else {
+            self.parse_for_each(token)?
+        };
+
+        self.log.indent_dec();
+
+        Ok(child)
+    }
+
+    // Expected Children: [ init, condition, step, body ]
+    // A left out init or step is kept as a SEMICOLON node, a left out condition as TRUE
+    fn parse_for_loop(&mut self, token: Token) -> Result<MTree, Diagnostic> {
+        let mut child = MTree::new(token);
+
+        self.expect(TokenType::PAREN_L)?;
+
+        if self.is(TokenType::SEMICOLON) {
+            child._push(MTree::new(self.current()));
+        } else if self.current().token_type.is_type(true) {
+            child._push(self.parse_variable_declaration()?);
+        } else {
+            child._push(self.parse_expression()?);
+        }
+        self.expect(TokenType::SEMICOLON)?;
+
+        if self.is(TokenType::SEMICOLON) {
+            child._push(MTree::new(Token::using_location(TokenType::TRUE, self.current())));
+        } else {
+            child._push(self.parse_expression()?);
+        }
+        self.expect(TokenType::SEMICOLON)?;
+
+        if self.is(TokenType::PAREN_R) {
+            child._push(MTree::new(Token::using_location(TokenType::SEMICOLON, self.current())));
+        } else {
+            child._push(self.parse_expression()?);
+        }
+        self.expect(TokenType::PAREN_R)?;
+
+        child._push(self.parse_optional_block()?);
+
+        Ok(child)
+    }
+
+    // Expected Children: [ ID, RANGE(start, end), body ]
+    fn parse_for_each(&mut self, token: Token) -> Result<MTree, Diagnostic> {
+        let mut child = MTree::new(Token::using_location(TokenType::FOR_EACH, token));
+
+        let id = self.current();
+        self.expect(TokenType::id())?;
+        child._push(MTree::new(id));
+
+        self.expect(TokenType::IN)?;
+
+        let start = self.parse_expression()?;
+        let range = self.current();
+        if !self.accept(TokenType::RANGE_INCLUSIVE) {
+            self.expect(TokenType::RANGE)?;
+        }
+        let end = self.parse_expression()?;
+        child._push(MTree::with_children(range, vec![start, end]));
+
+        child._push(self.parse_optional_block()?);
+
+        Ok(child)
+    }
+
+    // match (scrutinee) { pattern => body, ..., default => body }
+    // Expected Children: [ scrutinee, MATCH_ARM(pattern, body)... ]
+    // A pattern is a literal, a RANGE(start, end) or DEFAULT, commas between arms are optional
+    pub fn parse_match(&mut self) -> Result<MTree, Diagnostic> {
+        self.log.info("parse_match()");
+        self.log.indent_inc();
+
+        let mut child = MTree::new(self.current());
+
+        self.expect(TokenType::MATCH)?;
+        self.expect(TokenType::PAREN_L)?;
+        child._push(self.parse_expression()?);
+        self.expect(TokenType::PAREN_R)?;
+
+        self.expect(TokenType::BRACE_L)?;
+        while !self.is(TokenType::BRACE_R) && !self.is(TokenType::EOI) {
+            child._push(self.parse_match_arm()?);
+            self.accept(TokenType::COMMA);
+        }
+        self.expect(TokenType::BRACE_R)?;
+        child.extend(self.previous());
+
+        self.log.indent_dec();
+
+        Ok(child)
+    }
+
+    fn parse_match_arm(&mut self) -> Result<MTree, Diagnostic> {
+        self.log.info("parse_match_arm()");
+
+        let mut child = MTree::new(Token::using_location(TokenType::MATCH_ARM, self.current()));
+
+        if self.is(TokenType::DEFAULT) {
+            child._push(MTree::new(self.current()));
+            self.expect(TokenType::DEFAULT)?;
+        } else {
+            let start = self.parse_expression()?;
+            let range = self.current();
+            if self.accept(TokenType::RANGE) || self.accept(TokenType::RANGE_INCLUSIVE) {
+                let end = self.parse_expression()?;
+                child._push(MTree::with_children(range, vec![start, end]));
+            } else {
+                child._push(start);
+            }
+        }
+
+        self.expect(TokenType::ARROW)?;
+        if self.is(TokenType::BRACE_L) {
+            child._push(self.parse_block()?);
+        } else {
+            let outer = std::mem::replace(&mut self.arm_end, true);
+            let body = self.parse_statement();
+            self.arm_end = outer;
+            child._push(body?);
+        }
+
+        Ok(child)
     }
 
-    pub fn parse_optional_block(&mut self) -> MTree {
+    pub fn parse_optional_block(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_optional_block()");
 
         if self.is(TokenType::BRACE_L) {
@@ -194,12 +396,12 @@ impl Parser {
     }
 
 
-    pub fn parse_blank(&mut self) -> MTree {
+    pub fn parse_blank(&mut self) -> Result<MTree, Diagnostic> {
         self.log.info("parse_blank()");
         let child = MTree::new(self.current());
         while self.is(TokenType::SEMICOLON) {
-            self.expect(TokenType::SEMICOLON);
+            self.expect(TokenType::SEMICOLON)?;
         }
-        child
+        Ok(child)
     }
 }
\ No newline at end of file
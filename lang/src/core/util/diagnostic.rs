@@ -0,0 +1,157 @@
+use crate::core::util::location::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    ERROR,
+    WARNING
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::ERROR => "error",
+            Severity::WARNING => "warning",
+        }
+    }
+}
+
+// Underlined range in the rendered snippet
+// The primary label marks the span of the diagnostic itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code,
+            message: message.into(),
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>, span: Span) -> Diagnostic {
+        Diagnostic::new(Severity::ERROR, code, message, span)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Span) -> Diagnostic {
+        Diagnostic::new(Severity::WARNING, code, message, span)
+    }
+
+    // Text shown under the primary span
+    pub fn with_label(mut self, message: impl Into<String>) -> Diagnostic {
+        self.labels.push(Label { span: self.span, message: message.into(), primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Diagnostic {
+        if !span.is_empty() {
+            self.labels.push(Label { span, message: message.into(), primary: false });
+        }
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::ERROR
+    }
+}
+
+// Stable diagnostic codes, grouped by the stage that reports them
+pub mod codes {
+    // Lexer
+    pub const UNRECOGNIZED_CHARACTER: &str = "E0001";
+    pub const UNTERMINATED_STRING: &str = "E0002";
+    pub const INVALID_ESCAPE: &str = "E0003";
+    pub const INVALID_CHAR: &str = "E0004";
+    pub const UNCLOSED_INTERPOLATION: &str = "E0005";
+
+    // Parser
+    pub const EXPECTED_TOKEN: &str = "E0100";
+    pub const EXPECTED_TYPE: &str = "E0101";
+    pub const EXPECTED_FUNCTION_TYPE: &str = "E0102";
+    pub const INVALID_EXPRESSION: &str = "E0103";
+
+    // Converter
+    pub const MALFORMED_TREE: &str = "E0200";
+    pub const INVALID_ASSIGNMENT_TARGET: &str = "E0201";
+    pub const INVALID_TYPE: &str = "E0202";
+
+    // Analyzer
+    pub const UNDECLARED_VARIABLE: &str = "E0300";
+    pub const IMMUTABLE_ASSIGNMENT: &str = "E0301";
+    pub const TYPE_MISMATCH: &str = "E0302";
+    pub const RETURN_MISMATCH: &str = "E0303";
+    pub const MISSING_RETURN: &str = "E0304";
+    pub const RETURN_OUTSIDE_FUNCTION: &str = "E0305";
+    pub const JUMP_OUTSIDE_LOOP: &str = "E0306";
+    pub const UNDEFINED_FUNCTION: &str = "E0307";
+    pub const NOT_CALLABLE: &str = "E0308";
+    pub const ARGUMENT_COUNT: &str = "E0309";
+    pub const ARGUMENT_TYPE: &str = "E0310";
+    pub const INVALID_OPERANDS: &str = "E0311";
+    pub const INVALID_CONDITION: &str = "E0312";
+    pub const PRINT_NULL: &str = "E0313";
+    pub const INVALID_RANGE: &str = "E0314";
+    pub const INVALID_PATTERN: &str = "E0315";
+    pub const INVALID_INDEX: &str = "E0316";
+    pub const INVALID_ARRAY: &str = "E0317";
+    pub const UNDEFINED_CLASS: &str = "E0318";
+    pub const UNDEFINED_MEMBER: &str = "E0319";
+    pub const INVALID_CLASS: &str = "E0320";
+    pub const INACCESSIBLE_FUNCTION: &str = "E0321";
+    pub const INVALID_DEFER: &str = "E0322";
+    pub const NULLABLE_VALUE: &str = "E0323";
+    pub const INVALID_CAST: &str = "E0324";
+
+    // Codegen
+    pub const CODEGEN: &str = "E0400";
+
+    // Modules
+    pub const UNRESOLVED_IMPORT: &str = "E0500";
+    pub const IMPORT_CYCLE: &str = "E0501";
+    pub const DUPLICATE_IMPORT: &str = "E0502";
+
+    // Driver
+    pub const INVALID_INPUT: &str = "E0600";
+    pub const TARGET: &str = "E0601";
+    pub const OPTIMIZATION: &str = "E0602";
+    pub const OUTPUT: &str = "E0603";
+
+    // Warnings
+    pub const UNUSED_FUNCTION: &str = "W0001";
+    pub const EMPTY_BLOCK: &str = "W0002";
+    pub const UPPERCASE_VARIABLE: &str = "W0003";
+    pub const UNNECESSARY_SEMICOLON: &str = "W0004";
+    pub const UNREACHABLE_PATTERN: &str = "W0005";
+    pub const DUPLICATE_PATTERN: &str = "W0006";
+    pub const NEVER_NULL: &str = "W0007";
+    pub const REDUNDANT_CAST: &str = "W0008";
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Location {
     line: usize,
     col: usize
@@ -24,4 +24,74 @@ impl Location {
     pub fn to_string(&self) -> String {
         format!("[{}|{}]", self.line, self.col)
     }
-}
\ No newline at end of file
+}
+
+// Source range of a token or tree node, end is inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub file_id: usize,
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize
+}
+
+impl Span {
+    pub fn new(file_id: usize, start: Location, end: Location) -> Span {
+        Span {
+            file_id,
+            start_line: start.get_line(),
+            start_col: start.get_col(),
+            end_line: end.get_line(),
+            end_col: end.get_col()
+        }
+    }
+
+    pub fn empty() -> Span {
+        Span::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start_line == 0
+    }
+
+    pub fn start(&self) -> Location {
+        Location::new(self.start_line, self.start_col)
+    }
+
+    pub fn end(&self) -> Location {
+        Location::new(self.end_line, self.end_col)
+    }
+
+    // Smallest span covering both, ignoring empty spans
+    pub fn to(&self, other: Span) -> Span {
+        if self.is_empty() {
+            return other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+
+        let start = std::cmp::min((self.start_line, self.start_col), (other.start_line, other.start_col));
+        let end = std::cmp::max((self.end_line, self.end_col), (other.end_line, other.end_col));
+
+        Span {
+            file_id: self.file_id,
+            start_line: start.0,
+            start_col: start.1,
+            end_line: end.0,
+            end_col: end.1
+        }
+    }
+
+    pub fn contains(&self, location: Location) -> bool {
+        let position = (location.get_line(), location.get_col());
+        !self.is_empty()
+            && (self.start_line, self.start_col) <= position
+            && position <= (self.end_line, self.end_col)
+    }
+
+    pub fn to_string(&self) -> String {
+        format!("[{}|{}..{}|{}]", self.start_line, self.start_col, self.end_line, self.end_col)
+    }
+}
@@ -1,3 +1,8 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set when stdout is reserved for machine readable output
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
 #[derive(Debug, Clone)]
 pub struct Logger{
     indent: usize,
@@ -14,15 +19,33 @@ impl Logger {
     }
 
     pub fn info(&self, msg: &str) {
-        if self._debug {
-            println!("{:<indent$}{:}", "", msg, indent=self.indent);
+        if !self._debug {
+            return;
+        }
+        match TO_STDERR.load(Ordering::Relaxed) {
+            true => eprintln!("{:<indent$}{:}", "", msg, indent=self.indent),
+            false => println!("{:<indent$}{:}", "", msg, indent=self.indent),
         }
     }
 
+    // Debug output of every logger goes to stderr from now on
+    pub fn use_stderr() {
+        TO_STDERR.store(true, Ordering::Relaxed);
+    }
+
     pub fn indent_inc(&mut self) {
         self.indent += Self::INDENT;
     }
     pub fn indent_dec(&mut self) {
         self.indent -= Self::INDENT;
     }
+
+    pub fn get_indent(&self) -> usize {
+        self.indent
+    }
+
+    // Restores the indent after an error skipped the matching indent_dec calls
+    pub fn set_indent(&mut self, indent: usize) {
+        self.indent = indent;
+    }
 }
\ No newline at end of file
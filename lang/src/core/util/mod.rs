@@ -1,3 +1,5 @@
 pub mod logger;
-pub mod error;
-pub mod location;
\ No newline at end of file
+pub mod location;
+pub mod source;
+pub mod diagnostic;
+pub mod render;
@@ -0,0 +1,122 @@
+use colored::{Color, Colorize};
+use crate::core::util::diagnostic::{Diagnostic, Label, Severity};
+use crate::core::util::source::SourceMap;
+
+// Render a diagnostic in the style of rustc:
+//
+// error[E0300]: Variable 'y' is not declared
+//  --> main.ohl:4:11
+//   |
+// 4 |     print(y);
+//   |           ^ not found in this scope
+//   |
+//   = help: declare it before use
+pub fn render(diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+    let color = match diagnostic.severity {
+        Severity::ERROR => Color::Red,
+        Severity::WARNING => Color::Yellow,
+    };
+
+    let mut out = format!(
+        "{}: {}",
+        format!("{}[{}]", diagnostic.severity.name(), diagnostic.code).color(color).bold(),
+        diagnostic.message.bold()
+    );
+
+    let span = diagnostic.span;
+    let file = sources.get(span.file_id).filter(|_| !span.is_empty());
+
+    let Some(file) = file else {
+        if !span.is_empty() {
+            out.push_str(&format!("\n {} {}", "-->".blue().bold(), span.start().to_string()));
+        }
+        for note in &diagnostic.notes {
+            out.push_str(&format!("\n = {}: {}", "note".bold(), note));
+        }
+        if let Some(help) = &diagnostic.help {
+            out.push_str(&format!("\n = {}: {}", "help".bold(), help));
+        }
+        return out;
+    };
+
+    // The primary span is always underlined, even without a message
+    let mut labels: Vec<Label> = diagnostic.labels
+        .iter()
+        .filter(|label| label.span.file_id == span.file_id)
+        .cloned()
+        .collect();
+    if !labels.iter().any(|label| label.primary) {
+        labels.push(Label { span, message: String::new(), primary: true });
+    }
+    labels.sort_by_key(|label| (label.span.start_line, label.span.start_col));
+
+    let mut lines: Vec<usize> = labels.iter().map(|label| label.span.start_line).collect();
+    lines.dedup();
+
+    let width = lines.iter().max().unwrap_or(&0).to_string().len();
+    let pad = " ".repeat(width);
+    let bar = "|".blue().bold();
+
+    out.push_str(&format!(
+        "\n{}{} {}:{}:{}",
+        pad, "-->".blue().bold(), file.name, span.start_line, span.start_col
+    ));
+    out.push_str(&format!("\n{} {}", pad, bar));
+
+    let mut previous: Option<usize> = None;
+    for line in lines {
+        if let Some(previous) = previous {
+            if line > previous + 1 {
+                out.push_str(&format!("\n{}", "...".blue().bold()));
+            }
+        }
+        previous = Some(line);
+
+        let text = file.line(line).unwrap_or("");
+        out.push_str(&format!(
+            "\n{} {} {}",
+            format!("{:>width$}", line, width = width).blue().bold(), bar, text
+        ));
+
+        for label in labels.iter().filter(|label| label.span.start_line == line) {
+            let start = label.span.start_col.max(1);
+            let end = if label.span.end_line == line {
+                label.span.end_col
+            } else {
+                text.chars().count()
+            };
+            let end = end.min(text.chars().count().max(start));
+            let length = (end + 1).saturating_sub(start).max(1);
+
+            // Keep tabs so the carets line up with the source
+            let mut indent: String = text
+                .chars()
+                .take(start - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            while indent.chars().count() < start - 1 {
+                indent.push(' ');
+            }
+
+            let (mark, label_color) = if label.primary { ("^", color) } else { ("-", Color::Blue) };
+            let underline = format!("{} {}", mark.repeat(length), label.message);
+
+            out.push_str(&format!(
+                "\n{} {} {}{}",
+                pad, bar, indent, underline.trim_end().color(label_color).bold()
+            ));
+        }
+    }
+
+    if !diagnostic.notes.is_empty() || diagnostic.help.is_some() {
+        out.push_str(&format!("\n{} {}", pad, bar));
+    }
+    for note in &diagnostic.notes {
+        out.push_str(&format!("\n{} {} {}: {}", pad, "=".blue().bold(), "note".bold(), note));
+    }
+    if let Some(help) = &diagnostic.help {
+        out.push_str(&format!("\n{} {} {}: {}", pad, "=".blue().bold(), "help".bold(), help));
+    }
+
+    out
+}
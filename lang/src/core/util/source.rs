@@ -0,0 +1,36 @@
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub content: String
+}
+
+impl SourceFile {
+    // Lines are numbered from 1, as in Location
+    pub fn line(&self, number: usize) -> Option<&str> {
+        if number == 0 {
+            return None;
+        }
+        self.content.lines().nth(number - 1)
+    }
+}
+
+// Source text of every loaded file, indexed by the file id in Span
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: String, content: String) -> usize {
+        self.files.push(SourceFile { name, content });
+        self.files.len() - 1
+    }
+
+    pub fn get(&self, file_id: usize) -> Option<&SourceFile> {
+        self.files.get(file_id)
+    }
+}
@@ -1,19 +1,28 @@
 use std::fmt::format;
 use std::fs::File;
 use std::io::{Write, Result};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use clap::{Parser as ClapParser, Subcommand};
+use clap::{Args, Parser as ClapParser, Subcommand, ValueEnum};
 use colored::Colorize;
 use inkwell::context::Context;
+use inkwell::targets::TargetMachine;
 use crate::core::analyzer::analyzer::Analyzer;
-use crate::core::converter::converter::Converter;
 use crate::core::converter::stree::STree;
+use crate::core::interp::interpreter::Interpreter;
 use crate::core::ir::codegen::CodeGen;
+use crate::core::ir::optimize::OptLevel;
+use crate::core::ir::target::link_executable;
 use crate::core::parser::mtree::MTree;
 use crate::core::parser::parser::Parser;
-use crate::core::util::error::Error;
 use crate::core::lexer::lexer::Lexer;
+use crate::core::module::loader::Loader;
+use crate::oo::lsp::lsp;
+use crate::oo::repl::repl;
+use crate::oo::report::{ColorChoice, MessageFormat, Reporter};
+use crate::core::util::diagnostic::{codes, Diagnostic};
+use crate::core::util::location::Span;
 
 #[derive(ClapParser)]
 #[command(name = "oo", version)]
@@ -41,6 +50,10 @@ pub enum Command {
         #[arg(short, long)]
         debug: bool,
     },
+    Lsp {
+        #[arg(short, long)]
+        debug: bool,
+    },
     Token {
         filepath: String,
     },
@@ -57,28 +70,134 @@ pub enum Command {
     Analyze {
         filepath: String,
         #[arg(short, long)]
-        debug: bool
+        debug: bool,
+        #[command(flatten)]
+        report: ReportOptions
     },
     Ir {
         filepath: String,
         #[arg(short, long)]
         debug: bool,
         #[arg(short, long)]
-        out: bool
+        out: bool,
+        #[command(flatten)]
+        options: CompileOptions,
+        #[command(flatten)]
+        report: ReportOptions
+    },
+    Run {
+        filepath: String,
+        #[arg(short, long)]
+        debug: bool,
+        #[arg(short, long)]
+        time: bool,
+        #[arg(short, long)]
+        warnings: bool,
+        #[command(flatten)]
+        options: CompileOptions
+    },
+    Exec {
+        filepath: String,
+        #[arg(short, long)]
+        debug: bool,
+        #[arg(short, long)]
+        time: bool,
+        #[arg(short, long)]
+        warnings: bool
+    },
+    Build {
+        filepath: String,
+        #[arg(short, long)]
+        debug: bool,
+        #[arg(short, long)]
+        out: Option<String>,
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "exe")]
+        emit: Vec<Emit>,
+        #[arg(long)]
+        target: Option<String>,
+        #[arg(short, long)]
+        warnings: bool,
+        #[command(flatten)]
+        options: CompileOptions,
+        #[command(flatten)]
+        report: ReportOptions
+    }
+}
+
+#[derive(Clone, Args)]
+pub struct CompileOptions {
+    #[arg(short = 'O', value_enum, default_value = "0")]
+    pub opt_level: OptLevel,
+    #[arg(long)]
+    pub dump_ir: bool,
+}
+
+#[derive(Clone, Args)]
+pub struct ReportOptions {
+    #[arg(long, value_enum, default_value = "human")]
+    pub message_format: MessageFormat,
+    #[arg(long, value_enum, default_value = "auto")]
+    pub color: ColorChoice,
+}
+
+impl ReportOptions {
+    pub fn configure(&self, reporter: &mut Reporter) {
+        reporter.set_format(self.message_format);
+        self.color.apply();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Emit {
+    Obj,
+    Asm,
+    LlvmIr,
+    LlvmBc,
+    Exe,
+}
+
+impl Emit {
+    pub fn extension(&self, triple: &str) -> &'static str {
+        match self {
+            Emit::Obj => if triple.contains("windows") { "obj" } else { "o" },
+            Emit::Asm => "s",
+            Emit::LlvmIr => "ll",
+            Emit::LlvmBc => "bc",
+            Emit::Exe => if triple.contains("windows") { "exe" } else { "" },
+        }
     }
 }
 
 pub fn handle(cli: Cli) {
+    let mut reporter = Reporter::new();
+    let reporter = &mut reporter;
+
     match cli.command {
         Command::Print { filepath, numbered } => print(filepath, numbered),
         Command::Write { filepath, content, extension } => _ = write_to_file(filepath, extension, content),
         Command::Size { filepath } => size(filepath),
         Command::Repl { debug: _debug } => repl(_debug),
-        Command::Token { filepath } => _ = tokenize(filepath, true),
-        Command::Parse { filepath, debug: _debug } => _ = parse(filepath, _debug, true),
-        Command::Convert { filepath, debug: _debug } => _ = convert(filepath, _debug, true),
-        Command::Analyze { filepath, debug: _debug } => _ = analyze(filepath, _debug),
-        Command::Ir { filepath, debug: _debug, out } => _ = ir(filepath, _debug, out),
+        Command::Lsp { debug: _debug } => lsp(_debug),
+        Command::Token { filepath } => _ = tokenize(filepath, reporter, true),
+        Command::Parse { filepath, debug: _debug } => _ = parse(filepath, reporter, _debug, true),
+        Command::Convert { filepath, debug: _debug } => _ = convert(filepath, reporter, _debug, true),
+        Command::Analyze { filepath, debug: _debug, report } => {
+            report.configure(reporter);
+            _ = analyze(filepath, reporter, _debug, false);
+            reporter.finish();
+        },
+        Command::Ir { filepath, debug: _debug, out, options, report } => {
+            report.configure(reporter);
+            _ = ir(filepath, reporter, _debug, out, options);
+            reporter.finish();
+        },
+        Command::Run { filepath, debug: _debug, time, warnings, options } => run(filepath, reporter, _debug, time, warnings, options),
+        Command::Exec { filepath, debug: _debug, time, warnings } => exec(filepath, reporter, _debug, time, warnings),
+        Command::Build { filepath, debug: _debug, out, emit, target, warnings, options, report } => {
+            report.configure(reporter);
+            build(filepath, reporter, _debug, out, emit, target, warnings, options);
+            reporter.finish();
+        },
     }
 }
 
@@ -134,82 +253,56 @@ pub fn size(path: String) {
     print!("{} bytes", data.len().to_string().cyan());
 }
 
-pub fn validate_ohl_file(path: String) {
+pub fn validate_ohl_file(path: String, reporter: &mut Reporter) {
     use std::path::Path;
 
     let p = Path::new(&path);
 
     if p.is_dir() {
-        let mut e = Error::new(0, 0, "Expected file, got directory.".to_string());
-        e.disable_location();
-        e.report();
+        reporter.fail(input_error(format!("Expected a file, got directory '{}'", path)));
     }
 
 
     match p.extension().and_then(|e| e.to_str()) {
         Some("ohl") => {}
-        _ => {
-            eprintln!(
-                "{}: expected an .ohl file, got '{}'",
-                "Error".red(),
-                path
-            );
-            std::process::exit(0);
-        }
+        _ => reporter.fail(input_error(format!("Expected an .ohl file, got '{}'", path))),
     }
 }
 
-pub fn repl(_debug: bool) {
-    let mut input: String = String::new();
-
-    let mut lexer: Lexer = Lexer::new(String::new());
-    let mut parser: Parser;
-    let mut tree: MTree;
-
-    loop {
-        print!("ohl >>> ");
-        let _ = std::io::stdout().flush();
-        std::io::stdin().read_line(&mut input).expect("Failed to read line.");
-
-        lexer.set_input(input.clone());
-
-        if _debug {
-            lexer.print_tokens();
-            lexer.reset();
-        }
-
-        parser = Parser::new(lexer.clone(), _debug);
-        tree = parser.analyze();
-        if _debug {
-            println!("\n\nParse Tree:\n");
-            tree.print(_debug);
-            println!();
-        }
-
-        if input.is_empty() {
-            break;
-        }
-
-        println!("{}", input);
-    }
+// Errors outside of any source file have no span
+fn input_error(message: String) -> Diagnostic {
+    Diagnostic::error(codes::INVALID_INPUT, message, Span::empty())
 }
 
-pub fn tokenize(path: String, _debug: bool) -> Lexer {
-    validate_ohl_file(path.clone());
-    let contents = std::fs::read_to_string(path).unwrap();
+pub fn tokenize(path: String, reporter: &mut Reporter, _debug: bool) -> Lexer {
+    validate_ohl_file(path.clone(), reporter);
+    let contents = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        reporter.fail(input_error(format!("Failed to read '{}': {}", path, e)))
+    });
+    let file_id = reporter.add_source(path, contents.clone());
+
     let mut lexer = Lexer::new(contents);
+    lexer.set_file_id(file_id);
     if _debug {
         lexer.print_tokens();
+        reporter.report_all(&lexer.take_diagnostics());
         lexer.reset();
     }
 
     lexer
 }
 
-pub fn parse(path: String, _debug: bool, print_tree: bool) -> MTree {
-    let lexer = tokenize(path, _debug);
+pub fn parse(path: String, reporter: &mut Reporter, _debug: bool, print_tree: bool) -> MTree {
+    let lexer = tokenize(path, reporter, _debug);
     let mut parser = Parser::new(lexer, _debug);
     let tree = parser.analyze();
+
+    // Every syntax error is reported before giving up on the file
+    reporter.report_all(&parser.take_diagnostics());
+    if reporter.has_errors() {
+        reporter.exit(1);
+    }
+
     if print_tree {
         println!("\n\nParse Tree:\n");
         tree.print(_debug);
@@ -219,82 +312,243 @@ pub fn parse(path: String, _debug: bool, print_tree: bool) -> MTree {
     tree
 }
 
-pub fn convert(path: String, _debug: bool, print_tree: bool) -> STree {
-    let mtree = parse(path, _debug, _debug);
-    
-    let mut converter: Converter = Converter::new(_debug);
-    let stree = match converter.convert_tree(&mtree) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("{}: Semantic Conversion Failed \n{}\n", "ERROR".red(), e.red());
-            std::process::exit(0)
-        }
-    };
+// Converts the file and every module it imports into one program tree
+pub fn convert(path: String, reporter: &mut Reporter, _debug: bool, print_tree: bool) -> STree {
+    validate_ohl_file(path.clone(), reporter);
+
+    let mut loader = Loader::new(_debug);
+    let stree = loader.load(Path::new(&path), &mut reporter.sources);
+
+    // Every syntax and import error is reported before giving up on the program
+    let failed = loader.has_errors();
+    reporter.report_all(&loader.take_diagnostics());
+    if failed || reporter.has_errors() {
+        reporter.exit(1);
+    }
 
     if print_tree {
-        println!("\n\nSemantic Tree:\n{:#?}\n", stree);
+        reporter.output(&format!("\n\nSemantic Tree:\n{:#?}\n", stree));
     }
 
     stree
 }
 
-pub fn analyze(path: String, _debug: bool) -> STree{
+pub fn analyze(path: String, reporter: &mut Reporter, _debug: bool, hide_warnings: bool) -> STree {
     let mut analyzer = Analyzer::new(_debug);
-    let stree = convert(path, _debug, _debug);
+    let stree = convert(path, reporter, _debug, _debug);
 
     let result = analyzer.analyze(stree.clone());
     match result {
         Ok(warnings) => {
-            print_vec_string(warnings.clone());
-            println!(
-                "\nAnalysis complete with {} {}",
-                warnings.len(),
-                "warning(s)".yellow()
-            );
+            if !hide_warnings {
+                reporter.report_all(&warnings);
+            }
+            if !reporter.is_json() {
+                println!(
+                    "\nAnalysis complete with {} {}",
+                    warnings.len(),
+                    "warning(s)".yellow()
+                );
+            }
         },
         Err((warnings, errors)) => {
-            print_vec_string(warnings.clone());
-            print_vec_string(errors.clone());
-            println!(
-                "\nAnalysis complete with {} {} and {} {}",
-                warnings.len(),
-                "warning(s)".yellow(),
-                errors.len(),
-                "error(s)".red()
-            );
+            if !hide_warnings {
+                reporter.report_all(&warnings);
+            }
+            reporter.report_all(&errors);
+            if !reporter.is_json() {
+                println!(
+                    "\nAnalysis complete with {} {} and {} {}",
+                    warnings.len(),
+                    "warning(s)".yellow(),
+                    errors.len(),
+                    "error(s)".red()
+                );
+            }
+            reporter.exit(1);
         }
     }
     stree
 }
 
-fn print_vec_string(strings: Vec<String>) {
-    println!();
-    for msg in strings {
-        println!("{}", msg);
-    }
-}
-
-pub fn ir(path: String, _debug: bool, out: bool) -> Result<String> {
-    let stree = analyze(path.clone(), _debug);
+pub fn ir(path: String, reporter: &mut Reporter, _debug: bool, out: bool, options: CompileOptions) -> String {
+    let stree = analyze(path.clone(), reporter, _debug, false);
 
     let context = Context::create();
     let mut codegen = CodeGen::new(&context, "ohl", _debug);
     
-    match codegen.compile(&stree) {
-        Ok(_) => println!("\nCompilation Complete"),
-        Err(e) => println!("\nCompilation Error: {:?}", e)
+    if let Err(diagnostic) = codegen.compile(&stree) {
+        // The partial module is still shown, but the command fails
+        if _debug {
+            reporter.output(&format!("\nIR: \n\n{}, ", codegen.print_ir()));
+        }
+        reporter.fail(diagnostic);
     }
 
+    if !reporter.is_json() {
+        println!("\nCompilation Complete");
+    }
+    let machine = create_target_machine(None, options.opt_level, reporter);
+    codegen.set_target(&machine);
+    optimize(&codegen, &machine, &options, reporter);
+
     let content = codegen.print_ir();
 
     if _debug {
-        println!("\nIR: \n\n{}, ", content);
+        reporter.output(&format!("\nIR: \n\n{}, ", content));
     }
 
     if out {
         let (name, _) = split_filename(&path);
-        write_to_file(name, "ll".to_string(), content.clone())?;
+        if let Err(e) = write_to_file(name.clone(), "ll".to_string(), content.clone()) {
+            reporter.fail(output_error(format!("Failed to write '{}.ll': {}", name, e)));
+        }
+        reporter.artifact(Path::new(&format!("{}.ll", name)));
+    }
+
+    content
+}
+
+
+pub fn run(path: String, reporter: &mut Reporter, _debug: bool, time: bool, hide_warnings: bool, options: CompileOptions) {
+    let stree = analyze(path.clone(), reporter, _debug, hide_warnings);
+
+    let context = Context::create();
+    let mut codegen = CodeGen::new(&context, "ohl", _debug);
+
+    if let Err(diagnostic) = codegen.compile(&stree) {
+        reporter.report(&diagnostic);
+        reporter.exit(1);
     }
 
-    Ok(content)
+    let machine = create_target_machine(None, options.opt_level, reporter);
+    codegen.set_target(&machine);
+    optimize(&codegen, &machine, &options, reporter);
+
+    println!("\nRunning {}\n", path);
+
+    let start = Instant::now();
+    let code = match codegen.run_jit(options.opt_level.codegen_level()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}: {}", "Execution Error".red(), e);
+            std::process::exit(1);
+        }
+    };
+
+    if time {
+        println!("\nCompleted execution in {:.4}s", start.elapsed().as_secs_f64());
+    }
+
+    std::process::exit(code);
+}
+
+pub fn exec(path: String, reporter: &mut Reporter, _debug: bool, time: bool, hide_warnings: bool) {
+    let stree = analyze(path.clone(), reporter, _debug, hide_warnings);
+
+    println!("\nRunning {}\n", path);
+
+    let mut interpreter = Interpreter::new(_debug);
+
+    let start = Instant::now();
+    let code = match interpreter.run(&stree) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}: {}", "Execution Error".red(), e);
+            std::process::exit(1);
+        }
+    };
+
+    if time {
+        println!("\nCompleted execution in {:.4}s", start.elapsed().as_secs_f64());
+    }
+
+    std::process::exit(code);
+}
+
+pub fn build(path: String, reporter: &mut Reporter, _debug: bool, out: Option<String>, emit: Vec<Emit>, target: Option<String>, hide_warnings: bool, options: CompileOptions) {
+    let stree = analyze(path.clone(), reporter, _debug, hide_warnings);
+
+    let context = Context::create();
+    let mut codegen = CodeGen::new(&context, "ohl", _debug);
+
+    if let Err(diagnostic) = codegen.compile(&stree) {
+        reporter.report(&diagnostic);
+        reporter.exit(1);
+    }
+
+    let machine = create_target_machine(target.as_deref(), options.opt_level, reporter);
+    codegen.set_target(&machine);
+    optimize(&codegen, &machine, &options, reporter);
+
+    let triple = machine.get_triple().as_str().to_string_lossy().to_string();
+    let (name, _) = split_filename(&path);
+
+    // A single output uses -o as-is, multiple outputs use it as the base name
+    let output_path = |kind: Emit| -> PathBuf {
+        match &out {
+            Some(o) if emit.len() == 1 => PathBuf::from(o),
+            Some(o) => Path::new(o).with_extension(kind.extension(&triple)),
+            None => PathBuf::from(&name).with_extension(kind.extension(&triple)),
+        }
+    };
+
+    let mut written = Vec::new();
+    for kind in emit.iter().copied() {
+        let output = output_path(kind);
+        let result = match kind {
+            Emit::Obj => codegen.write_object(&machine, &output),
+            Emit::Asm => codegen.write_assembly(&machine, &output),
+            Emit::LlvmIr => codegen.write_ir(&output),
+            Emit::LlvmBc => codegen.write_bitcode(&output),
+            Emit::Exe => {
+                let object = std::env::temp_dir().join(format!("{}-{}.o", name, std::process::id()));
+                let linked = codegen
+                    .write_object(&machine, &object)
+                    .and_then(|_| link_executable(&[object.as_path()], &output, &machine));
+                let _ = std::fs::remove_file(&object);
+                linked
+            }
+        };
+
+        if let Err(e) = result {
+            reporter.fail(output_error(e));
+        }
+        reporter.artifact(&output);
+        written.push(output);
+    }
+
+    if !reporter.is_json() {
+        println!("\nBuild Complete ({})", triple.cyan());
+        for output in written {
+            println!("  {}", output.display());
+        }
+    }
+}
+
+fn output_error(message: String) -> Diagnostic {
+    Diagnostic::error(codes::OUTPUT, message, Span::empty())
+}
+
+fn create_target_machine(target: Option<&str>, level: OptLevel, reporter: &mut Reporter) -> TargetMachine {
+    CodeGen::create_target_machine(target, level.codegen_level()).unwrap_or_else(|e| {
+        reporter.fail(
+            Diagnostic::error(codes::TARGET, e, Span::empty())
+                .with_help("leave out --target to build for the host")
+        )
+    })
+}
+
+fn optimize(codegen: &CodeGen, machine: &TargetMachine, options: &CompileOptions, reporter: &mut Reporter) {
+    if options.dump_ir {
+        reporter.output(&format!("\n{}\n\n{}", "IR before optimization:".cyan(), codegen.print_ir()));
+    }
+
+    if let Err(e) = codegen.optimize(machine, options.opt_level) {
+        reporter.fail(Diagnostic::error(codes::OPTIMIZATION, e, Span::empty()));
+    }
+
+    if options.dump_ir {
+        reporter.output(&format!("\n{}\n\n{}", format!("IR after optimization ({:?}):", options.opt_level).cyan(), codegen.print_ir()));
+    }
 }
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+
+use serde_json::{json, Value};
+use crate::core::analyzer::analyzer::Analyzer;
+use crate::core::analyzer::symbol::{SymbolIndex, SymbolKind};
+use crate::core::converter::converter::Converter;
+use crate::core::lexer::lexer::Lexer;
+use crate::core::parser::parser::Parser;
+use crate::core::util::diagnostic::{Diagnostic, Severity};
+use crate::core::util::location::{Location, Span};
+
+// Language server over stdio
+// Every change re-runs the lexer, parser, converter and analyzer on the whole document
+pub fn lsp(_debug: bool) {
+    let mut server = Server::new(_debug);
+    let mut input = BufReader::new(std::io::stdin().lock());
+
+    while let Some(message) = read_message(&mut input) {
+        server.handle(message);
+    }
+}
+
+struct Document {
+    text: String,
+    symbols: SymbolIndex
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    // Columns are chars, clients that cannot take utf-32 get them in utf-16 code units
+    utf16: bool,
+    _debug: bool
+}
+
+impl Server {
+    fn new(_debug: bool) -> Server {
+        Server {
+            documents: HashMap::new(),
+            shutdown: false,
+            utf16: true,
+            _debug
+        }
+    }
+
+    fn handle(&mut self, message: Value) {
+        let method = message["method"].as_str().unwrap_or("").to_string();
+        let params = &message["params"];
+
+        if self._debug {
+            eprintln!("lsp: {}", method);
+        }
+
+        // Requests carry an id and must be answered, notifications must not
+        let Some(id) = message.get("id").cloned() else {
+            self.notification(&method, params);
+            return;
+        };
+
+        let result = match method.as_str() {
+            "initialize" => {
+                let encodings = params["capabilities"]["general"]["positionEncodings"].as_array();
+                self.utf16 = !encodings.is_some_and(|encodings| encodings.iter().any(|encoding| encoding == "utf-32"));
+                Ok(json!({
+                    "capabilities": {
+                        "positionEncoding": if self.utf16 { "utf-16" } else { "utf-32" },
+                        "textDocumentSync": 1,
+                        "hoverProvider": true,
+                        "definitionProvider": true,
+                        "documentSymbolProvider": true,
+                        "completionProvider": {}
+                    },
+                    "serverInfo": { "name": "oo", "version": env!("CARGO_PKG_VERSION") }
+                }))
+            },
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            },
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/definition" => Ok(self.definition(params)),
+            "textDocument/documentSymbol" => Ok(self.document_symbols(params)),
+            "textDocument/completion" => Ok(self.completion(params)),
+            _ => Err(format!("Unsupported method '{}'", method)),
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(message) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": message }
+            }),
+        };
+        write_message(&response);
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("").to_string();
+                self.update(uri, text);
+            },
+            // Full sync, so the last change holds the whole document
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()).and_then(|change| change["text"].as_str()) {
+                    self.update(uri, text.to_string());
+                }
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                publish(&uri, Vec::new());
+            },
+            "exit" => std::process::exit(if self.shutdown { 0 } else { 1 }),
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, uri: String, text: String) {
+        let (diagnostics, symbols) = check(&text);
+
+        let diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic_json(&uri, &text, diagnostic, self.utf16))
+            .collect();
+        publish(&uri, diagnostics);
+
+        // Keep the last symbols when the file no longer converts, so completion still works while typing
+        let symbols = match (symbols, self.documents.remove(&uri)) {
+            (Some(symbols), _) => symbols,
+            (None, Some(document)) => document.symbols,
+            (None, None) => SymbolIndex::new(),
+        };
+        self.documents.insert(uri, Document { text, symbols });
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some((document, location)) = self.locate(params) else {
+            return Value::Null;
+        };
+
+        let found = match document.symbols.reference_at(location) {
+            Some(reference) => Some((reference.detail.clone(), reference.span)),
+            None => document.symbols.symbols
+                .iter()
+                .map(|symbol| (symbol, name_span(&document.text, symbol.span, &symbol.name)))
+                .find(|(_, span)| span.contains(location))
+                .map(|(symbol, span)| (symbol.detail.clone(), span)),
+        };
+
+        match found {
+            Some((detail, span)) => json!({
+                "contents": { "kind": "markdown", "value": format!("```ohl\n{}\n```", detail) },
+                "range": range(&document.text, span, self.utf16)
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some((document, location)) = self.locate(params) else {
+            return Value::Null;
+        };
+
+        match document.symbols.reference_at(location) {
+            Some(reference) => json!({
+                "uri": params["textDocument"]["uri"],
+                "range": range(&document.text, name_span(&document.text, reference.definition, &reference.name), self.utf16)
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let Some(document) = self.documents.get(uri) else {
+            return json!([]);
+        };
+
+        let symbols: Vec<Value> = document.symbols
+            .declarations()
+            .map(|declaration| json!({
+                "name": declaration.name,
+                "detail": declaration.detail,
+                "kind": match declaration.kind {
+                    SymbolKind::CLASS => 5,
+                    _ => 12,
+                },
+                "range": range(&document.text, declaration.span, self.utf16),
+                "selectionRange": range(&document.text, name_span(&document.text, declaration.span, &declaration.name), self.utf16)
+            }))
+            .collect();
+        json!(symbols)
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let Some((document, location)) = self.locate(params) else {
+            return json!([]);
+        };
+
+        let mut items: Vec<Value> = document.symbols
+            .visible(location)
+            .iter()
+            .map(|symbol| json!({
+                "label": symbol.name,
+                "kind": match symbol.kind {
+                    SymbolKind::FUNCTION => 3,
+                    SymbolKind::CLASS => 7,
+                    SymbolKind::VARIABLE | SymbolKind::PARAMETER => 6,
+                },
+                "detail": symbol.detail
+            }))
+            .collect();
+
+        items.extend(Lexer::KEYWORDS.iter().map(|keyword| json!({ "label": keyword, "kind": 14 })));
+        json!(items)
+    }
+
+    // Document and 1-based location of a text document position parameter
+    fn locate(&self, params: &Value) -> Option<(&Document, Location)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let document = self.documents.get(uri)?;
+
+        let line = params["position"]["line"].as_u64()? as usize + 1;
+        let character = params["position"]["character"].as_u64()? as usize;
+        Some((document, Location::new(line, char_column(&document.text, line, character, self.utf16))))
+    }
+}
+
+// Diagnostics of every stage, and the symbols of the analyzed tree if it converted
+fn check(text: &str) -> (Vec<Diagnostic>, Option<SymbolIndex>) {
+    let lexer = Lexer::new(text.to_string());
+    let mut parser = Parser::new(lexer, false);
+    let mtree = parser.analyze();
+    let mut diagnostics = parser.take_diagnostics();
+
+    let stree = match Converter::new(false).convert_tree(&mtree) {
+        Ok(stree) => stree,
+        Err(diagnostic) => {
+            diagnostics.push(diagnostic);
+            return (diagnostics, None);
+        }
+    };
+
+    let mut analyzer = Analyzer::new(false);
+    analyzer.index_symbols();
+    match analyzer.analyze(stree) {
+        Ok(warnings) => diagnostics.extend(warnings),
+        Err((warnings, errors)) => {
+            diagnostics.extend(warnings);
+            diagnostics.extend(errors);
+        }
+    }
+
+    (diagnostics, Some(analyzer.take_symbols()))
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) {
+    write_message(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics }
+    }));
+}
+
+fn diagnostic_json(uri: &str, text: &str, diagnostic: &Diagnostic, utf16: bool) -> Value {
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+    if let Some(help) = &diagnostic.help {
+        message.push_str(&format!("\nhelp: {}", help));
+    }
+
+    let related: Vec<Value> = diagnostic.labels
+        .iter()
+        .filter(|label| !label.primary)
+        .map(|label| json!({
+            "location": { "uri": uri, "range": range(text, label.span, utf16) },
+            "message": label.message
+        }))
+        .collect();
+
+    json!({
+        "range": range(text, diagnostic.span, utf16),
+        "severity": match diagnostic.severity {
+            Severity::ERROR => 1,
+            Severity::WARNING => 2,
+        },
+        "code": diagnostic.code,
+        "source": "oo",
+        "message": message,
+        "relatedInformation": related
+    })
+}
+
+// LSP positions are 0-based with an exclusive end, spans are 1-based and inclusive
+fn range(text: &str, span: Span, utf16: bool) -> Value {
+    json!({
+        "start": {
+            "line": span.start_line.saturating_sub(1),
+            "character": column(text, span.start_line, span.start_col, utf16)
+        },
+        "end": {
+            "line": span.end_line.saturating_sub(1),
+            "character": column(text, span.end_line, span.end_col + 1, utf16)
+        }
+    })
+}
+
+// 0-based LSP character of a 1-based char column, columns past the line count as one unit each
+fn column(text: &str, line: usize, col: usize, utf16: bool) -> usize {
+    let count = col.saturating_sub(1);
+    if !utf16 {
+        return count;
+    }
+
+    let chars = text.lines().nth(line.saturating_sub(1)).unwrap_or("").chars().take(count);
+    let (units, taken) = chars.fold((0, 0), |(units, taken), c| (units + c.len_utf16(), taken + 1));
+    units + count - taken
+}
+
+// 1-based char column of a 0-based LSP character
+fn char_column(text: &str, line: usize, character: usize, utf16: bool) -> usize {
+    if !utf16 {
+        return character + 1;
+    }
+
+    let mut units = 0;
+    let mut col = 1;
+    for c in text.lines().nth(line.saturating_sub(1)).unwrap_or("").chars() {
+        if units >= character {
+            return col;
+        }
+        units += c.len_utf16();
+        col += 1;
+    }
+    col + character.saturating_sub(units)
+}
+
+// Declaration spans cover the whole statement, so find the declared name inside it
+fn name_span(text: &str, span: Span, name: &str) -> Span {
+    let length = name.chars().count();
+
+    for (index, line) in text.lines().enumerate().skip(span.start_line.saturating_sub(1)) {
+        let number = index + 1;
+        if number > span.end_line {
+            break;
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+
+        for start in 0..chars.len() {
+            let col = start + 1;
+            if number == span.start_line && col < span.start_col {
+                continue;
+            }
+
+            let end = start + length;
+            if end <= chars.len()
+                && chars[start..end].iter().collect::<String>() == name
+                && (start == 0 || !is_word(&chars[start - 1]))
+                && chars.get(end).map_or(true, |c| !is_word(c))
+            {
+                return Span {
+                    file_id: span.file_id,
+                    start_line: number,
+                    start_col: col,
+                    end_line: number,
+                    end_col: col + length - 1
+                };
+            }
+        }
+    }
+
+    span
+}
+
+// Messages are framed by a Content-Length header and a blank line
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    // A body that is not JSON is ignored like an unknown notification
+    Some(serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn write_message(message: &Value) {
+    let body = message.to_string();
+    let mut output = std::io::stdout().lock();
+    _ = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    _ = output.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_are_utf16_units_by_default() {
+        let text = "print(\"😀\"); x";
+        // 'x' is the 13th char, after a two unit emoji
+        assert_eq!(column(text, 1, 13, true), 13);
+        assert_eq!(column(text, 1, 13, false), 12);
+        assert_eq!(char_column(text, 1, 13, true), 13);
+        assert_eq!(char_column(text, 1, 12, false), 13);
+    }
+
+    #[test]
+    fn ranges_end_after_the_last_char() {
+        let text = "int é = 1;";
+        let span = Span { file_id: 0, start_line: 1, start_col: 5, end_line: 1, end_col: 5 };
+        assert_eq!(range(text, span, true)["end"]["character"], 5);
+    }
+
+    #[test]
+    fn non_ascii_documents_are_checked() {
+        let text = "// naïve ☕\npublic int main() {\n    string s = \"é😀\";\n    return y;\n}\n";
+        let (diagnostics, symbols) = check(text);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].span.start_line, 4);
+        assert!(symbols.is_some());
+    }
+}
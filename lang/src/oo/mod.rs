@@ -1 +1,4 @@
-pub mod cli;
\ No newline at end of file
+pub mod cli;
+pub mod lsp;
+pub mod repl;
+pub mod report;
@@ -0,0 +1,397 @@
+use std::path::PathBuf;
+
+use colored::Colorize;
+use inkwell::context::Context;
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use crate::core::analyzer::analyzer::Analyzer;
+use crate::core::analyzer::scope::Scope;
+use crate::core::converter::converter::Converter;
+use crate::core::converter::stree::STree;
+use crate::core::interp::interpreter::Interpreter;
+use crate::core::interp::value::Value;
+use crate::core::ir::codegen::CodeGen;
+use crate::core::lexer::lexer::Lexer;
+use crate::core::parser::mtree::MTree;
+use crate::core::parser::parser::Parser;
+use crate::core::util::diagnostic::{codes, Diagnostic};
+use crate::core::util::location::Span;
+use crate::oo::report::Reporter;
+
+const PROMPT: &str = "ohl >>> ";
+const CONTINUE_PROMPT: &str = "    ... ";
+
+const HELP: &str = "\
+Enter statements, expressions or function declarations.
+Variables and functions persist between lines.
+
+  :tokens <input>   print the tokens of the input
+  :ast <input>      print the parse and semantic trees of the input
+  :ir               print the LLVM IR of the declared functions
+  :type <expr>      print the type of an expression
+  :help             show this message
+  :quit             exit the REPL";
+
+pub struct Repl {
+    analyzer: Analyzer,
+    scope: Scope,
+    interpreter: Interpreter,
+    // Declared functions, kept for :ir
+    functions: Vec<STree>,
+    // Every entry is kept as a source so diagnostics can point back at it
+    reporter: Reporter,
+    _debug: bool
+}
+
+pub fn repl(_debug: bool) {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("{}: Failed to start REPL: {}", "Error".red(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
+    }
+
+    println!("Ohl {} REPL, type :help for commands", env!("CARGO_PKG_VERSION"));
+
+    let mut repl = Repl::new(_debug);
+    let mut input = String::new();
+
+    loop {
+        let prompt = if input.is_empty() { PROMPT } else { CONTINUE_PROMPT };
+
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("{}: {}", "Error".red(), e);
+                break;
+            }
+        };
+
+        input.push_str(&line);
+        input.push('\n');
+
+        // Keep reading while a block is left open
+        if brace_depth(&input) > 0 {
+            continue;
+        }
+
+        let entry = std::mem::take(&mut input);
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(entry);
+
+        if let Some(command) = entry.strip_prefix(':') {
+            if !repl.command(command) {
+                break;
+            }
+        } else {
+            repl.eval(entry);
+        }
+    }
+
+    if let Some(path) = &history {
+        let _ = editor.save_history(path);
+    }
+}
+
+impl Repl {
+    pub fn new(_debug: bool) -> Repl {
+        Repl {
+            analyzer: Analyzer::new(_debug),
+            scope: Scope::new(),
+            interpreter: Interpreter::new(_debug),
+            functions: Vec::new(),
+            reporter: Reporter::new(),
+            _debug
+        }
+    }
+
+    // Runs a meta command, returns false when the REPL should exit
+    pub fn command(&mut self, command: &str) -> bool {
+        let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let argument = argument.trim();
+
+        match name {
+            "tokens" => {
+                let mut lexer = Lexer::new(argument.to_string());
+                lexer.print_tokens();
+            }
+            "ast" => {
+                let Some(tree) = self.parse(argument) else { return true };
+                println!("\nParse Tree:\n");
+                tree.print(self._debug);
+                if let Some(nodes) = self.convert(&tree) {
+                    println!("\nSemantic Tree:\n{:#?}\n", nodes);
+                }
+            }
+            "ir" => self.print_ir(),
+            "type" => self.print_type(argument),
+            "help" => println!("{}", HELP),
+            "quit" | "q" => return false,
+            _ => eprintln!("{}: Unknown command ':{}', type :help for commands", "Error".red(), name),
+        }
+
+        true
+    }
+
+    pub fn eval(&mut self, input: &str) {
+        let Some(tree) = self.parse(input) else { return };
+        let Some(nodes) = self.convert(&tree) else { return };
+
+        // Modules are resolved by the loader, which only runs on files
+        if let Some(STree::IMPORT { span, .. }) = nodes.iter().find(|node| matches!(node, STree::IMPORT { .. })) {
+            self.reporter.report(&Diagnostic::error(codes::UNRESOLVED_IMPORT, "Imports are not available in the REPL", *span));
+            return;
+        }
+
+        // Redeclaring a variable at the top level replaces it, unless the line is rejected
+        let saved_scope = self.scope.clone();
+        for node in &nodes {
+            if let STree::VAR_DECL { id, .. } = node {
+                self.scope.variables.remove(id);
+            }
+        }
+
+        match self.analyzer.analyze_line(&nodes, &mut self.scope) {
+            Ok(warnings) => self.reporter.report_all(&warnings),
+            Err((warnings, errors)) => {
+                self.scope = saved_scope;
+                self.reporter.report_all(&warnings);
+                self.reporter.report_all(&errors);
+                return;
+            }
+        }
+
+        for node in &nodes {
+            let result = match node {
+                STree::FUNCTION { name, .. } => {
+                    self.functions.retain(|f| !matches!(f, STree::FUNCTION { name: n, .. } if n == name));
+                    self.functions.push(node.clone());
+                    self.interpreter.declare_functions(node)
+                }
+
+                STree::CLASS { name, .. } => {
+                    self.functions.retain(|f| !matches!(f, STree::CLASS { name: n, .. } if n == name));
+                    self.functions.push(node.clone());
+                    self.interpreter.declare_functions(node)
+                }
+
+                STree::EXPR { .. }
+                | STree::PRFX_EXPR { .. }
+                | STree::FUNCTION_CALL { .. }
+                | STree::MEMBER_CALL { .. }
+                | STree::ID { .. }
+                | STree::LIT_INT { .. }
+                | STree::LIT_FLOAT { .. }
+                | STree::LIT_BOOL { .. }
+                | STree::LIT_CHAR { .. }
+                | STree::LIT_STRING { .. }
+                | STree::CAST { .. } => {
+                    self.interpreter.evaluate(node).map(|value| {
+                        if value != Value::NULL {
+                            println!("{}", value);
+                        }
+                    })
+                }
+
+                _ => self.interpreter.execute(node).map(|_| ()),
+            };
+
+            if let Err(e) = result {
+                eprintln!("{}: {}", "Execution Error".red(), e);
+                return;
+            }
+        }
+    }
+
+    fn parse(&mut self, input: &str) -> Option<MTree> {
+        let file_id = self.reporter.add_source("<repl>".to_string(), input.to_string());
+        let mut lexer = Lexer::new(input.to_string());
+        lexer.set_file_id(file_id);
+        let mut parser = Parser::new(lexer, self._debug);
+
+        let tree = parser.analyze_line();
+        let diagnostics = parser.take_diagnostics();
+        self.reporter.report_all(&diagnostics);
+
+        if diagnostics.iter().any(|d| d.is_error()) {
+            None
+        } else {
+            Some(tree)
+        }
+    }
+
+    fn convert(&mut self, tree: &MTree) -> Option<Vec<STree>> {
+        let mut converter = Converter::new(self._debug);
+
+        let mut nodes = Vec::new();
+        for child in &tree.children {
+            match converter.convert_tree(child) {
+                Ok(node) => nodes.push(node),
+                Err(diagnostic) => {
+                    self.reporter.report(&diagnostic);
+                    return None;
+                }
+            }
+        }
+        Some(nodes)
+    }
+
+    fn print_ir(&mut self) {
+        let context = Context::create();
+        let mut codegen = CodeGen::new(&context, "repl", self._debug);
+
+        let program = STree::START { functions: self.functions.clone(), span: Span::empty() };
+        match codegen.compile(&program) {
+            Ok(_) => println!("{}", codegen.print_ir()),
+            Err(diagnostic) => self.reporter.report(&diagnostic),
+        }
+    }
+
+    fn print_type(&mut self, input: &str) {
+        let Some(tree) = self.parse(input) else { return };
+        let Some(nodes) = self.convert(&tree) else { return };
+
+        let [node] = nodes.as_slice() else {
+            eprintln!("{}: :type expects a single expression", "Error".red());
+            return;
+        };
+
+        match self.analyzer.type_of(node, &mut self.scope) {
+            Ok(var_type) => println!("{:?}", var_type),
+            Err(errors) => self.reporter.report_all(&errors),
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".ohl_history"))
+}
+
+// Number of braces left open, ignoring strings, chars and comments
+fn brace_depth(input: &str) -> i32 {
+    let mut depth = 0;
+    // Interpolations left open, each with the braces opened inside it
+    let mut templates: Vec<i32> = Vec::new();
+    let mut chars = input.chars().peekable();
+    let mut previous = ' ';
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                depth += 1;
+                if let Some(open) = templates.last_mut() {
+                    *open += 1;
+                }
+            }
+            // Closes an interpolation, the rest of its string follows
+            '}' if templates.last() == Some(&0) => {
+                templates.pop();
+                if skip_string(&mut chars, false) {
+                    templates.push(0);
+                }
+            }
+            '}' => {
+                depth -= 1;
+                if let Some(open) = templates.last_mut() {
+                    *open -= 1;
+                }
+            }
+            'r' if chars.peek() == Some(&'"') && !(previous.is_alphanumeric() || previous == '_') => {
+                chars.next();
+                skip_string(&mut chars, true);
+            }
+            '"' => {
+                if skip_string(&mut chars, false) {
+                    templates.push(0);
+                }
+            }
+            '\'' => {
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => { chars.next(); }
+                        '\'' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for next in chars.by_ref() {
+                    if last == '*' && next == '/' {
+                        break;
+                    }
+                    last = next;
+                }
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+
+    depth
+}
+
+// Skips to the end of a string, true when it stopped at an interpolation instead
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars>, raw: bool) -> bool {
+    while let Some(next) = chars.next() {
+        match next {
+            '\\' if !raw => { chars.next(); }
+            '{' if !raw => return true,
+            '"' => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejected_redeclaration_keeps_the_old_variable() {
+        let mut repl = Repl::new(false);
+        repl.eval("int x = 1;");
+        repl.eval("int x = \"a\";");
+        let x = repl.scope.lookup("x").expect("x was dropped");
+        assert_eq!(x.var_type.keyword(), "int");
+    }
+
+    #[test]
+    fn braces_in_strings_and_chars_are_ignored() {
+        assert_eq!(brace_depth("char c = '\\'';"), 0);
+        assert_eq!(brace_depth("print(\"\\\"{\");"), 0);
+        assert_eq!(brace_depth("print(r\"C:\\{\");"), 0);
+        assert_eq!(brace_depth("if (x) { print('}');"), 1);
+    }
+
+    #[test]
+    fn interpolations_are_followed() {
+        assert_eq!(brace_depth("print(\"{f(\"}\")} done\");"), 0);
+        assert_eq!(brace_depth("if (x) { print(\"{x}\");"), 1);
+    }
+}
@@ -0,0 +1,244 @@
+use std::io::IsTerminal;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use crate::core::util::diagnostic::Diagnostic;
+use crate::core::util::logger::Logger;
+use crate::core::util::location::Span;
+use crate::core::util::render::render;
+use crate::core::util::source::SourceMap;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn apply(&self) {
+        match self {
+            ColorChoice::Always => colored::control::set_override(true),
+            ColorChoice::Never => colored::control::set_override(false),
+            // Diagnostics go to stderr, so that is the stream to check
+            ColorChoice::Auto => {
+                if !std::io::stderr().is_terminal() {
+                    colored::control::set_override(false);
+                }
+            }
+        }
+    }
+}
+
+// Prints diagnostics from every stage against the loaded sources
+pub struct Reporter {
+    pub sources: SourceMap,
+    format: MessageFormat,
+    errors: usize,
+    warnings: usize
+}
+
+impl Reporter {
+    pub fn new() -> Reporter {
+        Reporter {
+            sources: SourceMap::new(),
+            format: MessageFormat::Human,
+            errors: 0,
+            warnings: 0
+        }
+    }
+
+    pub fn set_format(&mut self, format: MessageFormat) {
+        self.format = format;
+        if format == MessageFormat::Json {
+            Logger::use_stderr();
+        }
+    }
+
+    // Human readable progress output is left out of JSON output
+    pub fn is_json(&self) -> bool {
+        self.format == MessageFormat::Json
+    }
+
+    pub fn add_source(&mut self, name: String, content: String) -> usize {
+        self.sources.add(name, content)
+    }
+
+    pub fn report(&mut self, diagnostic: &Diagnostic) {
+        if diagnostic.is_error() {
+            self.errors += 1;
+        } else {
+            self.warnings += 1;
+        }
+
+        match self.format {
+            MessageFormat::Human => eprintln!("{}\n", render(diagnostic, &self.sources)),
+            MessageFormat::Json => println!("{}", self.diagnostic_json(diagnostic)),
+        }
+    }
+
+    pub fn report_all(&mut self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            self.report(diagnostic);
+        }
+    }
+
+    // Text that is not a diagnostic, like trees and IR. JSON output keeps stdout for JSON lines only
+    pub fn output(&self, text: &str) {
+        match self.format {
+            MessageFormat::Human => println!("{}", text),
+            MessageFormat::Json => eprintln!("{}", text),
+        }
+    }
+
+    // A file written by the build
+    pub fn artifact(&self, path: &Path) {
+        if self.is_json() {
+            println!("{}", json!({ "type": "artifact", "path": path.display().to_string() }));
+        }
+    }
+
+    // Final summary object, printed once per command
+    pub fn finish(&self) {
+        self.summary(0);
+    }
+
+    // A failing exit status is never reported as a success
+    pub fn exit(&self, code: i32) -> ! {
+        self.summary(code);
+        std::process::exit(code);
+    }
+
+    // Reports the failure and exits with 1
+    pub fn fail(&mut self, diagnostic: Diagnostic) -> ! {
+        self.report(&diagnostic);
+        self.exit(1);
+    }
+
+    fn summary(&self, code: i32) {
+        if self.is_json() {
+            println!("{}", self.summary_json(code));
+        }
+    }
+
+    fn summary_json(&self, code: i32) -> Value {
+        json!({
+            "type": "summary",
+            "success": code == 0 && !self.has_errors(),
+            "errors": self.errors,
+            "warnings": self.warnings
+        })
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors > 0
+    }
+
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.warnings
+    }
+
+    fn diagnostic_json(&self, diagnostic: &Diagnostic) -> Value {
+        let file = self.sources
+            .get(diagnostic.span.file_id)
+            .filter(|_| !diagnostic.span.is_empty())
+            .map(|file| file.name.clone());
+
+        let labels: Vec<Value> = diagnostic.labels
+            .iter()
+            .map(|label| json!({
+                "span": span_json(label.span),
+                "message": label.message,
+                "primary": label.primary
+            }))
+            .collect();
+
+        json!({
+            "type": "diagnostic",
+            "file": file,
+            "span": span_json(diagnostic.span),
+            "severity": diagnostic.severity.name(),
+            "code": diagnostic.code,
+            "message": diagnostic.message,
+            "labels": labels,
+            "notes": diagnostic.notes,
+            "help": diagnostic.help
+        })
+    }
+}
+
+fn span_json(span: Span) -> Value {
+    if span.is_empty() {
+        return Value::Null;
+    }
+    json!({
+        "start": { "line": span.start_line, "column": span.start_col },
+        "end": { "line": span.end_line, "column": span.end_col }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::util::diagnostic::codes;
+    use crate::core::util::location::Location;
+
+    fn json_reporter() -> (Reporter, usize) {
+        let mut reporter = Reporter::new();
+        reporter.set_format(MessageFormat::Json);
+        let file_id = reporter.add_source("main.ohl".to_string(), "int x = y;\n".to_string());
+        (reporter, file_id)
+    }
+
+    #[test]
+    fn diagnostic_shape() {
+        let (reporter, file_id) = json_reporter();
+        let span = Span::new(file_id, Location::new(1, 9), Location::new(1, 9));
+        let diagnostic = Diagnostic::error(codes::UNDECLARED_VARIABLE, "Variable 'y' is not declared.", span)
+            .with_label("not found in this scope")
+            .with_help("declare it first");
+
+        let value = reporter.diagnostic_json(&diagnostic);
+        assert_eq!(value["type"], "diagnostic");
+        assert_eq!(value["file"], "main.ohl");
+        assert_eq!(value["severity"], "error");
+        assert_eq!(value["code"], "E0300");
+        assert_eq!(value["span"]["start"], json!({ "line": 1, "column": 9 }));
+        assert_eq!(value["labels"][0]["primary"], true);
+        assert_eq!(value["help"], "declare it first");
+    }
+
+    #[test]
+    fn driver_errors_have_no_file_or_span() {
+        let (reporter, _) = json_reporter();
+        let diagnostic = Diagnostic::error(codes::OUTPUT, "Failed to link", Span::empty());
+
+        let value = reporter.diagnostic_json(&diagnostic);
+        assert_eq!(value["file"], Value::Null);
+        assert_eq!(value["span"], Value::Null);
+    }
+
+    #[test]
+    fn summary_follows_errors_and_exit_status() {
+        let (mut reporter, _) = json_reporter();
+        assert_eq!(reporter.summary_json(0), json!({ "type": "summary", "success": true, "errors": 0, "warnings": 0 }));
+        assert_eq!(reporter.summary_json(1)["success"], false);
+
+        reporter.report(&Diagnostic::warning(codes::UNUSED_FUNCTION, "Unused", Span::empty()));
+        assert_eq!(reporter.summary_json(0)["success"], true);
+
+        reporter.report(&Diagnostic::error(codes::OUTPUT, "Failed", Span::empty()));
+        assert_eq!(reporter.summary_json(0), json!({ "type": "summary", "success": false, "errors": 1, "warnings": 1 }));
+    }
+}
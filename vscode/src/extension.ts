@@ -1,9 +1,11 @@
 import * as vscode from "vscode";
+import { LanguageClient, LanguageClientOptions, ServerOptions } from "vscode-languageclient/node";
 
+let client: LanguageClient | undefined;
 
 export function activate(context: vscode.ExtensionContext) {
 
-    vscode.commands.registerCommand("ohl.runFile", async () => {
+    context.subscriptions.push(vscode.commands.registerCommand("ohl.runFile", async () => {
         const editor = vscode.window.activeTextEditor;
         if (!editor) return;
 
@@ -18,6 +20,22 @@ export function activate(context: vscode.ExtensionContext) {
 
         terminal.show();
         terminal.sendText(`oo run "${editor.document.fileName}"`);
-    });
+    }));
 
-}
\ No newline at end of file
+    // Diagnostics, hover, go to definition, symbols and completion come from `oo lsp`
+    const serverOptions: ServerOptions = {
+        command: "oo",
+        args: ["lsp"]
+    };
+
+    const clientOptions: LanguageClientOptions = {
+        documentSelector: [{ scheme: "file", language: "ohl" }]
+    };
+
+    client = new LanguageClient("ohl", "Ohl Language Server", serverOptions, clientOptions);
+    client.start();
+}
+
+export function deactivate(): Thenable<void> | undefined {
+    return client?.stop();
+}